@@ -0,0 +1,435 @@
+use std::{collections::HashMap, rc::Rc};
+
+use druid::{
+    commands, im,
+    widget::{Button, CrossAxisAlignment, Either, Flex, Label, Scroll, SizedBox},
+    Data, FileDialogOptions, FileInfo, FileSpec, FontDescriptor, FontFamily, Selector, Widget,
+    WidgetExt,
+};
+
+use crate::{
+    canvas::CanvasState,
+    component::ComponentState,
+    kind::{ComponentKind, PinType},
+    logic::Expr,
+    sim::Netlist,
+    value::{Radix, Value},
+    wire::WireState,
+};
+
+pub const EXPORT_ANALYSIS: Selector<FileInfo> = Selector::new("logicism/export-analysis");
+
+pub const CSV: FileSpec = FileSpec::new("CSV", &["csv"]);
+pub const MARKDOWN: FileSpec = FileSpec::new("Markdown", &["md"]);
+pub const LATEX: FileSpec = FileSpec::new("LaTeX", &["tex"]);
+pub const TEXT: FileSpec = FileSpec::new("Text", &["txt"]);
+
+/// The most input bits a truth table is made for, which keeps it to a few thousand rows
+pub const MAX_TABLE_INPUTS: u8 = 12;
+
+/// Something worked out about the circuit, shown in a panel beside the canvas
+#[derive(Clone, Data)]
+pub struct Analysis {
+    pub title: String,
+    pub text: String,
+    /// The forms it can be exported in, and what goes in the file for each
+    pub exports: Rc<Vec<(FileSpec, String)>>,
+}
+
+/// An input or output of a circuit, named after its component
+#[derive(Clone, Debug)]
+pub struct Port {
+    pub id: usize,
+    pub name: String,
+    pub width: u8,
+}
+
+/// The outputs of a combinational circuit for every combination of its inputs
+pub struct TruthTable {
+    pub inputs: Vec<Port>,
+    pub outputs: Vec<Port>,
+    /// The input values and then the output values of each row, with the first input changing
+    /// slowest
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// The inputs and outputs of a circuit: its input components, and the probes that stand for its
+/// outputs as wide as the nets they're on, in the order they were placed
+pub fn ports(
+    components: &im::OrdMap<usize, ComponentState>,
+    netlist: &Netlist,
+) -> (Vec<Port>, Vec<Port>) {
+    let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+    for (&id, component) in components.iter() {
+        let name = component.instance.port_name(id);
+        match component.instance.ty.kind {
+            ComponentKind::Input { width } => inputs.push(Port { id, name, width }),
+            ComponentKind::Probe { .. } => {
+                let width = netlist.width(netlist.pins[&id][0]);
+                outputs.push(Port { id, name, width })
+            },
+            _ => {},
+        }
+    }
+    (inputs, outputs)
+}
+
+/// Runs the circuit on every combination of inputs. Fails for circuits that remember anything or
+/// have too many inputs, or if the circuit doesn't settle for some combination.
+pub fn truth_table(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Result<TruthTable, String> {
+    let netlist = Netlist::build(components, wires);
+    let (inputs, outputs) = ports(components, &netlist);
+    if let Some((id, c)) = components
+        .iter()
+        .find(|(_, c)| c.instance.ty.kind.is_sequential())
+    {
+        return Err(format!(
+            "{} holds state, so the circuit isn't combinational",
+            c.instance.name(*id)
+        ));
+    }
+    if inputs.is_empty() || outputs.is_empty() {
+        return Err("Place inputs, and probes on the outputs".to_owned());
+    }
+    let bits: u32 = inputs.iter().map(|p| p.width as u32).sum();
+    if bits > MAX_TABLE_INPUTS as u32 {
+        return Err(format!(
+            "{} input bits is too many, there can be at most {}",
+            bits, MAX_TABLE_INPUTS
+        ));
+    }
+
+    let mut rows = Vec::new();
+    for combination in 0..1u64 << bits {
+        let mut components = components.clone();
+        let mut wires = wires.clone();
+        let mut row = Vec::new();
+        let mut shift = bits;
+        for port in inputs.iter() {
+            shift -= port.width as u32;
+            let value = combination >> shift & Value::mask(port.width);
+            components.get_mut(&port.id).unwrap().state.value = value;
+            row.push(Value::new(port.width, value));
+        }
+        if netlist.settle(&mut components, &mut wires).is_err() {
+            let inputs: Vec<_> = row.iter().map(|v| v.format(Radix::Binary)).collect();
+            return Err(format!(
+                "The circuit doesn't settle for inputs {}",
+                inputs.join(" ")
+            ));
+        }
+        for port in outputs.iter() {
+            let probe = &components[&port.id];
+            row.push(
+                probe
+                    .values
+                    .get(0)
+                    .copied()
+                    .unwrap_or(Value::floating(port.width)),
+            );
+        }
+        rows.push(row);
+    }
+    Ok(TruthTable {
+        inputs,
+        outputs,
+        rows,
+    })
+}
+
+/// How many terms the expressions for a circuit's outputs may have between them before they are
+/// too big to be worth showing
+const EXPR_BUDGET: usize = 10_000;
+
+/// A walk back from the outputs of a circuit to its inputs, remembering the expression for each
+/// net it has been through so that signals that fan out and meet again are only worked out once
+struct Walk<'a> {
+    netlist: &'a Netlist,
+    components: &'a im::OrdMap<usize, ComponentState>,
+    visiting: Vec<usize>,
+    /// The expression for each net done so far, with how many terms it has
+    done: HashMap<usize, (Expr, usize)>,
+    /// How many more terms the expressions may have
+    budget: usize,
+}
+
+impl Walk<'_> {
+    /// The expression for the value on a net in terms of the circuit's inputs, found by walking
+    /// back through the gates driving it, or `None` once the expressions get too big. Anything
+    /// that isn't a gate, an input or a constant, and any feedback, stands in as a signal named
+    /// after the pin driving it.
+    fn net_expr(&mut self, net: usize) -> Option<(Expr, usize)> {
+        if let Some((expr, size)) = self.done.get(&net) {
+            self.budget = self.budget.checked_sub(*size)?;
+            return Some((expr.clone(), *size));
+        }
+        let (id, pin) = match self.netlist.drivers(net) {
+            [] => return Some((Expr::Var("Z".to_owned()), 1)),
+            &[driver] => driver,
+            _ => return Some((Expr::Var(format!("n{}", net)), 1)),
+        };
+        let instance = &self.components[&id].instance;
+        let opaque = Expr::Var(format!(
+            "{}.{}",
+            instance.name(id),
+            instance.ty.pins[pin].name
+        ));
+        if self.visiting.contains(&id) {
+            return Some((opaque, 1));
+        }
+
+        let kind = instance.ty.kind;
+        let inputs: Vec<_> = instance
+            .ty
+            .pins
+            .iter()
+            .zip(&self.netlist.pins[&id])
+            .filter(|(pin, _)| pin.ty == PinType::Input)
+            .map(|(_, &net)| net)
+            .collect();
+        let is_gate = matches!(
+            kind,
+            ComponentKind::Not
+                | ComponentKind::And
+                | ComponentKind::Or
+                | ComponentKind::Nand
+                | ComponentKind::Nor
+                | ComponentKind::Xor
+        );
+        let mut terms = Vec::new();
+        let mut size = 1;
+        if is_gate {
+            self.visiting.push(id);
+            for &input in inputs.iter() {
+                let (term, term_size) = self.net_expr(input)?;
+                terms.push(term);
+                size += term_size;
+            }
+            self.visiting.pop();
+        }
+        self.budget = self.budget.checked_sub(1)?;
+
+        let expr = match kind {
+            ComponentKind::Input { width: 1 } => Expr::Var(instance.port_name(id)),
+            ComponentKind::Constant { width: 1, value } => Expr::Const(value & 1 == 1),
+            ComponentKind::Not => Expr::not(terms.remove(0)),
+            ComponentKind::And => Expr::and(terms),
+            ComponentKind::Or => Expr::or(terms),
+            ComponentKind::Nand => Expr::not(Expr::and(terms)),
+            ComponentKind::Nor => Expr::not(Expr::or(terms)),
+            ComponentKind::Xor => Expr::xor(terms),
+            _ => opaque,
+        };
+        self.done.insert(net, (expr.clone(), size));
+        Some((expr, size))
+    }
+}
+
+/// The expression each output of the circuit implements, by output name
+pub fn expressions(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Result<Vec<(String, Expr)>, String> {
+    let netlist = Netlist::build(components, wires);
+    let (_, outputs) = ports(components, &netlist);
+    if outputs.is_empty() {
+        return Err("Place probes on the outputs".to_owned());
+    }
+    let mut walk = Walk {
+        netlist: &netlist,
+        components,
+        visiting: Vec::new(),
+        done: HashMap::new(),
+        budget: EXPR_BUDGET,
+    };
+    outputs
+        .into_iter()
+        .map(|port| {
+            let net = netlist.pins[&port.id][0];
+            match walk.net_expr(net) {
+                Some((expr, _)) => Ok((port.name, expr)),
+                None => Err(format!(
+                    "The expressions run to more than {} terms, too many to show",
+                    EXPR_BUDGET
+                )),
+            }
+        })
+        .collect()
+}
+
+impl TruthTable {
+    fn header(&self) -> Vec<&str> {
+        self.inputs
+            .iter()
+            .chain(&self.outputs)
+            .map(|port| port.name.as_str())
+            .collect()
+    }
+
+    fn cells(&self, row: &[Value]) -> Vec<String> {
+        row.iter()
+            .map(|value| value.format(Radix::Binary))
+            .collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = self.header().join(",");
+        out.push('\n');
+        for row in self.rows.iter() {
+            out.push_str(&self.cells(row).join(","));
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let header = self.header();
+        let mut out = format!("| {} |\n", header.join(" | "));
+        let rule: Vec<_> = header.iter().map(|_| "---").collect();
+        out.push_str(&format!("|{}|\n", rule.join("|")));
+        for row in self.rows.iter() {
+            out.push_str(&format!("| {} |\n", self.cells(row).join(" | ")));
+        }
+        out
+    }
+
+    /// Lines the columns up, with a bar between the inputs and outputs
+    pub fn to_text(&self) -> String {
+        let header = self.header();
+        let widths: Vec<_> = self
+            .inputs
+            .iter()
+            .chain(&self.outputs)
+            .zip(&header)
+            .map(|(port, name)| name.len().max(port.width as usize))
+            .collect();
+        let line = |cells: Vec<String>| {
+            let cells: Vec<_> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:>1$}", cell, width))
+                .collect();
+            let (inputs, outputs) = cells.split_at(self.inputs.len());
+            format!("{} | {}\n", inputs.join(" "), outputs.join(" "))
+        };
+        let mut out = line(header.iter().map(|s| s.to_string()).collect());
+        for row in self.rows.iter() {
+            out.push_str(&line(self.cells(row)));
+        }
+        out
+    }
+}
+
+impl Analysis {
+    pub fn truth_table(table: &TruthTable) -> Self {
+        Analysis {
+            title: "Truth table".to_owned(),
+            text: table.to_text(),
+            exports: Rc::new(vec![(CSV, table.to_csv()), (MARKDOWN, table.to_markdown())]),
+        }
+    }
+
+    /// Each output's expression as written and typeset, along with the smallest sum of products
+    /// equivalent to it when there are few enough inputs to work it out
+    pub fn expressions(expressions: &[(String, Expr)]) -> Self {
+        let mut text = String::new();
+        let mut latex = "\\begin{align*}\n".to_owned();
+        for (name, expr) in expressions {
+            text.push_str(&format!("{} = {}\n", name, expr));
+            let name_latex = Expr::Var(name.clone()).to_latex();
+            latex.push_str(&format!("{} &= {}", name_latex, expr.to_latex()));
+            if expr.vars().len() <= MAX_TABLE_INPUTS as usize {
+                let simplified = expr.simplified();
+                if simplified != *expr {
+                    text.push_str(&format!("  simplified: {}\n", simplified));
+                    latex.push_str(&format!(" \\\\\n  &= {}", simplified.to_latex()));
+                }
+            } else {
+                text.push_str("  too many inputs to simplify\n");
+            }
+            text.push_str(&format!("  LaTeX: {}\n\n", expr.to_latex()));
+            latex.push_str(" \\\\\n");
+        }
+        latex.push_str("\\end{align*}\n");
+        Analysis {
+            title: "Expressions".to_owned(),
+            exports: Rc::new(vec![(LATEX, latex), (TEXT, text.clone())]),
+            text,
+        }
+    }
+
+    /// A report in words, which can be exported as text
+    pub fn report(title: &str, text: String) -> Self {
+        Analysis {
+            title: title.to_owned(),
+            exports: Rc::new(vec![(TEXT, text.clone())]),
+            text,
+        }
+    }
+
+    /// Just a message, for when the analysis couldn't be done
+    pub fn failed(title: &str, error: String) -> Self {
+        Analysis {
+            title: title.to_owned(),
+            text: error,
+            exports: Rc::new(Vec::new()),
+        }
+    }
+}
+
+/// A panel with the last analysis of the circuit, which is empty while there isn't one
+pub fn panel() -> impl Widget<CanvasState> {
+    let export = |ctx: &mut druid::EventCtx, data: &mut CanvasState, _: &druid::Env| {
+        let specs: Vec<_> = match data.analysis() {
+            Some(analysis) => analysis.exports.iter().map(|(spec, _)| *spec).collect(),
+            None => return,
+        };
+        if let Some(&first) = specs.first() {
+            let options = FileDialogOptions::new()
+                .allowed_types(specs)
+                .default_type(first)
+                .accept_command(EXPORT_ANALYSIS);
+            ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+        }
+    };
+    let buttons = Flex::row()
+        .with_child(Label::dynamic(|data: &CanvasState, _| {
+            data.analysis().map(|a| a.title.clone()).unwrap_or_default()
+        }))
+        .with_flex_spacer(1.0)
+        .with_child(Button::new("Export").on_click(export))
+        .with_child(
+            Button::new("Close").on_click(|_, data: &mut CanvasState, _| data.close_analysis()),
+        );
+
+    let text = Label::dynamic(|data: &CanvasState, _| {
+        data.analysis().map(|a| a.text.clone()).unwrap_or_default()
+    })
+    .with_font(FontDescriptor::new(FontFamily::MONOSPACE));
+    let panel = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(buttons)
+        .with_flex_child(Scroll::new(text).expand(), 1.0)
+        .padding(4.0)
+        .fix_width(320.0);
+    Either::new(
+        |data: &CanvasState, _| data.analysis().is_some(),
+        panel,
+        SizedBox::empty(),
+    )
+}
+
+/// Writes the export of `analysis` whose file type matches the extension of `path`
+pub fn export(analysis: &Analysis, path: &std::path::Path) -> Result<(), String> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let (_, contents) = analysis
+        .exports
+        .iter()
+        .find(|(spec, _)| spec.extensions.contains(&extension))
+        .or_else(|| analysis.exports.first())
+        .ok_or("There's nothing to export")?;
+    std::fs::write(path, contents).map_err(|e| e.to_string())
+}
@@ -0,0 +1,243 @@
+use std::path::Path;
+
+use druid::{FileSpec, Selector};
+
+use crate::import::{Assembly, Gates, Import};
+
+pub const IMPORT_BLIF: Selector<druid::FileInfo> = Selector::new("logicism/import-blif");
+
+pub const BLIF: FileSpec = FileSpec::new("BLIF netlist", &["blif"]);
+
+/// The lines of a BLIF file split into words, without comments and with continued lines joined
+fn lines(text: &str) -> Vec<Vec<&str>> {
+    let mut out: Vec<Vec<&str>> = Vec::new();
+    let mut continued = false;
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim_end();
+        let (line, continues) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let words = line.split_whitespace();
+        match out.last_mut() {
+            Some(last) if continued => last.extend(words),
+            _ => out.push(words.collect()),
+        }
+        continued = continues;
+    }
+    out.retain(|words| !words.is_empty());
+    out
+}
+
+/// Adds the gates for a `.names` cover, made of a row for each cube of the inputs with a 1 for
+/// an input that must be high, a 0 for one that must be low and a dash for one that doesn't
+/// matter, followed by the output's value. Rows that end in 0 give where the output is low
+/// instead.
+fn cover(
+    gates: &mut Gates,
+    inputs: &[&str],
+    output: &str,
+    rows: &[Vec<&str>],
+) -> Result<(), String> {
+    let nets: Vec<usize> = inputs.iter().map(|name| gates.net(name)).collect();
+    let mut cubes = Vec::new();
+    let mut value = true;
+    for row in rows {
+        let (cube, out) = match row[..] {
+            [out] if inputs.is_empty() => ("", out),
+            [cube, out] if cube.len() == inputs.len() => (cube, out),
+            _ => {
+                return Err(format!(
+                    "The cover for {} has a row that doesn't fit",
+                    output
+                ))
+            },
+        };
+        value = out == "1";
+        let mut literals = Vec::new();
+        for (c, &net) in cube.chars().zip(&nets) {
+            match c {
+                '1' => literals.push(net),
+                '0' => literals.push(gates.builder.not(net)),
+                '-' => {},
+                _ => {
+                    return Err(format!(
+                        "The cover for {} has a row that doesn't fit",
+                        output
+                    ))
+                },
+            }
+        }
+        cubes.push(gates.builder.and_all(&literals));
+    }
+    let any = gates.builder.or_all(&cubes);
+    let net = if value { any } else { gates.builder.not(any) };
+    gates.drive(output, net);
+    Ok(())
+}
+
+/// Reads the first model of a BLIF netlist, such as ABC and Yosys's `write_blif` give, made of
+/// logic covers and latches, and lays it out with an input for each input and a probe for each
+/// output
+pub fn import(text: &str) -> Result<Import, String> {
+    let lines = lines(text);
+    if !lines.iter().any(|words| words[0] == ".model") {
+        return Err("This isn't a BLIF netlist".to_owned());
+    }
+
+    let mut assembly = Assembly::new();
+    let mut gates = Gates::new();
+    let mut models = 0;
+    let mut subcircuits = false;
+    let mut starts_high = false;
+    // an input added for latches that leave their clock out
+    let mut clock = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let words = &lines[i];
+        i += 1;
+        match words[0] {
+            ".model" => {
+                models += 1;
+                if models > 1 {
+                    assembly.note("Only the first model in the netlist is read.");
+                    break;
+                }
+            },
+            ".inputs" | ".outputs" => {
+                for &name in words[1..].iter() {
+                    gates.port(name, words[0] == ".inputs", &[name.to_owned()]);
+                }
+            },
+            ".names" => {
+                let (output, inputs) = match words[1..].split_last() {
+                    Some(split) => split,
+                    None => return Err("A cover has no output".to_owned()),
+                };
+                let start = i;
+                while i < lines.len() && !lines[i][0].starts_with('.') {
+                    i += 1;
+                }
+                cover(&mut gates, inputs, output, &lines[start..i])?;
+            },
+            ".latch" => {
+                let (input, output) = match words[..] {
+                    [_, input, output, ..] => (input, output),
+                    _ => return Err("A latch is missing its input or output".to_owned()),
+                };
+                // the type and control are left out for a latch on the one clock of the model
+                let (ty, control, init) = match words[3..] {
+                    [ty, control, init] => (ty, Some(control), Some(init)),
+                    [ty, control] => (ty, Some(control), None),
+                    [init] => ("re", None, Some(init)),
+                    _ => ("re", None, None),
+                };
+                let clk = match (ty, control) {
+                    ("re", Some(control)) | ("fe", Some(control)) if control != "NIL" => {
+                        gates.net(control)
+                    },
+                    ("re", _) | ("fe", _) => {
+                        if !clock {
+                            gates.port("clock", true, &["clock".to_owned()]);
+                            clock = true;
+                        }
+                        gates.net("clock")
+                    },
+                    _ => {
+                        assembly.leave_out(format!("{} latch", ty));
+                        continue;
+                    },
+                };
+                let clk = if ty == "fe" {
+                    gates.builder.not(clk)
+                } else {
+                    clk
+                };
+                starts_high |= init == Some("1");
+                let d = gates.net(input);
+                let (en, rst) = (gates.builder.one(), gates.builder.zero());
+                let q = gates.builder.register(d, clk, en, rst);
+                gates.drive(output, q);
+            },
+            ".subckt" | ".gate" | ".mlatch" => {
+                subcircuits = true;
+                let name = words.get(1).copied().unwrap_or("");
+                assembly.leave_out(format!("{} {}", words[0], name));
+            },
+            ".end" => break,
+            _ => {},
+        }
+    }
+
+    if subcircuits {
+        assembly.note(
+            "Subcircuits and library gates are left out. Running flatten in Yosys, or writing \
+             the netlist from ABC before mapping to a library, brings in their logic instead.",
+        );
+    }
+    if starts_high {
+        assembly.note("Some latches started at 1 in the original, but registers start at 0.");
+    }
+    gates.finish(&mut assembly);
+    Ok(assembly.finish())
+}
+
+pub fn read_blif(path: &Path) -> Result<Import, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    import(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::tests::truth_table;
+
+    #[test]
+    fn imports_a_half_adder() {
+        // the sum is given by where it is low, and the carry's inputs are continued on a new line
+        let text = "# a half adder\n\
+                    .model half\n\
+                    .inputs a b\n\
+                    .outputs c s\n\
+                    .names a \\\n  b c\n\
+                    11 1\n\
+                    .names a b s\n\
+                    00 0\n\
+                    11 0\n\
+                    .end\n";
+        let (names, rows) = truth_table(import(text).unwrap());
+        assert_eq!(names, ["a", "b", "c", "s"]);
+        assert_eq!(
+            rows,
+            [[0, 0, 0, 0], [0, 1, 0, 1], [1, 0, 0, 1], [1, 1, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn imports_dont_cares_and_constants() {
+        let text = ".model m\n\
+                    .inputs a b c\n\
+                    .outputs y one\n\
+                    .names a b c y\n\
+                    1-1 1\n\
+                    -1- 1\n\
+                    .names one\n\
+                    1\n\
+                    .end\n";
+        let (_, rows) = truth_table(import(text).unwrap());
+        let expected: Vec<Vec<u64>> = (0..8)
+            .map(|i| {
+                let (a, b, c) = (i >> 2 & 1, i >> 1 & 1, i & 1);
+                vec![a, b, c, (a & c) | b, 1]
+            })
+            .collect();
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn rejects_rows_that_do_not_fit() {
+        let text = ".model m\n.inputs a\n.outputs y\n.names a y\n11 1\n.end\n";
+        assert!(import(text).is_err());
+        assert!(import("not a netlist").is_err());
+    }
+}
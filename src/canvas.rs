@@ -1,15 +1,36 @@
-use std::{collections::BTreeMap, rc::Rc, sync::atomic::AtomicUsize};
+use std::{collections::BTreeMap, path::Path, rc::Rc, sync::atomic::AtomicUsize, time::Duration};
 
 use druid::{
-    im, Affine, BoxConstraints, Color, Data, MouseButton, Point, Rect, RenderContext, Selector,
-    Size, Vec2, Widget, WidgetId, WidgetPod,
+    im,
+    kurbo::RoundedRect,
+    piet::{Text, TextLayout, TextLayoutBuilder},
+    Affine, BoxConstraints, Color, Data, MouseButton, Point, Rect, RenderContext, Selector, Size,
+    TimerToken, Vec2, Widget, WidgetId, WidgetPod,
 };
 
 use crate::{
-    component::{Component, ComponentInstance, ComponentState, ComponentType, Orientation},
+    analysis::{self, Analysis},
+    component::{self, Component, ComponentInstance, ComponentState, ComponentType, Orientation},
+    equiv::{self, Circuit},
+    expand::Style,
+    import::{self, Import},
+    karnaugh::KarnaughMap,
+    kind::{ComponentKind, State, KEYBOARD_BUFFER},
+    memory::{self, MemoryEditor},
+    picture::Picture,
+    place::{self, Layout, Ports},
+    sim::{Anchor, Netlist, Oscillation},
+    synth::{self, Spec},
+    value::Radix,
+    waveform::Trace,
     wire::{Wire, WireSegment, WireState},
 };
 
+/// How long each half of a clock cycle lasts
+const CLOCK_PERIOD: Duration = Duration::from_millis(500);
+/// How far simulation time moves on with each half of a clock cycle, for the waveform
+const HALF_PERIOD: u64 = 50;
+
 #[derive(Clone, Data)]
 pub enum WireDraw {
     FromComponent { id: usize, pin: usize, loc: Coords },
@@ -26,10 +47,12 @@ impl WireDraw {
 
 pub const DESELECT_ALL: Selector<WidgetId> = Selector::new("logicism/deselect-all");
 pub const BEGIN_WIRE_DRAW: Selector<WireDraw> = Selector::new("logicism/begin-wire-draw");
+/// Replaces the component with the given id by its gate-level implementation
+pub const EXPAND_COMPONENT: Selector<usize> = Selector::new("logicism/expand-component");
 
 static NEXT_ITEM_ID: AtomicUsize = AtomicUsize::new(0);
 
-#[derive(Clone, Copy, Data, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Data, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Coords {
     pub x: isize,
     pub y: isize,
@@ -90,10 +113,34 @@ pub enum Tool {
 pub struct CanvasState {
     wires: im::OrdMap<usize, WireState>,
     components: im::OrdMap<usize, ComponentState>,
+    /// Types whose propagation delay has been changed, each shared by every component of its
+    /// kind
+    types: im::Vector<Rc<ComponentType>>,
     tool: Tool,
     mouse_pos: Option<Coords>,
     last_orientation: Orientation,
     drawing: Option<WireDraw>,
+    memory_editor: Option<MemoryEditor>,
+    /// How long the circuit has been simulated for
+    time: u64,
+    traces: im::Vector<Trace>,
+    /// The trace whose wires are picked out on the canvas
+    highlighted: Option<usize>,
+    /// A note about the last thing that happened, like a file being exported
+    status: String,
+    /// Whether components take their propagation delays to respond, rather than responding at
+    /// once
+    timing: bool,
+    /// What kept changing when the circuit was last found not to settle. Simulation stops until
+    /// the circuit is edited or reset.
+    oscillation: Option<Oscillation>,
+    analysis: Option<Analysis>,
+    /// The function typed in to be synthesised, while synthesis is open
+    synthesis: Option<String>,
+    karnaugh: Option<KarnaughMap>,
+    /// A copy of the circuit to check later versions against
+    reference: Option<Circuit>,
+    picture: Picture,
 }
 
 impl CanvasState {
@@ -101,18 +148,643 @@ impl CanvasState {
         CanvasState {
             wires: im::OrdMap::new(),
             components: im::OrdMap::new(),
+            types: im::Vector::new(),
             tool: Tool::Hand,
             mouse_pos: None,
             last_orientation: Orientation::North,
             drawing: None,
+            memory_editor: None,
+            time: 0,
+            traces: im::Vector::new(),
+            highlighted: None,
+            status: String::new(),
+            timing: false,
+            oscillation: None,
+            analysis: None,
+            synthesis: None,
+            karnaugh: None,
+            reference: None,
+            picture: Picture::default(),
+        }
+    }
+
+    pub fn select_tool(&mut self, ty: Rc<ComponentType>) {
+        self.tool = Tool::Place(shared(&self.types, ty), self.last_orientation);
+    }
+
+    /// Sets the propagation delay of a kind of component, for the ones already placed as well as
+    /// the ones placed from now on, and returns the type they now share
+    fn set_type_delay(&mut self, kind: ComponentKind, delay: u64) -> Rc<ComponentType> {
+        let ty = Rc::new(ComponentType::with_delay(kind, delay));
+        self.types.retain(|t| t.kind != kind);
+        self.types.push_back(Rc::clone(&ty));
+        let ids: Vec<_> = self
+            .components
+            .iter()
+            .filter(|(_, c)| c.instance.ty.kind == kind)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.components.get_mut(&id).unwrap().instance.ty = Rc::clone(&ty);
+        }
+        ty
+    }
+
+    pub fn add_component(
+        &mut self,
+        coords: Coords,
+        ty: Rc<ComponentType>,
+        orientation: Orientation,
+    ) -> usize {
+        let id = NEXT_ITEM_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let ty = shared(&self.types, ty);
+        self.components
+            .insert(id, ComponentState::new(coords, ty, orientation));
+        id
+    }
+
+    pub fn add_wire(&mut self, segments: im::Vector<WireSegment>) -> usize {
+        let id = NEXT_ITEM_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        self.wires.insert(
+            id,
+            WireState {
+                segments,
+                value: None,
+            },
+        );
+        id
+    }
+
+    pub fn memory_editor(&self) -> Option<&MemoryEditor> {
+        self.memory_editor.as_ref()
+    }
+
+    pub fn memory_editor_mut(&mut self) -> Option<&mut MemoryEditor> {
+        self.memory_editor.as_mut()
+    }
+
+    /// Opens the editor on the selected memory, or closes it if no memory is selected
+    fn sync_memory_editor(&mut self) {
+        let selected = self
+            .components
+            .iter()
+            .find(|(_, c)| c.is_selected() && c.instance.ty.kind.memory_size().is_some())
+            .map(|(id, _)| *id);
+        match selected {
+            Some(id) if self.memory_editor.as_ref().map(|e| e.id) != Some(id) => {
+                self.memory_editor = Some(MemoryEditor {
+                    id,
+                    text: String::new(),
+                    error: None,
+                    initial: false,
+                    shown: Rc::default(),
+                });
+                self.refresh_memory_editor();
+            },
+            Some(_) => {},
+            None => self.memory_editor = None,
+        }
+    }
+
+    fn refresh_memory_editor(&mut self) {
+        if let Some((address, data, words)) = self.edited_memory() {
+            let editor = self.memory_editor.as_mut().unwrap();
+            editor.text = memory::dump(&words, address, data);
+            editor.error = None;
+            editor.shown = Rc::new(words);
+        }
+    }
+
+    /// Shows what the memory in the editor holds now, if it has changed since it was last shown
+    /// and the editor isn't on its initial contents
+    fn follow_memory_editor(&mut self) {
+        if let Some(editor) = &self.memory_editor {
+            let memory = &self.components[&editor.id].state.memory;
+            if !editor.initial && *editor.shown != *memory {
+                self.refresh_memory_editor();
+            }
+        }
+    }
+
+    /// Switches the editor between what the memory holds in the simulation and the initial
+    /// contents it starts from, which are the ones that can be edited
+    pub fn edit_initial_memory(&mut self, initial: bool) {
+        if let Some(editor) = &mut self.memory_editor {
+            editor.initial = initial;
+            self.refresh_memory_editor();
+        }
+    }
+
+    /// The address and data widths of the memory being edited, and the words the editor is on:
+    /// its initial contents, or what it holds now
+    pub fn edited_memory(&self) -> Option<(u8, u8, Vec<u64>)> {
+        let editor = self.memory_editor.as_ref()?;
+        let component = &self.components[&editor.id];
+        let (address, data) = component.instance.ty.kind.memory_size()?;
+        let words = if editor.initial {
+            component.instance.contents.iter().copied().collect()
+        } else {
+            component.state.memory.clone()
+        };
+        Some((address, data, words))
+    }
+
+    /// Replaces the initial contents of the memory being edited, starting it over from them, and
+    /// shows them in the editor
+    pub fn set_edited_memory(&mut self, words: Vec<u64>) {
+        if let Some(editor) = &mut self.memory_editor {
+            editor.initial = true;
+            let component = self.components.get_mut(&editor.id).unwrap();
+            component.state.memory = words.clone();
+            component.instance.contents = words.into();
+            self.refresh_memory_editor();
+        }
+    }
+
+    /// Shows an error in the memory editor, such as a file that couldn't be loaded
+    pub fn set_memory_error(&mut self, error: String) {
+        if let Some(editor) = &mut self.memory_editor {
+            editor.error = Some(error);
+        }
+    }
+
+    /// Parses the text in the memory editor into the initial contents of its memory
+    pub fn apply_memory_editor(&mut self) {
+        let (address, data, _) = match self.edited_memory() {
+            Some(memory) if self.memory_editor.as_ref().unwrap().initial => memory,
+            _ => return,
+        };
+        let text = &self.memory_editor.as_ref().unwrap().text;
+        match memory::parse_dump(text, address, data) {
+            Ok(words) => self.set_edited_memory(words),
+            Err(error) => self.set_memory_error(error),
+        }
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn components(&self) -> &im::OrdMap<usize, ComponentState> {
+        &self.components
+    }
+
+    pub fn wires(&self) -> &im::OrdMap<usize, WireState> {
+        &self.wires
+    }
+
+    pub fn traces(&self) -> &im::Vector<Trace> {
+        &self.traces
+    }
+
+    pub fn highlighted(&self) -> Option<usize> {
+        self.highlighted
+    }
+
+    pub fn highlight(&mut self, trace: Option<usize>) {
+        self.highlighted = trace;
+    }
+
+    pub fn highlighted_trace(&self) -> Option<&Trace> {
+        self.traces.get(self.highlighted?)
+    }
+
+    pub fn highlighted_trace_mut(&mut self) -> Option<&mut Trace> {
+        self.traces.get_mut(self.highlighted?)
+    }
+
+    pub fn analysis(&self) -> Option<&Analysis> {
+        self.analysis.as_ref()
+    }
+
+    pub fn close_analysis(&mut self) {
+        self.analysis = None;
+    }
+
+    pub fn show_truth_table(&mut self) {
+        self.analysis = Some(match analysis::truth_table(&self.components, &self.wires) {
+            Ok(table) => Analysis::truth_table(&table),
+            Err(error) => Analysis::failed("Truth table", error),
+        });
+    }
+
+    pub fn show_expressions(&mut self) {
+        self.analysis = Some(match analysis::expressions(&self.components, &self.wires) {
+            Ok(expressions) => Analysis::expressions(&expressions),
+            Err(error) => Analysis::failed("Expressions", error),
+        });
+    }
+
+    pub fn synthesis(&self) -> Option<&String> {
+        self.synthesis.as_ref()
+    }
+
+    pub fn synthesis_mut(&mut self) -> Option<&mut String> {
+        self.synthesis.as_mut()
+    }
+
+    pub fn open_synthesis(&mut self) {
+        self.synthesis.get_or_insert_with(String::new);
+    }
+
+    pub fn close_synthesis(&mut self) {
+        self.synthesis = None;
+    }
+
+    /// Builds the function typed in for synthesis out of gates of the given style, to the right
+    /// of everything already on the canvas
+    pub fn synthesise(&mut self, style: Style) {
+        match synth::parse(self.synthesis.as_deref().unwrap_or_default()) {
+            Ok(spec) => self.place_synthesised(&spec, style),
+            Err(error) => self.status = error,
+        }
+    }
+
+    fn place_synthesised(&mut self, spec: &Spec, style: Style) {
+        let (netlist, exprs) = synth::synthesise(spec, style);
+
+        let right = self
+            .components
+            .values()
+            .map(|c| {
+                let size = c.instance.ty.size;
+                c.instance.coords.x + size.width.max(size.height).ceil() as isize
+            })
+            .chain(self.wires.values().flat_map(|w| {
+                w.segments
+                    .iter()
+                    .map(|s| s.ends().0.x.max(s.ends().1.x))
+                    .collect::<Vec<_>>()
+            }))
+            .max()
+            .map_or(2, |x| x + 4);
+        let inputs = Ports::Standalone {
+            inputs: spec.inputs.len(),
+        };
+        let mut layout = place::place(&netlist, inputs, Coords::new(right, 0));
+        // the layout grows upwards from its origin, so bring it down onto the canvas
+        let top = layout
+            .components
+            .iter()
+            .map(|(coords, _)| coords.y)
+            .chain(
+                layout
+                    .wires
+                    .iter()
+                    .flatten()
+                    .map(|(start, end)| start.y.min(end.y)),
+            )
+            .min()
+            .unwrap_or(0);
+        let shift = Coords::new(0, 2 - top);
+        for (coords, _) in layout.components.iter_mut() {
+            *coords += shift;
+        }
+        for (start, end) in layout.wires.iter_mut().flatten() {
+            *start += shift;
+            *end += shift;
+        }
+        self.add_layout(layout);
+
+        let exprs: Vec<_> = exprs
+            .iter()
+            .map(|(name, expr)| format!("{} = {}", name, expr))
+            .collect();
+        self.status = format!(
+            "Built {} from inputs {}, left to right",
+            exprs.join(", "),
+            spec.inputs.join(", ")
+        );
+    }
+
+    pub fn karnaugh(&self) -> Option<&KarnaughMap> {
+        self.karnaugh.as_ref()
+    }
+
+    pub fn karnaugh_mut(&mut self) -> Option<&mut KarnaughMap> {
+        self.karnaugh.as_mut()
+    }
+
+    pub fn show_karnaugh(&mut self) {
+        let map = analysis::truth_table(&self.components, &self.wires)
+            .and_then(|table| KarnaughMap::of(&table));
+        match map {
+            Ok(map) => self.karnaugh = Some(map),
+            Err(error) => self.status = error,
+        }
+    }
+
+    pub fn close_karnaugh(&mut self) {
+        self.karnaugh = None;
+    }
+
+    /// Throws the circuit away and builds the functions in the Karnaugh map in its place
+    pub fn rebuild_from_karnaugh(&mut self) {
+        let spec = match &self.karnaugh {
+            Some(map) => map.spec(),
+            None => return,
+        };
+        self.components = im::OrdMap::new();
+        self.wires = im::OrdMap::new();
+        self.traces = im::Vector::new();
+        self.highlighted = None;
+        self.place_synthesised(&spec, Style::Mixed);
+
+        // the new inputs and outputs have new names
+        let netlist = Netlist::build(&self.components, &self.wires);
+        let (inputs, outputs) = analysis::ports(&self.components, &netlist);
+        if let Some(map) = &mut self.karnaugh {
+            map.rename(
+                inputs.into_iter().map(|port| port.name).collect(),
+                outputs.into_iter().map(|port| port.name).collect(),
+            );
+        }
+    }
+
+    /// Replaces the circuit with one read from another program's file, reporting what came
+    /// across and what didn't
+    pub fn replace_with_import(&mut self, title: &str, import: Import) {
+        self.components = im::OrdMap::new();
+        self.wires = im::OrdMap::new();
+        self.traces = im::Vector::new();
+        self.highlighted = None;
+        let mut names = Vec::new();
+        for instance in import.components.iter() {
+            let id = self.add_component(
+                instance.coords,
+                Rc::clone(&instance.ty),
+                instance.orientation,
+            );
+            if let Some(component) = self.components.get_mut(&id) {
+                component.instance.label = instance.label.clone();
+            }
+            names.push(instance.name(id));
+        }
+        for segments in import.wires.iter() {
+            self.add_wire(segments.clone());
+        }
+        self.analysis = Some(Analysis::report(title, import.report(&names)));
+    }
+
+    /// Keeps a copy of the circuit as it is now, for later versions to be compared against
+    pub fn set_reference(&mut self) {
+        self.reference = Some(Circuit {
+            components: self.components.clone(),
+            wires: self.wires.clone(),
+        });
+        self.status = "Kept a copy of the circuit to compare against".to_owned();
+    }
+
+    /// Keeps a circuit read from `path` to compare the one on the canvas against
+    pub fn set_reference_from_import(&mut self, path: &Path, import: Import) {
+        let mut reference = CanvasState::new();
+        reference.replace_with_import("", import);
+        self.reference = Some(Circuit {
+            components: reference.components,
+            wires: reference.wires,
+        });
+        self.status = format!("Loaded {} to compare against", path.display());
+    }
+
+    pub fn compare_with_reference(&mut self) {
+        let reference = match &self.reference {
+            Some(reference) => reference,
+            None => {
+                self.status = "Set a reference circuit to compare against first".to_owned();
+                return;
+            },
+        };
+        self.analysis = Some(
+            match equiv::compare(&self.components, &self.wires, reference) {
+                Ok(report) => Analysis::report("Equivalence", report),
+                Err(error) => Analysis::failed("Equivalence", error),
+            },
+        );
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    pub fn picture(&self) -> Picture {
+        self.picture
+    }
+
+    pub fn toggle_grid_dots(&mut self) {
+        self.picture.grid = !self.picture.grid;
+        self.status = if self.picture.grid {
+            "Pictures show the grid's dots".to_owned()
+        } else {
+            "Pictures leave out the grid's dots".to_owned()
+        };
+    }
+
+    pub fn next_picture_scale(&mut self) {
+        self.picture.next_scale();
+        self.status = format!("PNG pictures are drawn at {}×", self.picture.scale);
+    }
+
+    /// A name for a pin or wire: the component's kind and id with the pin name, or for a wire the
+    /// name of a pin driving it
+    fn anchor_name(&self, anchor: Anchor, netlist: &Netlist) -> String {
+        match anchor {
+            Anchor::Pin(id, pin) => {
+                let instance = &self.components[&id].instance;
+                format!("{}.{}", instance.name(id), instance.ty.pins[pin].name)
+            },
+            Anchor::Wire(id) => {
+                let drivers = netlist.net_of(anchor).map(|net| netlist.drivers(net));
+                match drivers {
+                    Some(&[(component, pin), ..]) => {
+                        self.anchor_name(Anchor::Pin(component, pin), netlist)
+                    },
+                    _ => format!("w{}", id),
+                }
+            },
+        }
+    }
+
+    /// Starts tracing the net `anchor` is on, or stops if it is traced already
+    fn toggle_trace(&mut self, anchor: Anchor, netlist: &Netlist) {
+        let net = netlist.net_of(anchor);
+        match self
+            .traces
+            .iter()
+            .position(|t| netlist.net_of(t.anchor) == net)
+        {
+            Some(i) => {
+                self.traces.remove(i);
+                self.highlighted = match self.highlighted {
+                    Some(h) if h == i => None,
+                    Some(h) if h > i => Some(h - 1),
+                    h => h,
+                };
+            },
+            None => {
+                let name = self.anchor_name(anchor, netlist);
+                self.traces.push_back(Trace::new(anchor, name));
+            },
+        }
+    }
+
+    /// Adds the current value of every traced net to its history
+    fn record_traces(&mut self, netlist: &Netlist) {
+        for i in 0..self.traces.len() {
+            let net = match netlist.net_of(self.traces[i].anchor) {
+                Some(net) => net,
+                None => continue,
+            };
+            let value = netlist.value(net, &self.components);
+            if self.traces[i].value_at(self.time) != Some(value) {
+                self.traces[i].record(self.time, value);
+            }
+        }
+    }
+
+    /// When the next tick of the clocks is due
+    fn next_tick(&self) -> u64 {
+        (self.time / HALF_PERIOD + 1) * HALF_PERIOD
+    }
+
+    /// Types a character on the selected keyboards, returning whether any are selected
+    fn type_on_keyboards(&mut self, c: char) -> bool {
+        let keyboards: Vec<_> = self
+            .components
+            .iter()
+            .filter(|(_, c)| c.instance.ty.kind == ComponentKind::Keyboard && c.is_selected())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in keyboards.iter() {
+            let text = &mut self.components.get_mut(id).unwrap().state.text;
+            if text.len() < KEYBOARD_BUFFER {
+                text.push(c);
+            }
+        }
+        !keyboards.is_empty()
+    }
+
+    /// Toggles every clock, for the next tick of the simulation
+    pub fn tick_clocks(&mut self) {
+        self.time = self.next_tick();
+        let clocks: Vec<_> = self
+            .components
+            .iter()
+            .filter(|(_, c)| c.instance.ty.kind == ComponentKind::Clock)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in clocks {
+            self.components.get_mut(&id).unwrap().state.value ^= 1;
+        }
+    }
+
+    /// Puts every component back the way it was when it was placed, with memories holding their
+    /// initial contents
+    pub fn reset_simulation(&mut self) {
+        let ids: Vec<_> = self.components.keys().copied().collect();
+        for id in ids {
+            let component = self.components.get_mut(&id).unwrap();
+            component.state = State {
+                memory: component.instance.contents.iter().copied().collect(),
+                ..State::default()
+            };
+            component.values = im::Vector::new();
+        }
+        self.time = 0;
+        self.oscillation = None;
+        for trace in self.traces.iter_mut() {
+            trace.changes.clear();
+        }
+    }
+
+    /// Swaps a component for the gates it is made of, if it isn't a gate already. The gates are
+    /// laid out growing up from just to the right of where the component was, and wired back to
+    /// the points its pins were at so that the circuit works as before.
+    pub fn expand_component(&mut self, id: usize) {
+        let instance = &self.components[&id].instance;
+        let kind = instance.ty.kind;
+        let netlist = match kind.expand() {
+            Some(netlist) => netlist,
+            None => return,
+        };
+        let pins: Vec<Coords> = (0..instance.ty.pins.len())
+            .map(|pin| instance.pin_coords(pin))
+            .collect();
+        // clear of the pins, so that there is room to wire the gates back to them
+        let right = pins.iter().map(|p| p.x).max().unwrap_or(instance.coords.x);
+        let bottom = pins.iter().map(|p| p.y).max().unwrap_or(instance.coords.y);
+        let origin = Coords::new(right + 3, bottom);
+        let layout = place::place(&netlist, Ports::Of(kind), origin);
+
+        self.components.remove(&id);
+        let links: Vec<_> = layout.ports.iter().copied().zip(pins).collect();
+        self.add_layout(layout);
+        // join the gates' pins to whatever the component's pins were wired to
+        let pins = self.components.values().flat_map(|component| {
+            let instance = &component.instance;
+            (0..instance.ty.pins.len()).map(move |pin| instance.pin_coords(pin))
+        });
+        let segments = self
+            .wires
+            .values()
+            .flat_map(|wire| wire.segments.iter().map(WireSegment::ends));
+        for segments in import::route_links(pins, segments, &links) {
+            let segments: im::Vector<_> = segments
+                .into_iter()
+                .filter_map(|(start, end)| WireSegment::new(start, end))
+                .collect();
+            if !segments.is_empty() {
+                self.add_wire(segments);
+            }
+        }
+    }
+
+    fn add_layout(&mut self, layout: Layout) {
+        for (coords, kind) in layout.components {
+            self.add_component(
+                coords,
+                Rc::new(ComponentType::new(kind)),
+                Orientation::North,
+            );
+        }
+        for segments in layout.wires {
+            let segments = segments
+                .into_iter()
+                .filter_map(|(start, end)| WireSegment::new(start, end))
+                .collect();
+            self.add_wire(segments);
         }
     }
 }
 
+/// The type that components of `ty`'s kind share, if their delay has been changed, or else `ty`
+fn shared(types: &im::Vector<Rc<ComponentType>>, ty: Rc<ComponentType>) -> Rc<ComponentType> {
+    types
+        .iter()
+        .find(|t| t.kind == ty.kind)
+        .cloned()
+        .unwrap_or(ty)
+}
+
+/// Paints a wide band under a wire, to pick it out
+fn paint_wire_overlay(ctx: &mut druid::PaintCtx, wire: &WireState, color: &Color) {
+    for segment in wire.segments.iter() {
+        let (start, end) = segment.ends();
+        let rect = Rect::from_points(start.to_canvas_space(), end.to_canvas_space());
+        ctx.fill(rect.inflate(4.0, 4.0), color);
+    }
+}
+
 pub struct Canvas {
     component_types: Rc<Vec<Rc<ComponentType>>>,
     wires: BTreeMap<usize, WidgetPod<WireState, Wire>>,
     components: BTreeMap<usize, WidgetPod<ComponentState, Component>>,
+    /// The nets of the circuit, kept until it is next edited
+    netlist: Option<Netlist>,
+    clock_timer: TimerToken,
 }
 
 impl Canvas {
@@ -121,8 +793,136 @@ impl Canvas {
             component_types,
             wires: BTreeMap::new(),
             components: BTreeMap::new(),
+            netlist: None,
+            clock_timer: TimerToken::INVALID,
         }
     }
+
+    /// Describes the net under the mouse: the pin there if any, the value, the width and what
+    /// drives it
+    fn tooltip(&self, data: &CanvasState) -> Option<String> {
+        if data.drawing.is_some() || !matches!(data.tool, Tool::Hand) {
+            return None;
+        }
+        let netlist = self.netlist.as_ref()?;
+        let (net, anchor) = netlist.net_at(data.mouse_pos?, &data.components, &data.wires)?;
+        let pin_name = |(id, pin): (usize, usize)| {
+            let ty = &data.components[&id].instance.ty;
+            format!("{} {}", ty.kind.name(), ty.pins[pin].name)
+        };
+
+        let mut lines = Vec::new();
+        if let Anchor::Pin(id, pin) = anchor {
+            lines.push(pin_name((id, pin)));
+            if data.timing {
+                lines.push(format!("delay {}", data.components[&id].instance.delay()));
+            }
+        }
+        let value = netlist.value(net, &data.components);
+        let width = netlist.width(net);
+        lines.push(if width == 1 {
+            value.format(Radix::Binary)
+        } else if width <= 16 {
+            format!(
+                "0b{} 0x{} {}",
+                value.format(Radix::Binary),
+                value.format(Radix::Hex),
+                value.format(Radix::Decimal)
+            )
+        } else {
+            format!(
+                "0x{} {}",
+                value.format(Radix::Hex),
+                value.format(Radix::Decimal)
+            )
+        });
+        lines.push(format!(
+            "{} bit{}",
+            width,
+            if width == 1 { "" } else { "s" }
+        ));
+        lines.push(match netlist.drivers(net) {
+            [] => "undriven".to_owned(),
+            [driver] => format!("driven by {}", pin_name(*driver)),
+            drivers if value.has_error() => format!("{} drivers, contended", drivers.len()),
+            drivers => format!("{} drivers", drivers.len()),
+        });
+        Some(lines.join("\n"))
+    }
+
+    /// Brings the values in `data` up to date with the circuit, working the nets out again if it
+    /// has been edited
+    fn simulate(&mut self, data: &mut CanvasState) {
+        let mut netlist = match self.netlist.take() {
+            Some(netlist) if netlist.is_current(&data.components, &data.wires) => netlist,
+            _ => {
+                data.oscillation = None;
+                Netlist::build(&data.components, &data.wires)
+            },
+        };
+        if data.oscillation.is_some() {
+            self.netlist = Some(netlist);
+            return;
+        }
+
+        let result = if data.timing {
+            let until = data.next_tick();
+            let nets: Vec<_> = data
+                .traces
+                .iter()
+                .map(|t| netlist.net_of(t.anchor))
+                .collect();
+            let traces = &mut data.traces;
+            let (time, result) = netlist.run(
+                &mut data.components,
+                &mut data.wires,
+                data.time,
+                until,
+                |time, net, value| {
+                    for (trace, _) in traces.iter_mut().zip(&nets).filter(|t| *t.1 == Some(net)) {
+                        trace.record(time, value);
+                    }
+                },
+            );
+            data.time = time;
+            result
+        } else {
+            netlist.settle(&mut data.components, &mut data.wires)
+        };
+        if let Err(oscillation) = result {
+            data.status = "Stopped because the highlighted part of the circuit doesn't settle. \
+                           Edit it or press r to carry on."
+                .to_owned();
+            data.oscillation = Some(oscillation);
+        }
+        data.record_traces(&netlist);
+        data.follow_memory_editor();
+        self.netlist = Some(netlist);
+    }
+
+    /// Adds and removes child widgets to match the items in `data`, returning whether anything
+    /// changed.
+    fn sync_children(&mut self, data: &CanvasState) -> bool {
+        let before = (self.wires.len(), self.components.len());
+        self.wires.retain(|id, _| data.wires.contains_key(id));
+        self.components
+            .retain(|id, _| data.components.contains_key(id));
+        let mut changed = before != (self.wires.len(), self.components.len());
+
+        for id in data.wires.keys() {
+            if !self.wires.contains_key(id) {
+                self.wires.insert(*id, WidgetPod::new(Wire(*id)));
+                changed = true;
+            }
+        }
+        for id in data.components.keys() {
+            if !self.components.contains_key(id) {
+                self.components.insert(*id, WidgetPod::new(Component(*id)));
+                changed = true;
+            }
+        }
+        changed
+    }
 }
 
 impl Widget<CanvasState> for Canvas {
@@ -143,14 +943,70 @@ impl Widget<CanvasState> for Canvas {
             widget.event(ctx, event, state, env);
         }
 
+        data.sync_memory_editor();
         if ctx.is_handled() {
+            self.simulate(data);
             return;
         }
 
         use druid::keyboard_types::Key;
         use druid::Event::*;
+        // keys typed while a keyboard is selected are for it rather than shortcuts
+        if let KeyDown(key_event) = event {
+            if let Some(c) = component::ascii(&key_event.key) {
+                if !key_event.mods.ctrl() && data.type_on_keyboards(c) {
+                    ctx.set_handled();
+                    self.simulate(data);
+                    return;
+                }
+            }
+        }
         match (event, &mut data.tool) {
-            (WindowConnected, _) => ctx.request_focus(),
+            (WindowConnected, _) => {
+                ctx.request_focus();
+                self.clock_timer = ctx.request_timer(CLOCK_PERIOD);
+            },
+            (Timer(token), _) if *token == self.clock_timer => {
+                data.tick_clocks();
+                self.clock_timer = ctx.request_timer(CLOCK_PERIOD);
+            },
+            (KeyDown(key_event), _) if key_event.key == Key::Character("r".into()) => {
+                data.reset_simulation();
+                // along with any changes still on their way
+                self.netlist = None;
+            },
+            (KeyDown(key_event), _) if key_event.key == Key::Character("p".into()) => {
+                data.timing = !data.timing;
+                data.status = if data.timing {
+                    "Simulating propagation delays".to_owned()
+                } else {
+                    "Simulating without delays".to_owned()
+                };
+                self.netlist = None;
+            },
+            (KeyDown(key_event), Tool::Hand) if key_event.key == Key::Character("t".into()) => {
+                let netlist = self.netlist.as_ref();
+                let found = data
+                    .mouse_pos
+                    .and_then(|point| netlist?.net_at(point, &data.components, &data.wires));
+                if let (Some(netlist), Some((_, anchor))) = (netlist, found) {
+                    data.toggle_trace(anchor, netlist);
+                }
+            },
+            (KeyDown(key_event), Tool::Place(ty, orientation))
+                if key_event.key == Key::Character("-".into())
+                    || key_event.key == Key::Character("=".into()) =>
+            {
+                let delay = if key_event.key == Key::Character("=".into()) {
+                    ty.delay + 1
+                } else {
+                    ty.delay.saturating_sub(1)
+                };
+                let (kind, orientation) = (ty.kind, *orientation);
+                let ty = data.set_type_delay(kind, delay);
+                data.tool = Tool::Place(ty, orientation);
+                ctx.request_paint();
+            },
             (KeyDown(key_event), tool) => {
                 let mut new_tool = tool.clone();
                 match (&key_event.key, &*tool) {
@@ -162,11 +1018,29 @@ impl Widget<CanvasState> for Canvas {
                         let n = u16::from_str_radix(&s, 10).unwrap().wrapping_sub(1) as usize;
                         if n < self.component_types.len() {
                             new_tool = Tool::Place(
-                                Rc::clone(&self.component_types[n]),
+                                shared(&data.types, Rc::clone(&self.component_types[n])),
                                 data.last_orientation,
                             );
                         }
                     },
+                    (Key::Character(ref s), &Tool::Place(ref ty, orientation))
+                        if s == "[" || s == "]" =>
+                    {
+                        let delta = if s == "]" { 1 } else { -1 };
+                        if let Some(kind) = ty.kind.resized(delta) {
+                            let ty = Rc::new(ComponentType::new(kind));
+                            new_tool = Tool::Place(shared(&data.types, ty), orientation);
+                        }
+                    },
+                    (Key::Character(ref s), &Tool::Place(ref ty, orientation))
+                        if s == "{" || s == "}" =>
+                    {
+                        let delta = if s == "}" { 1 } else { -1 };
+                        if let Some(kind) = ty.kind.widened(delta) {
+                            let ty = Rc::new(ComponentType::new(kind));
+                            new_tool = Tool::Place(shared(&data.types, ty), orientation);
+                        }
+                    },
                     (Key::Character(ref s), &Tool::Place(ref ty, _)) if s == "w" => {
                         new_tool = Tool::Place(Rc::clone(&ty), Orientation::North)
                     },
@@ -190,9 +1064,8 @@ impl Widget<CanvasState> for Canvas {
                 let new_coords = Coords::from_canvas_space(m.pos);
                 if data.mouse_pos != Some(new_coords) {
                     data.mouse_pos = Some(new_coords);
-                    if data.drawing.is_some() {
-                        ctx.request_paint();
-                    }
+                    // for the wire being drawn or the tooltip
+                    ctx.request_paint();
                 }
             },
             (MouseMove(m), Tool::Place(_, _)) => {
@@ -211,35 +1084,33 @@ impl Widget<CanvasState> for Canvas {
                         WireSegment::new(wire_draw.start_point(), data.mouse_pos.unwrap())
                     {
                         // TODO: merge connected segments
-                        let id = NEXT_ITEM_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                        let widget = WidgetPod::new(Wire(id));
-                        self.wires.insert(id, widget);
-                        let state = WireState {
-                            segments: im::Vector::from(&[segment][..]),
-                        };
-                        data.wires.insert(id, state);
-                        ctx.children_changed();
+                        data.add_wire(im::Vector::from(&[segment][..]));
                     }
                 }
                 data.drawing = None;
             },
             (MouseDown(ev), Tool::Place(ty, orientation)) if ev.button == MouseButton::Left => {
                 let coords = Coords::from_canvas_space(ev.pos);
-                let id = NEXT_ITEM_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                self.components.insert(id, WidgetPod::new(Component(id)));
-                data.components.insert(
-                    id,
-                    ComponentState::new(coords, Rc::clone(&ty), *orientation),
-                );
-                ctx.children_changed();
+                let (ty, orientation) = (Rc::clone(ty), *orientation);
+                data.add_component(coords, ty, orientation);
                 ctx.request_paint();
             },
             (Command(c), _) if c.is(BEGIN_WIRE_DRAW) => {
                 let wire_draw = c.get(BEGIN_WIRE_DRAW).unwrap().clone();
                 data.drawing = Some(wire_draw);
             },
+            (Command(c), _) if c.is(EXPAND_COMPONENT) => {
+                let id = *c.get(EXPAND_COMPONENT).unwrap();
+                data.expand_component(id);
+                ctx.request_paint();
+            },
             _ => {},
         }
+
+        if self.sync_children(data) {
+            ctx.children_changed();
+        }
+        self.simulate(data);
     }
 
     fn lifecycle(
@@ -273,10 +1144,17 @@ impl Widget<CanvasState> for Canvas {
         data: &CanvasState,
         env: &druid::Env,
     ) {
+        if !Data::same(&data.tool, &old_data.tool)
+            || data.highlighted != old_data.highlighted
+            || !data.oscillation.same(&old_data.oscillation)
+        {
+            ctx.request_paint();
+        }
+
         for (widget, new, old) in self
             .wires
             .iter_mut()
-            .map(|(id, widget)| (widget, &data.wires[id], old_data.wires.get(id)))
+            .filter_map(|(id, widget)| Some((widget, data.wires.get(id)?, old_data.wires.get(id))))
         {
             widget.update(ctx, new, env);
             if let Some(old) = old {
@@ -286,11 +1164,13 @@ impl Widget<CanvasState> for Canvas {
             }
         }
 
-        for (widget, new, old) in self
-            .components
-            .iter_mut()
-            .map(|(id, widget)| (widget, &data.components[id], old_data.components.get(id)))
-        {
+        for (widget, new, old) in self.components.iter_mut().filter_map(|(id, widget)| {
+            Some((
+                widget,
+                data.components.get(id)?,
+                old_data.components.get(id),
+            ))
+        }) {
             widget.update(ctx, new, env);
             if let Some(old) = old {
                 if !Data::same(&new.instance, &old.instance) {
@@ -298,6 +1178,10 @@ impl Widget<CanvasState> for Canvas {
                 }
             }
         }
+
+        if self.sync_children(data) {
+            ctx.children_changed();
+        }
     }
 
     fn layout(
@@ -368,10 +1252,39 @@ impl Widget<CanvasState> for Canvas {
                 ctx.transform(Affine::translate(
                     segment.bounding_rect().origin() - Point::ORIGIN,
                 ));
-                segment.paint(ctx);
+                segment.paint(ctx, segment.bounding_rect().origin(), &Color::GREEN);
             });
         }
 
+        // wires on the net highlighted in the waveform
+        let highlighted = data.highlighted.and_then(|i| {
+            let netlist = self.netlist.as_ref()?;
+            Some((netlist, netlist.net_of(data.traces.get(i)?.anchor)?))
+        });
+        if let Some((netlist, net)) = highlighted {
+            for (id, wire) in data.wires.iter() {
+                if netlist.wires.get(id) == Some(&net) {
+                    paint_wire_overlay(ctx, wire, &Color::rgba8(0xff, 0xd0, 0x00, 0x80));
+                }
+            }
+        }
+
+        // whatever stopped the circuit from settling
+        if let Some(oscillation) = &data.oscillation {
+            let color = Color::rgba8(0xff, 0x00, 0xff, 0x60);
+            for id in oscillation.wires.iter() {
+                if let Some(wire) = data.wires.get(id) {
+                    paint_wire_overlay(ctx, wire, &color);
+                }
+            }
+            for id in oscillation.components.iter() {
+                if let Some(component) = data.components.get(id) {
+                    let rect = component.instance.bounding_rect().inflate(4.0, 4.0);
+                    ctx.fill(RoundedRect::from_rect(rect, 4.0), &color);
+                }
+            }
+        }
+
         for (widget, data) in self.wires.values_mut().zip(data.wires.values()) {
             widget.paint(ctx, data, env);
         }
@@ -379,5 +1292,22 @@ impl Widget<CanvasState> for Canvas {
         for (widget, data) in self.components.values_mut().zip(data.components.values()) {
             widget.paint(ctx, data, env);
         }
+
+        if let Some(text) = self.tooltip(data) {
+            let label = ctx
+                .text()
+                .new_text_layout(text)
+                .text_color(Color::BLACK)
+                .build()
+                .unwrap();
+            let origin = data.mouse_pos.unwrap().to_canvas_space() + Vec2::new(12.0, 12.0);
+            let rect = Rect::from_origin_size(origin, label.size()).inflate(4.0, 4.0);
+            ctx.fill(
+                RoundedRect::from_rect(rect, 4.0),
+                &Color::rgb8(0xff, 0xff, 0xe0),
+            );
+            ctx.stroke(RoundedRect::from_rect(rect, 4.0), &Color::GRAY, 1.0);
+            ctx.draw_text(&label, origin);
+        }
     }
 }
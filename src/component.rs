@@ -1,12 +1,17 @@
 use std::{rc::Rc, str::FromStr};
 
 use druid::{
-    kurbo::RoundedRect, widget::SvgData, Affine, Color, Data, Event, Insets, PaintCtx, Point, Rect,
-    RenderContext, Size, Vec2, Widget,
+    im,
+    kurbo::{Circle, Line, RoundedRect},
+    piet::{FontFamily, Text, TextLayout, TextLayoutBuilder},
+    widget::SvgData,
+    Affine, Color, Data, Event, Insets, PaintCtx, Point, Rect, RenderContext, Size, Vec2, Widget,
 };
 
 use crate::{
-    canvas::{Coords, WireDraw, BEGIN_WIRE_DRAW, DESELECT_ALL},
+    canvas::{Coords, WireDraw, BEGIN_WIRE_DRAW, DESELECT_ALL, EXPAND_COMPONENT},
+    kind::{ComponentKind, PinType, ShiftMode, Side, State},
+    value::{Radix, Value},
     IDENTITY,
 };
 
@@ -29,82 +34,239 @@ impl Orientation {
     }
 }
 
-#[derive(Debug)]
-enum PinType {
-    Input,
-    Output,
+/// The source of the drawing for a kind of gate, for the kinds that have one
+pub fn icon(kind: ComponentKind) -> Option<&'static str> {
+    match kind {
+        ComponentKind::Not => Some(include_str!("../res/not_gate.svg")),
+        ComponentKind::And => Some(include_str!("../res/and_gate.svg")),
+        ComponentKind::Or => Some(include_str!("../res/or_gate.svg")),
+        ComponentKind::Nand => Some(include_str!("../res/nand_gate.svg")),
+        ComponentKind::Nor => Some(include_str!("../res/nor_gate.svg")),
+        ComponentKind::Xor => Some(include_str!("../res/xor_gate.svg")),
+        _ => None,
+    }
 }
 
 #[derive(Debug)]
-struct Pin {
-    pos: Coords,
-    ty: PinType,
+pub struct Pin {
+    pub pos: Coords,
+    pub ty: PinType,
+    pub width: u8,
+    pub name: String,
 }
 
 impl Pin {
-    fn new(x: isize, y: isize, ty: PinType) -> Self {
+    fn new(x: isize, y: isize, ty: PinType, name: &str) -> Self {
         Pin {
             pos: Coords::new(x, y),
             ty,
+            width: 1,
+            name: name.to_owned(),
         }
     }
 }
 
+pub enum Symbol {
+    Svg(SvgData),
+    /// A labelled rectangle, for components whose shape depends on their attributes
+    Box,
+    /// A rectangle with a face that shows the values at its pins, for inputs and displays
+    Display,
+}
+
 pub struct ComponentType {
+    pub kind: ComponentKind,
     pub size: Size,
     /// The point that is represented by the coordinates of a component when it is oriented north
     anchor_offset: Vec2,
-    pub icon: SvgData,
-    pins: Vec<Pin>,
+    pub symbol: Symbol,
+    pub pins: Vec<Pin>,
+    /// The propagation delay of components of this type that don't have their own
+    pub delay: u64,
 }
 
 impl ComponentType {
     pub fn enumerate() -> Vec<Rc<Self>> {
-        let not_gate = ComponentType {
-            size: Size::new(24.0, 48.0),
-            anchor_offset: Vec2::new(12.0, 32.0),
-            icon: SvgData::from_str(include_str!("../res/not_gate.svg")).unwrap(),
-            pins: vec![
-                Pin::new(0, 1, PinType::Input),
-                Pin::new(0, -2, PinType::Output),
-            ],
-        };
-        let and_gate = ComponentType {
-            size: Size::new(48.0, 48.0),
-            anchor_offset: Vec2::new(24.0, 32.0),
-            icon: SvgData::from_str(include_str!("../res/and_gate.svg")).unwrap(),
-            pins: vec![
-                Pin::new(-1, 1, PinType::Input),
-                Pin::new(1, 1, PinType::Input),
-                Pin::new(0, -2, PinType::Output),
-            ],
-        };
-        let or_gate = ComponentType {
-            size: Size::new(48.0, 48.0),
-            anchor_offset: Vec2::new(24.0, 32.0),
-            icon: SvgData::from_str(include_str!("../res/or_gate.svg")).unwrap(),
-            pins: vec![
-                Pin::new(-1, 1, PinType::Input),
-                Pin::new(1, 1, PinType::Input),
-                Pin::new(0, -2, PinType::Output),
-            ],
-        };
-        let nand_gate = ComponentType {
+        vec![
+            ComponentKind::Not,
+            ComponentKind::And,
+            ComponentKind::Or,
+            ComponentKind::Nand,
+            ComponentKind::Nor,
+            ComponentKind::Xor,
+            ComponentKind::Constant { width: 1, value: 1 },
+            ComponentKind::Split { width: 4 },
+            ComponentKind::Join { width: 4 },
+            ComponentKind::Mux {
+                select: 1,
+                width: 1,
+            },
+            ComponentKind::Demux {
+                select: 1,
+                width: 1,
+            },
+            ComponentKind::Decoder { select: 2 },
+            ComponentKind::PriorityEncoder { select: 2 },
+            ComponentKind::FullAdder,
+            ComponentKind::Adder { width: 4 },
+            ComponentKind::Subtractor { width: 4 },
+            ComponentKind::Comparator { width: 4 },
+            ComponentKind::Multiplier { width: 4 },
+            ComponentKind::Shifter { width: 4 },
+            ComponentKind::Alu { width: 4 },
+            ComponentKind::Register { width: 4 },
+            ComponentKind::Counter { width: 4 },
+            ComponentKind::ShiftRegister {
+                width: 4,
+                mode: ShiftMode::Sipo,
+            },
+            ComponentKind::ShiftRegister {
+                width: 4,
+                mode: ShiftMode::Piso,
+            },
+            ComponentKind::ShiftRegister {
+                width: 4,
+                mode: ShiftMode::Universal,
+            },
+            ComponentKind::Rom {
+                address: 4,
+                data: 8,
+            },
+            ComponentKind::Ram {
+                address: 4,
+                data: 8,
+                sync: true,
+            },
+            ComponentKind::Ram {
+                address: 4,
+                data: 8,
+                sync: false,
+            },
+            ComponentKind::Input { width: 1 },
+            ComponentKind::Clock,
+            ComponentKind::SevenSegment,
+            ComponentKind::HexDisplay,
+            ComponentKind::DotMatrix { rows: 5, cols: 7 },
+            ComponentKind::Keyboard,
+            ComponentKind::Tty { rows: 8, cols: 32 },
+            ComponentKind::TriState { width: 1 },
+            ComponentKind::TransmissionGate { width: 1 },
+            ComponentKind::Probe { radix: Radix::Hex },
+        ]
+        .into_iter()
+        .map(|kind| Rc::new(ComponentType::new(kind)))
+        .collect()
+    }
+
+    pub fn new(kind: ComponentKind) -> Self {
+        let gate = |icon: &str| ComponentType {
+            kind,
             size: Size::new(48.0, 48.0),
             anchor_offset: Vec2::new(24.0, 32.0),
-            icon: SvgData::from_str(include_str!("../res/nand_gate.svg")).unwrap(),
+            symbol: Symbol::Svg(SvgData::from_str(icon).unwrap()),
             pins: vec![
-                Pin::new(-1, 1, PinType::Input),
-                Pin::new(1, 1, PinType::Input),
-                Pin::new(0, -2, PinType::Output),
+                Pin::new(-1, 1, PinType::Input, "A"),
+                Pin::new(1, 1, PinType::Input, "B"),
+                Pin::new(0, -2, PinType::Output, "Y"),
             ],
+            delay: kind.delay(),
         };
-        vec![
-            Rc::new(not_gate),
-            Rc::new(and_gate),
-            Rc::new(or_gate),
-            Rc::new(nand_gate),
-        ]
+        match kind {
+            ComponentKind::Not => ComponentType {
+                kind,
+                size: Size::new(24.0, 48.0),
+                anchor_offset: Vec2::new(12.0, 32.0),
+                symbol: Symbol::Svg(SvgData::from_str(icon(kind).unwrap()).unwrap()),
+                pins: vec![
+                    Pin::new(0, 1, PinType::Input, "A"),
+                    Pin::new(0, -2, PinType::Output, "Y"),
+                ],
+                delay: kind.delay(),
+            },
+            ComponentKind::And
+            | ComponentKind::Or
+            | ComponentKind::Nand
+            | ComponentKind::Nor
+            | ComponentKind::Xor => gate(icon(kind).unwrap()),
+            ComponentKind::Input { width } => {
+                ComponentType::sized(kind, Symbol::Display, width as isize + 1, 2)
+            },
+            ComponentKind::SevenSegment => ComponentType::sized(kind, Symbol::Display, 0, 5),
+            ComponentKind::HexDisplay => ComponentType::sized(kind, Symbol::Display, 3, 5),
+            ComponentKind::DotMatrix { rows, cols } => {
+                ComponentType::sized(kind, Symbol::Display, cols as isize + 1, rows as isize + 1)
+            },
+            ComponentKind::Keyboard => ComponentType::sized(kind, Symbol::Display, 6, 2),
+            ComponentKind::Probe { .. } => ComponentType::sized(kind, Symbol::Display, 4, 2),
+            // characters are half a cell wide
+            ComponentKind::Tty { rows, cols } => ComponentType::sized(
+                kind,
+                Symbol::Display,
+                (cols as isize + 1) / 2 + 1,
+                rows as isize + 1,
+            ),
+            _ => ComponentType::boxed(kind),
+        }
+    }
+
+    /// Lays out a rectangular symbol big enough for the pins of `kind`: data inputs along the
+    /// bottom, controls up the left and outputs along the top.
+    fn boxed(kind: ComponentKind) -> Self {
+        ComponentType::sized(kind, Symbol::Box, 0, 3)
+    }
+
+    /// Lays out a symbol like `boxed`, but at least `min_cols` by `min_rows` cells so that there's
+    /// room for a face
+    fn sized(kind: ComponentKind, symbol: Symbol, min_cols: isize, min_rows: isize) -> Self {
+        let specs = kind.pins();
+        let count = |side| specs.iter().filter(|p| p.side == side).count() as isize;
+        let cols = (count(Side::Bottom).max(count(Side::Top)) + 1).max(min_cols);
+        let rows = (count(Side::Left) + 1).max(min_rows);
+
+        let (mut bottom, mut left, mut top) = (0, 0, 0);
+        let pins = specs
+            .into_iter()
+            .map(|spec| {
+                let pos = match spec.side {
+                    Side::Bottom => {
+                        bottom += 1;
+                        Coords::new(bottom - 1, 1)
+                    },
+                    Side::Left => {
+                        left += 1;
+                        Coords::new(-1, 1 - left)
+                    },
+                    Side::Top => {
+                        top += 1;
+                        Coords::new(top - 1, 1 - rows)
+                    },
+                };
+                Pin {
+                    pos,
+                    ty: spec.ty,
+                    width: spec.width,
+                    name: spec.name,
+                }
+            })
+            .collect();
+
+        let size = Size::new(cols as f64 * 16.0, rows as f64 * 16.0);
+        ComponentType {
+            kind,
+            size,
+            anchor_offset: Vec2::new(16.0, size.height - 16.0),
+            symbol,
+            pins,
+            delay: kind.delay(),
+        }
+    }
+
+    /// The same type with a different propagation delay
+    pub fn with_delay(kind: ComponentKind, delay: u64) -> Self {
+        ComponentType {
+            delay,
+            ..ComponentType::new(kind)
+        }
     }
 
     pub fn anchor_offset(&self, orientation: Orientation) -> Vec2 {
@@ -129,9 +291,15 @@ impl ComponentType {
 
 #[derive(Clone, Data)]
 pub struct ComponentInstance {
-    coords: Coords,
-    ty: Rc<ComponentType>,
-    orientation: Orientation,
+    pub coords: Coords,
+    pub ty: Rc<ComponentType>,
+    pub orientation: Orientation,
+    /// The initial contents of a memory, where any words past the end are zero
+    pub contents: im::Vector<u64>,
+    /// A propagation delay for just this component, instead of the one for its type
+    pub delay: Option<u64>,
+    /// What the file it was imported from called it, if anything
+    pub label: String,
 }
 
 impl ComponentInstance {
@@ -140,6 +308,27 @@ impl ComponentInstance {
             coords,
             ty,
             orientation,
+            contents: im::Vector::new(),
+            delay: None,
+            label: String::new(),
+        }
+    }
+
+    pub fn delay(&self) -> u64 {
+        self.delay.unwrap_or(self.ty.delay)
+    }
+
+    /// A name for this component given its id, like `ADD12`
+    pub fn name(&self, id: usize) -> String {
+        format!("{}{}", self.ty.kind.name().replace(' ', ""), id)
+    }
+
+    /// The name an input or output goes by when comparing circuits: its label, if it has one
+    pub fn port_name(&self, id: usize) -> String {
+        if self.label.is_empty() {
+            self.name(id)
+        } else {
+            self.label.clone()
         }
     }
 
@@ -150,7 +339,25 @@ impl ComponentInstance {
     pub fn paint(&self, ctx: &mut PaintCtx) {
         ctx.with_save(|ctx| {
             ctx.transform(self.rotate_about_anchor());
-            self.ty.icon.to_piet(IDENTITY, ctx);
+            match self.ty.symbol {
+                Symbol::Svg(ref icon) => icon.to_piet(IDENTITY, ctx),
+                Symbol::Box => {
+                    let rect = Rect::from_origin_size(Point::ORIGIN, self.ty.size).inset(-1.0);
+                    ctx.stroke(rect, &Color::BLACK, 2.0);
+                    let label = ctx
+                        .text()
+                        .new_text_layout(self.ty.kind.name())
+                        .text_color(Color::BLACK)
+                        .build()
+                        .unwrap();
+                    let origin = (self.ty.size.to_vec2() - label.size().to_vec2()) / 2.0;
+                    ctx.draw_text(&label, origin.to_point());
+                },
+                Symbol::Display => {
+                    let rect = Rect::from_origin_size(Point::ORIGIN, self.ty.size).inset(-1.0);
+                    ctx.stroke(rect, &Color::BLACK, 2.0);
+                },
+            }
 
             ctx.transform(Affine::translate(self.anchor_offset()));
             for pin in self.ty.pins.iter() {
@@ -162,11 +369,94 @@ impl ComponentInstance {
         });
     }
 
+    /// Paints what a display or input shows, given the values at its pins
+    fn paint_face(&self, ctx: &mut PaintCtx, values: &im::Vector<Value>, state: &State) {
+        let value = |i: usize| values.get(i).copied().unwrap_or_else(|| Value::zero(1));
+        let rect = Rect::from_origin_size(Point::ORIGIN, self.ty.size);
+        ctx.with_save(|ctx| {
+            ctx.transform(self.rotate_about_anchor());
+            match self.ty.kind {
+                ComponentKind::Input { width } => {
+                    let left = (rect.width() - width as f64 * 16.0) / 2.0;
+                    for i in 0..width {
+                        let bit = if value(0).bit(width - 1 - i) {
+                            "1"
+                        } else {
+                            "0"
+                        };
+                        let label = ctx
+                            .text()
+                            .new_text_layout(bit)
+                            .text_color(Color::BLACK)
+                            .build()
+                            .unwrap();
+                        let cell = Point::new(left + i as f64 * 16.0 + 8.0, rect.height() / 2.0);
+                        ctx.draw_text(&label, cell - label.size().to_vec2() / 2.0);
+                    }
+                },
+                ComponentKind::SevenSegment => {
+                    let segments = (0..7).fold(0, |s, i| s | (value(i).is_high() as u8) << i);
+                    paint_segments(ctx, rect, segments, value(7).is_high());
+                },
+                ComponentKind::HexDisplay => {
+                    let digit = SEGMENTS[value(0).bits() as usize & 0xf];
+                    paint_segments(ctx, rect, digit, value(1).is_high());
+                },
+                ComponentKind::DotMatrix { rows, cols } => {
+                    for r in 0..rows {
+                        for c in 0..cols {
+                            let lit = value(0).bit(r) && value(1).bit(c);
+                            let center = Point::new((c + 1) as f64 * 16.0, (r + 1) as f64 * 16.0);
+                            ctx.fill(Circle::new(center, 6.0), if lit { &LIT } else { &UNLIT });
+                        }
+                    }
+                },
+                ComponentKind::Probe { radix } => {
+                    let label = ctx
+                        .text()
+                        .new_text_layout(value(0).format(radix))
+                        .font(FontFamily::MONOSPACE, 12.0)
+                        .text_color(Color::BLACK)
+                        .build()
+                        .unwrap();
+                    ctx.draw_text(&label, rect.center() - label.size().to_vec2() / 2.0);
+                },
+                ComponentKind::Keyboard | ComponentKind::Tty { .. } => {
+                    for (i, line) in state.text.split('\n').enumerate() {
+                        let label = ctx
+                            .text()
+                            .new_text_layout(shown_line(line))
+                            .font(FontFamily::MONOSPACE, 12.0)
+                            .text_color(Color::BLACK)
+                            .build()
+                            .unwrap();
+                        ctx.draw_text(&label, (8.0, i as f64 * 16.0 + 8.0));
+                    }
+                },
+                _ => {},
+            }
+        });
+    }
+
     fn anchor_offset(&self) -> Vec2 {
         self.ty.anchor_offset(Orientation::North)
     }
 
-    fn rotate_about_anchor(&self) -> Affine {
+    /// Where a pin ends up on the grid, once the component is placed and rotated
+    pub fn pin_coords(&self, i: usize) -> Coords {
+        let Coords { x, y } = self.ty.pins[i].pos;
+        let offset = match self.orientation {
+            Orientation::North => Coords::new(x, y),
+            Orientation::East => Coords::new(-y, x),
+            Orientation::South => Coords::new(-x, -y),
+            Orientation::West => Coords::new(y, -x),
+        };
+        self.coords + offset
+    }
+
+    /// Turns the component's symbol, drawn facing north with its top left corner at the origin,
+    /// to face its way with its bounding rect's top left corner at the origin
+    pub fn rotate_about_anchor(&self) -> Affine {
         let recenter = match self.orientation {
             Orientation::North => IDENTITY,
             Orientation::East => Affine::translate(Vec2::new(self.ty.size.height, 0.0)),
@@ -190,6 +480,9 @@ pub struct ComponentState {
     pub instance: ComponentInstance,
     selected: bool,
     dragging: Option<(Coords, Point)>,
+    /// The value at each pin as of the last simulation
+    pub values: im::Vector<Value>,
+    pub state: State,
 }
 
 impl ComponentState {
@@ -198,8 +491,103 @@ impl ComponentState {
             instance: ComponentInstance::new(coords, ty, orientation),
             selected: false,
             dragging: None,
+            values: im::Vector::new(),
+            state: State::default(),
+        }
+    }
+
+    /// Flips the bit of an input under the mouse, or shows a probe in the next radix
+    fn poke(&mut self, pos: Point) {
+        match self.instance.ty.kind {
+            ComponentKind::Input { width } => {
+                let pos = self.instance.rotate_about_anchor().inverse() * pos;
+                let left = (self.instance.ty.size.width - width as f64 * 16.0) / 2.0;
+                let i = ((pos.x - left) / 16.0)
+                    .floor()
+                    .max(0.0)
+                    .min(width as f64 - 1.0) as u8;
+                self.state.value ^= 1 << (width - 1 - i);
+            },
+            ComponentKind::Probe { radix } => {
+                let kind = ComponentKind::Probe {
+                    radix: radix.next(),
+                };
+                self.instance.ty = Rc::new(ComponentType::new(kind));
+            },
+            _ => {},
         }
     }
+
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+}
+
+pub const LIT: Color = Color::rgb8(0xe0, 0x10, 0x10);
+pub const UNLIT: Color = Color::rgb8(0xf0, 0xe0, 0xe0);
+
+/// The segments lit for each hex digit, with `a` in the least significant bit
+pub const SEGMENTS: [u8; 16] = [
+    0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f, 0x77, 0x7c, 0x39, 0x5e, 0x79, 0x71,
+];
+
+/// The segments of a seven-segment digit in the middle of `rect`, in the order of their pins,
+/// with the decimal point and the thickness of a segment
+pub fn segment_shapes(rect: Rect) -> ([Line; 7], Circle, f64) {
+    let h = rect.height() - 24.0;
+    let (w, t) = (h / 2.0, 4.0);
+    let (x0, y0) = (rect.center().x - w / 2.0, rect.y0 + 12.0);
+    let (x1, ym, y1) = (x0 + w, y0 + h / 2.0, y0 + h);
+    let ends = [
+        ((x0, y0), (x1, y0)),
+        ((x1, y0), (x1, ym)),
+        ((x1, ym), (x1, y1)),
+        ((x0, y1), (x1, y1)),
+        ((x0, ym), (x0, y1)),
+        ((x0, y0), (x0, ym)),
+        ((x0, ym), (x1, ym)),
+    ];
+    let lines = ends.map(|(start, end)| {
+        let line = Line::new(start, end);
+        // pull the ends in so that neighbouring segments don't run into each other
+        let gap = (line.p1 - line.p0).normalize() * (t * 0.75);
+        Line::new(line.p0 + gap, line.p1 - gap)
+    });
+    (lines, Circle::new((x1 + 2.0 * t, y1), t * 0.75), t)
+}
+
+/// Paints a seven-segment digit in the middle of `rect`
+fn paint_segments(ctx: &mut PaintCtx, rect: Rect, segments: u8, dp: bool) {
+    let (lines, point, t) = segment_shapes(rect);
+    for (i, &line) in lines.iter().enumerate() {
+        let color = if segments >> i & 1 == 1 { &LIT } else { &UNLIT };
+        ctx.stroke(line, color, t);
+    }
+    ctx.fill(point, if dp { &LIT } else { &UNLIT });
+}
+
+/// A line of a keyboard or TTY as it is shown, with control characters waiting in a keyboard
+/// shown as their symbols
+pub fn shown_line(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            c if c.is_ascii_control() => std::char::from_u32(0x2400 + c as u32).unwrap(),
+            c => c,
+        })
+        .collect()
+}
+
+/// The ASCII character a key types, if any
+pub fn ascii(key: &druid::keyboard_types::Key) -> Option<char> {
+    use druid::keyboard_types::Key;
+    match key {
+        Key::Character(s) => s.chars().next().filter(|c| s.len() == 1 && c.is_ascii()),
+        Key::Enter => Some('\n'),
+        Key::Backspace => Some('\x08'),
+        Key::Tab => Some('\t'),
+        Key::Escape => Some('\x1b'),
+        _ => None,
+    }
 }
 
 pub struct Component(pub usize);
@@ -238,7 +626,12 @@ impl Widget<ComponentState> for Component {
                     ctx.set_handled();
                 }
             },
-            Event::MouseUp(_) => {
+            Event::MouseUp(ev) => {
+                if let Some((coords, _)) = data.dragging {
+                    if coords == data.instance.coords {
+                        data.poke(ev.pos);
+                    }
+                }
                 data.dragging = None;
                 ctx.set_active(false);
             },
@@ -248,6 +641,8 @@ impl Widget<ComponentState> for Component {
                     data.instance.coords = coords + Coords::from_widget_space(delta);
                 }
             },
+            // the canvas passes typing on to keyboards
+            Event::KeyDown(_) if data.instance.ty.kind == ComponentKind::Keyboard => {},
             Event::KeyDown(ev) => {
                 use druid::keyboard_types::Key;
                 let mut orientation = data.instance.orientation;
@@ -256,6 +651,19 @@ impl Widget<ComponentState> for Component {
                     Key::Character(ref s) if s == "a" => orientation = Orientation::West,
                     Key::Character(ref s) if s == "s" => orientation = Orientation::South,
                     Key::Character(ref s) if s == "d" => orientation = Orientation::East,
+                    Key::Character(ref s) if s == "e" => {
+                        ctx.submit_command(EXPAND_COMPONENT.with(self.0));
+                        ctx.set_handled();
+                    },
+                    Key::Character(ref s) if s == "-" || s == "=" => {
+                        let delay = data.instance.delay();
+                        data.instance.delay = Some(if s == "=" {
+                            delay + 1
+                        } else {
+                            delay.saturating_sub(1)
+                        });
+                        ctx.set_handled();
+                    },
                     _ => {},
                 }
                 if orientation != data.instance.orientation {
@@ -287,11 +695,14 @@ impl Widget<ComponentState> for Component {
 
     fn update(
         &mut self,
-        _ctx: &mut druid::UpdateCtx,
-        _old_data: &ComponentState,
-        _data: &ComponentState,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &ComponentState,
+        data: &ComponentState,
         _env: &druid::Env,
     ) {
+        if !old_data.values.same(&data.values) || !old_data.state.same(&data.state) {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -307,6 +718,7 @@ impl Widget<ComponentState> for Component {
 
     fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &ComponentState, _env: &druid::Env) {
         data.instance.paint(ctx);
+        data.instance.paint_face(ctx, &data.values, &data.state);
         if data.selected {
             // we're painting in widget space already so the bounding rect needs to be translated
             // back
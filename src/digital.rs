@@ -0,0 +1,641 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::Path,
+};
+
+use druid::{im, FileSpec, Selector};
+
+use crate::{
+    canvas::Coords,
+    component::{ComponentState, Orientation},
+    import::{Assembly, Import},
+    kind::{ComponentKind, MAX_WIDTH},
+    logisim::escape,
+    sim::Netlist,
+    value::Radix,
+    wire::{WireSegment, WireState},
+};
+
+pub const IMPORT_DIGITAL: Selector<druid::FileInfo> = Selector::new("logicism/import-digital");
+pub const EXPORT_DIGITAL: Selector<druid::FileInfo> = Selector::new("logicism/export-digital");
+
+pub const DIGITAL: FileSpec = FileSpec::new("Digital circuit", &["dig"]);
+
+/// Pixels to a cell of the grid, which is the spacing of Digital's grid. A two input gate has the
+/// same pins in both at this scale.
+const SCALE: isize = 20;
+
+/// The way a component faces after Digital's rotation, which turns it anticlockwise from facing
+/// east a quarter turn at a time
+fn orientation(rotation: u8) -> Orientation {
+    match rotation % 4 {
+        1 => Orientation::North,
+        2 => Orientation::West,
+        3 => Orientation::South,
+        _ => Orientation::East,
+    }
+}
+
+fn rotation(orientation: Orientation) -> u8 {
+    match orientation {
+        Orientation::East => 0,
+        Orientation::North => 1,
+        Orientation::West => 2,
+        Orientation::South => 3,
+    }
+}
+
+/// An offset for a component facing east, turned to match one facing `orientation`
+fn turn(x: isize, y: isize, orientation: Orientation) -> Coords {
+    match orientation {
+        Orientation::East => Coords::new(x, y),
+        Orientation::North => Coords::new(y, -x),
+        Orientation::West => Coords::new(-x, -y),
+        Orientation::South => Coords::new(-y, x),
+    }
+}
+
+/// Where the output of one of Digital's gates is, from its first input as if it faced east. A
+/// bubble on the output makes it a cell longer.
+fn gate_output(kind: ComponentKind, wide: bool) -> Coords {
+    let length = if wide { 4 } else { 3 };
+    match kind {
+        ComponentKind::Nand | ComponentKind::Nor => Coords::new(length + 1, 1),
+        ComponentKind::Not => Coords::new(length - 1, 0),
+        _ => Coords::new(length, 1),
+    }
+}
+
+fn gate_name(kind: ComponentKind) -> &'static str {
+    match kind {
+        ComponentKind::Not => "Not",
+        ComponentKind::And => "And",
+        ComponentKind::Or => "Or",
+        ComponentKind::Nand => "NAnd",
+        ComponentKind::Nor => "NOr",
+        _ => "XOr",
+    }
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn position(node: roxmltree::Node) -> Result<Coords, String> {
+    let get = |name| {
+        node.attribute(name)
+            .and_then(|value| value.parse::<f64>().ok())
+            .map(|value| (value / SCALE as f64).round() as isize)
+            .ok_or_else(|| format!("A {} has no {}", node.tag_name().name(), name))
+    };
+    Ok(Coords::new(get("x")?, get("y")?))
+}
+
+/// The attributes of an element, which Digital gives as `<entry>` children each with the name
+/// and then the value. A rotation is kept as its number of quarter turns and a set of inverted
+/// inputs as how many there are.
+fn entries(node: roxmltree::Node) -> BTreeMap<String, String> {
+    let mut attrs = BTreeMap::new();
+    for entry in node.children().filter(|n| n.has_tag_name("entry")) {
+        let mut parts = entry.children().filter(|n| n.is_element());
+        let (name, value) = match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => (name, value),
+            _ => continue,
+        };
+        let value = match value.tag_name().name() {
+            "rotation" => value.attribute("rotation").unwrap_or("0").to_owned(),
+            "inverterConfig" => value
+                .children()
+                .filter(|n| n.is_element())
+                .count()
+                .to_string(),
+            _ => value.text().unwrap_or("").to_owned(),
+        };
+        attrs.insert(name.text().unwrap_or("").to_owned(), value);
+    }
+    attrs
+}
+
+/// The widths of the parts a splitter breaks its bus into, written like "4,4", or "4*2" for the
+/// same
+fn parts(text: &str) -> Option<Vec<u32>> {
+    let mut parts = Vec::new();
+    for part in text.split(',') {
+        match part.split_once('*') {
+            Some((bits, count)) => {
+                let bits: u32 = bits.trim().parse().ok()?;
+                let count = count.trim().parse().ok()?;
+                parts.extend(std::iter::repeat(bits).take(count));
+            },
+            None => parts.push(part.trim().parse().ok()?),
+        }
+    }
+    Some(parts)
+}
+
+/// A component in a Digital circuit
+struct Element {
+    name: String,
+    attrs: BTreeMap<String, String>,
+    pos: Coords,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(String::as_str)
+    }
+
+    fn number(&self, name: &str, default: u32) -> u32 {
+        self.attr(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    }
+
+    fn orientation(&self) -> Orientation {
+        orientation(self.number("rotation", 0) as u8)
+    }
+
+    /// The width, if it isn't too wide
+    fn width(&self) -> Option<u8> {
+        match self.number("Bits", 1) {
+            width if width <= MAX_WIDTH as u32 => Some(width as u8),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &str {
+        self.attr("Label").unwrap_or("")
+    }
+
+    /// A point on the component, from its position as if it faced east
+    fn at(&self, offset: Coords) -> Coords {
+        self.pos + turn(offset.x, offset.y, self.orientation())
+    }
+}
+
+fn add(assembly: &mut Assembly, element: &Element, tunnels: &mut BTreeMap<String, Coords>) {
+    let orientation = element.orientation();
+    let kind = match element.name.as_str() {
+        "Not" => Some(ComponentKind::Not),
+        "And" => Some(ComponentKind::And),
+        "Or" => Some(ComponentKind::Or),
+        "NAnd" => Some(ComponentKind::Nand),
+        "NOr" => Some(ComponentKind::Nor),
+        "XOr" => Some(ComponentKind::Xor),
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        let inputs = if kind == ComponentKind::Not { 1 } else { 2 };
+        if element.number("Bits", 1) != 1 {
+            assembly.leave_out(format!("{} wider than one bit", element.name));
+        } else if element.number("inverterConfig", 0) > 0 {
+            assembly.leave_out(format!("{} with inverted inputs", element.name));
+        } else if element.number("Inputs", 2) != 2 && kind != ComponentKind::Not {
+            assembly.leave_out(format!("{} without two inputs", element.name));
+        } else {
+            let wide = element.attr("wideShape") == Some("true");
+            let index = assembly.place(kind, orientation, 0, element.pos);
+            if inputs == 2 {
+                assembly.connect(index, 1, element.at(Coords::new(0, 2)));
+            }
+            assembly.connect(index, inputs, element.at(gate_output(kind, wide)));
+        }
+        return;
+    }
+
+    match element.name.as_str() {
+        "In" | "Button" => match element.width() {
+            Some(width) => {
+                let kind = ComponentKind::Input { width };
+                let index = assembly.place(kind, orientation, 0, element.pos);
+                assembly.label(index, element.label());
+            },
+            None => assembly.leave_out("In wider than 32 bits"),
+        },
+        "Out" | "Probe" | "LED" => {
+            let radix = match element.attr("intFormat") {
+                Some("bin") => Radix::Binary,
+                Some("dec") | Some("signed") => Radix::Decimal,
+                _ if element.name == "LED" => Radix::Binary,
+                _ => Radix::Hex,
+            };
+            let kind = ComponentKind::Probe { radix };
+            let index = assembly.place(kind, orientation, 0, element.pos);
+            assembly.label(index, element.label());
+        },
+        "Clock" => {
+            let index = assembly.place(ComponentKind::Clock, orientation, 0, element.pos);
+            assembly.label(index, element.label());
+        },
+        "Const" | "Ground" | "VDD" => {
+            let width = match element.width() {
+                Some(width) => width,
+                None => return assembly.leave_out(format!("{} wider than 32 bits", element.name)),
+            };
+            let all = u64::MAX >> (64 - width as u32);
+            // ground hangs below its pin and the supply sits above it
+            let (value, orientation) = match element.name.as_str() {
+                "Ground" => (0, Orientation::North),
+                "VDD" => (all, Orientation::South),
+                _ => {
+                    let value = element.attr("Value").unwrap_or("1");
+                    let value = value.parse::<i64>().map_or(1, |value| value as u64);
+                    (value & all, orientation)
+                },
+            };
+            let kind = ComponentKind::Constant { width, value };
+            assembly.place(kind, orientation, 0, element.pos);
+        },
+        "Splitter" => {
+            let split = |name, default| parts(element.attr(name).unwrap_or(default));
+            let single = |parts: &[u32]| parts.iter().all(|&bits| bits == 1);
+            match (
+                split("Input Splitting", "4,4"),
+                split("Output Splitting", "8"),
+            ) {
+                (Some(inputs), Some(outputs))
+                    if inputs.len() == 1
+                        && single(&outputs)
+                        && outputs.len() as u32 == inputs[0]
+                        && inputs[0] <= MAX_WIDTH as u32 =>
+                {
+                    let width = inputs[0] as u8;
+                    let kind = ComponentKind::Split { width };
+                    let index = assembly.place(kind, orientation, 0, element.pos);
+                    for bit in 0..width as usize {
+                        let end = element.at(Coords::new(1, bit as isize));
+                        assembly.connect(index, 1 + bit, end);
+                    }
+                },
+                (Some(inputs), Some(outputs))
+                    if outputs.len() == 1
+                        && single(&inputs)
+                        && inputs.len() as u32 == outputs[0]
+                        && outputs[0] <= MAX_WIDTH as u32 =>
+                {
+                    let width = outputs[0] as u8;
+                    let kind = ComponentKind::Join { width };
+                    let index = assembly.place(kind, orientation, 0, element.pos);
+                    for bit in 1..width as usize {
+                        let end = element.at(Coords::new(0, bit as isize));
+                        assembly.connect(index, bit, end);
+                    }
+                    let end = element.at(Coords::new(1, 0));
+                    assembly.connect(index, width as usize, end);
+                },
+                _ => assembly.leave_out("Splitter between buses of more than one bit"),
+            }
+        },
+        "Tunnel" => {
+            let name = element.attr("NetName").unwrap_or("").to_owned();
+            match tunnels.get(&name) {
+                Some(&first) => assembly.link(first, element.pos),
+                None => {
+                    tunnels.insert(name, element.pos);
+                },
+            }
+        },
+        // notes and boxes drawn on the circuit
+        "Text" | "Rectangle" => {},
+        other => assembly.leave_out(other),
+    }
+}
+
+/// Reads a circuit saved by Digital
+pub fn import(text: &str) -> Result<Import, String> {
+    let document = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+    let root = document.root_element();
+    if !root.has_tag_name("circuit") || child(root, "visualElements").is_none() {
+        return Err("This isn't a Digital circuit".to_owned());
+    }
+
+    let mut assembly = Assembly::new();
+    let mut wires = Vec::new();
+    for wire in child(root, "wires")
+        .into_iter()
+        .flat_map(|list| list.children())
+        .filter(|n| n.has_tag_name("wire"))
+    {
+        let end = |name| child(wire, name).ok_or_else(|| "A wire is missing an end".to_owned());
+        wires.push((position(end("p1")?)?, position(end("p2")?)?));
+    }
+    assembly.add_wires(&wires);
+
+    let mut tunnels = BTreeMap::new();
+    for node in child(root, "visualElements")
+        .into_iter()
+        .flat_map(|list| list.children())
+        .filter(|n| n.has_tag_name("visualElement"))
+    {
+        let name = child(node, "elementName").and_then(|n| n.text());
+        let pos = child(node, "pos").ok_or_else(|| "A component has no position".to_owned())?;
+        let element = Element {
+            name: name.unwrap_or("").to_owned(),
+            attrs: child(node, "elementAttributes").map_or_else(BTreeMap::new, entries),
+            pos: position(pos)?,
+        };
+        add(&mut assembly, &element, &mut tunnels);
+    }
+    assembly.note("Imported from Digital.");
+    Ok(assembly.finish())
+}
+
+pub fn read_digital(path: &Path) -> Result<Import, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    import(&text)
+}
+
+/// A circuit being written out for Digital
+#[derive(Default)]
+struct Sheet {
+    /// Each component as a `<visualElement>` element
+    elements: Vec<String>,
+    wires: Vec<(Coords, Coords)>,
+    /// Everywhere that something connects, where a wire running through has to be cut
+    points: BTreeSet<Coords>,
+}
+
+impl Sheet {
+    /// Adds a component, with each attribute given as its name, the element its value goes in
+    /// and the value
+    fn element(&mut self, name: &str, pos: Coords, attrs: &[(&str, &str, String)]) {
+        let mut out = String::from("    <visualElement>\n");
+        writeln!(out, "      <elementName>{}</elementName>", escape(name)).unwrap();
+        out.push_str("      <elementAttributes>\n");
+        for (name, ty, value) in attrs {
+            out.push_str("        <entry>\n");
+            writeln!(out, "          <string>{}</string>", name).unwrap();
+            match *ty {
+                "rotation" => writeln!(out, "          <rotation rotation=\"{}\"/>", value),
+                _ => writeln!(out, "          <{}>{}</{}>", ty, escape(value), ty),
+            }
+            .unwrap();
+            out.push_str("        </entry>\n");
+        }
+        out.push_str("      </elementAttributes>\n");
+        writeln!(
+            out,
+            "      <pos x=\"{}\" y=\"{}\"/>",
+            pos.x * SCALE,
+            pos.y * SCALE
+        )
+        .unwrap();
+        out.push_str("    </visualElement>\n");
+        self.elements.push(out);
+        self.points.insert(pos);
+    }
+
+    fn wire(&mut self, a: Coords, b: Coords) {
+        if a != b {
+            self.wires.push((a, b));
+            self.points.insert(a);
+            self.points.insert(b);
+        }
+    }
+}
+
+/// Writes the circuit out in Digital's format, with the components where they are. Returns the
+/// names of the components that Digital has nothing like.
+pub fn export(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> (String, Vec<String>) {
+    let netlist = Netlist::build(components, wires);
+    let mut sheet = Sheet::default();
+    let mut left_out = Vec::new();
+    for (&id, component) in components.iter() {
+        let instance = &component.instance;
+        let name = instance.name(id);
+        let orientation = instance.orientation;
+        let pin = |i| instance.pin_coords(i);
+        let at = |origin: Coords, x, y| origin + turn(x, y, orientation);
+        let rotation = ("rotation", "rotation", rotation(orientation).to_string());
+        match instance.ty.kind {
+            kind @ ComponentKind::Not
+            | kind @ ComponentKind::And
+            | kind @ ComponentKind::Or
+            | kind @ ComponentKind::Nand
+            | kind @ ComponentKind::Nor
+            | kind @ ComponentKind::Xor => {
+                let output = gate_output(kind, false);
+                let y = instance.ty.pins.len() - 1;
+                sheet.element(gate_name(kind), pin(0), &[rotation]);
+                sheet.wire(at(pin(0), output.x, output.y), pin(y));
+            },
+            ComponentKind::Input { width } => {
+                let attrs = [
+                    rotation,
+                    ("Label", "string", name),
+                    ("Bits", "int", width.to_string()),
+                ];
+                sheet.element("In", pin(0), &attrs);
+            },
+            ComponentKind::Clock => {
+                sheet.element("Clock", pin(0), &[rotation, ("Label", "string", name)]);
+            },
+            ComponentKind::Probe { radix } => {
+                let width = netlist.width(netlist.pins[&id][0]);
+                let format = match radix {
+                    Radix::Binary => "bin",
+                    Radix::Decimal => "dec",
+                    Radix::Hex => "hex",
+                };
+                let attrs = [
+                    rotation,
+                    ("Label", "string", name),
+                    ("Bits", "int", width.to_string()),
+                    ("intFormat", "intFormat", format.to_owned()),
+                ];
+                sheet.element("Out", pin(0), &attrs);
+            },
+            ComponentKind::Constant { width, value } => {
+                let attrs = [
+                    rotation,
+                    ("Value", "long", value.to_string()),
+                    ("Bits", "int", width.to_string()),
+                ];
+                sheet.element("Const", pin(0), &attrs);
+            },
+            ComponentKind::Split { width } => {
+                let bits = vec!["1"; width as usize].join(",");
+                let attrs = [
+                    rotation,
+                    ("Input Splitting", "string", width.to_string()),
+                    ("Output Splitting", "string", bits),
+                ];
+                sheet.element("Splitter", pin(0), &attrs);
+                for bit in 0..width as usize {
+                    sheet.wire(at(pin(0), 1, bit as isize), pin(1 + bit));
+                }
+            },
+            ComponentKind::Join { width } => {
+                let bits = vec!["1"; width as usize].join(",");
+                let attrs = [
+                    rotation,
+                    ("Input Splitting", "string", bits),
+                    ("Output Splitting", "string", width.to_string()),
+                ];
+                sheet.element("Splitter", pin(0), &attrs);
+                sheet.wire(at(pin(0), 1, 0), pin(width as usize));
+            },
+            _ => {
+                left_out.push(name);
+                continue;
+            },
+        }
+        for i in 0..instance.ty.pins.len() {
+            sheet.points.insert(pin(i));
+        }
+    }
+    for wire in wires.values() {
+        for segment in wire.segments.iter() {
+            let (a, b) = segment.ends();
+            sheet.wire(a, b);
+        }
+    }
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <circuit>\n  \
+         <version>2</version>\n  \
+         <attributes/>\n  \
+         <visualElements>\n",
+    );
+    for element in sheet.elements.iter() {
+        out.push_str(element);
+    }
+    out.push_str("  </visualElements>\n  <wires>\n");
+    for &(a, b) in sheet.wires.iter() {
+        for (a, b) in WireSegment::cut(a, b, &sheet.points) {
+            writeln!(
+                out,
+                "    <wire>\n      <p1 x=\"{}\" y=\"{}\"/>\n      <p2 x=\"{}\" \
+                 y=\"{}\"/>\n    </wire>",
+                a.x * SCALE,
+                a.y * SCALE,
+                b.x * SCALE,
+                b.y * SCALE
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("  </wires>\n  <measurementOrdering/>\n</circuit>\n");
+    (out, left_out)
+}
+
+pub fn write_digital(
+    path: &Path,
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Result<Vec<String>, String> {
+    let (text, left_out) = export(components, wires);
+    std::fs::write(path, text).map_err(|e| e.to_string())?;
+    Ok(left_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canvas::CanvasState, import::tests::truth_table};
+
+    /// A circuit with the components and wires given, each in Digital's own markup
+    fn circuit(elements: &[&str], wires: &[((isize, isize), (isize, isize))]) -> String {
+        let mut out = String::from("<circuit>\n  <version>2</version>\n  <visualElements>\n");
+        for element in elements {
+            out.push_str(element);
+        }
+        out.push_str("  </visualElements>\n  <wires>\n");
+        for ((x1, y1), (x2, y2)) in wires {
+            writeln!(
+                out,
+                "    <wire><p1 x=\"{}\" y=\"{}\"/><p2 x=\"{}\" y=\"{}\"/></wire>",
+                x1, y1, x2, y2
+            )
+            .unwrap();
+        }
+        out.push_str("  </wires>\n</circuit>\n");
+        out
+    }
+
+    fn element(name: &str, label: &str, x: isize, y: isize) -> String {
+        let attrs = if label.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "<entry><string>Label</string><string>{}</string></entry>",
+                label
+            )
+        };
+        format!(
+            "    <visualElement><elementName>{}</elementName>\
+             <elementAttributes>{}</elementAttributes><pos x=\"{}\" y=\"{}\"/></visualElement>\n",
+            name, attrs, x, y
+        )
+    }
+
+    /// A half adder, with the gates' first inputs at their positions and outputs three cells on
+    fn half_adder() -> String {
+        let elements = [
+            element("In", "a", 100, 100),
+            element("In", "b", 100, 140),
+            element("And", "", 200, 100),
+            element("XOr", "", 200, 200),
+            element("Out", "c", 300, 120),
+            element("Out", "s", 300, 220),
+        ];
+        let elements: Vec<_> = elements.iter().map(String::as_str).collect();
+        let wires = [
+            ((100, 100), (140, 100)),
+            ((140, 100), (200, 100)),
+            ((140, 100), (140, 200)),
+            ((140, 200), (200, 200)),
+            ((100, 140), (120, 140)),
+            ((120, 140), (200, 140)),
+            ((120, 140), (120, 240)),
+            ((120, 240), (200, 240)),
+            ((260, 120), (300, 120)),
+            ((260, 220), (300, 220)),
+        ];
+        circuit(&elements, &wires)
+    }
+
+    #[test]
+    fn imports_a_half_adder() {
+        let (names, rows) = truth_table(import(&half_adder()).unwrap());
+        assert_eq!(names, ["a", "b", "c", "s"]);
+        assert_eq!(
+            rows,
+            [[0, 0, 0, 0], [0, 1, 0, 1], [1, 0, 0, 1], [1, 1, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn leaves_out_what_it_cannot_import() {
+        let counter = element("Counter", "", 100, 100);
+        let report = import(&circuit(&[&counter], &[])).unwrap().report(&[]);
+        assert!(report.contains("Counter"), "{}", report);
+        assert!(import("<project/>").is_err());
+    }
+
+    #[test]
+    fn reads_splitter_parts() {
+        assert_eq!(parts("4,4"), Some(vec![4, 4]));
+        assert_eq!(parts("1*3,2"), Some(vec![1, 1, 1, 2]));
+        assert_eq!(parts("4,x"), None);
+    }
+
+    #[test]
+    fn round_trips_its_own_export() {
+        let (_, before) = truth_table(import(&half_adder()).unwrap());
+        let mut canvas = CanvasState::new();
+        canvas.replace_with_import("", import(&half_adder()).unwrap());
+        let (text, left_out) = export(canvas.components(), canvas.wires());
+        assert!(left_out.is_empty());
+        let (_, after) = truth_table(import(&text).unwrap());
+        assert_eq!(after, before);
+    }
+}
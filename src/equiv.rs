@@ -0,0 +1,632 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::Path,
+};
+
+use druid::{im, Data, Selector};
+
+use crate::{
+    analysis::{ports, Port, MAX_TABLE_INPUTS},
+    blif,
+    component::ComponentState,
+    digital,
+    expand::GateNetlist,
+    import::Import,
+    kind::{ComponentKind, PinType},
+    logisim,
+    sim::Netlist,
+    value::{Radix, Value},
+    wire::WireState,
+    yosys,
+};
+
+pub const LOAD_REFERENCE: Selector<druid::FileInfo> = Selector::new("logicism/load-reference");
+
+/// A circuit kept to compare later versions against
+#[derive(Clone, Data)]
+pub struct Circuit {
+    pub components: im::OrdMap<usize, ComponentState>,
+    pub wires: im::OrdMap<usize, WireState>,
+}
+
+/// Reads a circuit to compare against from any of the formats that can be imported
+pub fn read_reference(path: &Path) -> Result<Import, String> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("circ") => logisim::read_logisim(path),
+        Some("dig") => digital::read_digital(path),
+        Some("json") => yosys::read_yosys(path),
+        Some("blif") => blif::read_blif(path),
+        _ => Err(format!("Can't read a circuit from {}", path.display())),
+    }
+}
+
+/// The most nodes the decision diagrams of a comparison may take before it is given up on
+const MAX_BDD_NODES: usize = 1 << 20;
+
+/// Pairs up the ports of two circuits by name, failing if any port has no match or shares its
+/// name with another. Ports paired up must be the same width.
+fn pair(what: &str, ours: Vec<Port>, theirs: Vec<Port>) -> Result<Vec<(Port, Port)>, String> {
+    for (ports, place) in [(&ours, "here"), (&theirs, "in the reference")].iter() {
+        let mut names: Vec<_> = ports.iter().map(|p| &p.name).collect();
+        names.sort();
+        if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(format!(
+                "{} names more than one of the {} {}",
+                pair[0], what, place
+            ));
+        }
+    }
+    let unmatched = |from: &[Port], to: &[Port]| {
+        from.iter()
+            .filter(|p| !to.iter().any(|q| q.name == p.name))
+            .map(|p| p.name.clone())
+            .collect::<Vec<_>>()
+    };
+    let only_ours = unmatched(&ours, &theirs);
+    let only_theirs = unmatched(&theirs, &ours);
+    if !only_ours.is_empty() || !only_theirs.is_empty() {
+        let mut problems = Vec::new();
+        if !only_ours.is_empty() {
+            problems.push(format!("{} only here", only_ours.join(", ")));
+        }
+        if !only_theirs.is_empty() {
+            problems.push(format!("{} only in the reference", only_theirs.join(", ")));
+        }
+        return Err(format!(
+            "The circuits have different {}: {}",
+            what,
+            problems.join("; ")
+        ));
+    }
+    let pairs: Vec<(Port, Port)> = ours
+        .into_iter()
+        .map(|our| {
+            let their = theirs.iter().find(|p| p.name == our.name).unwrap().clone();
+            (our, their)
+        })
+        .collect();
+    if let Some((our, their)) = pairs.iter().find(|(a, b)| a.width != b.width) {
+        return Err(format!(
+            "{} is {} bits wide but {} in the reference is {}",
+            our.name, our.width, their.name, their.width
+        ));
+    }
+    Ok(pairs)
+}
+
+/// One side of a comparison, ready to be run on any inputs
+struct Side<'a> {
+    components: &'a im::OrdMap<usize, ComponentState>,
+    wires: &'a im::OrdMap<usize, WireState>,
+    netlist: Netlist,
+}
+
+impl<'a> Side<'a> {
+    fn new(
+        components: &'a im::OrdMap<usize, ComponentState>,
+        wires: &'a im::OrdMap<usize, WireState>,
+    ) -> Result<Self, String> {
+        if let Some((id, c)) = components
+            .iter()
+            .find(|(_, c)| c.instance.ty.kind.is_sequential())
+        {
+            return Err(format!(
+                "{} holds state, so the circuit isn't combinational",
+                c.instance.name(*id)
+            ));
+        }
+        Ok(Side {
+            components,
+            wires,
+            netlist: Netlist::build(components, wires),
+        })
+    }
+
+    /// The values on `outputs` with `inputs` set to the given values, or `None` if the circuit
+    /// doesn't settle
+    fn run(&self, inputs: &[(usize, u64)], outputs: &[usize]) -> Option<Vec<Value>> {
+        let mut components = self.components.clone();
+        let mut wires = self.wires.clone();
+        for &(id, value) in inputs {
+            components.get_mut(&id).unwrap().state.value = value;
+        }
+        self.netlist.settle(&mut components, &mut wires).ok()?;
+        Some(
+            outputs
+                .iter()
+                .map(|id| {
+                    let values = &components[id].values;
+                    values.get(0).copied().unwrap_or(Value::floating(1))
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Whether the circuit on the canvas does the same as `reference`, as a report saying which
+/// input ports and outputs were paired up and, if they differ, inputs they give different
+/// outputs for. Circuits with few input bits are run on every combination of inputs; larger ones
+/// are broken down into gates and compared symbolically, assuming no value is ever left floating.
+pub fn compare(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+    reference: &Circuit,
+) -> Result<String, String> {
+    let ours = Side::new(components, wires)?;
+    let theirs = Side::new(&reference.components, &reference.wires)?;
+    let (our_inputs, our_outputs) = ports(components, &ours.netlist);
+    let (their_inputs, their_outputs) = ports(&reference.components, &theirs.netlist);
+    if our_outputs.is_empty() {
+        return Err("Place probes on the outputs".to_owned());
+    }
+    let inputs = pair("inputs", our_inputs, their_inputs)?;
+    let outputs = pair("outputs", our_outputs, their_outputs)?;
+
+    let mut report = String::new();
+    for (what, pairs) in [("Inputs", &inputs), ("Outputs", &outputs)] {
+        let names: Vec<_> = pairs
+            .iter()
+            .map(|(our, their)| {
+                if our.name == their.name {
+                    our.name.clone()
+                } else {
+                    format!("{} ~ {}", our.name, their.name)
+                }
+            })
+            .collect();
+        report.push_str(&format!("{}: {}\n", what, names.join(", ")));
+    }
+
+    let bits: u32 = inputs.iter().map(|(port, _)| port.width as u32).sum();
+    let counterexample = if bits <= MAX_TABLE_INPUTS as u32 {
+        report.push_str(&format!(
+            "Ran both on all {} combinations of inputs\n\n",
+            1u64 << bits
+        ));
+        exhaustive(&ours, &theirs, &inputs, &outputs, bits)?
+    } else {
+        report.push_str(&format!(
+            "Compared {} input bits symbolically, with decision diagrams\n\n",
+            bits
+        ));
+        symbolic(&ours, &theirs, &inputs, &outputs)?
+    };
+
+    let combination = match counterexample {
+        None => {
+            report.push_str("The circuits are equivalent");
+            return Ok(report);
+        },
+        Some(combination) => combination,
+    };
+    report.push_str("The circuits differ, for example with inputs\n");
+    for ((port, _), &value) in inputs.iter().zip(&combination) {
+        let value = Value::new(port.width, value);
+        report.push_str(&format!(
+            "  {} = {}\n",
+            port.name,
+            value.format(Radix::Binary)
+        ));
+    }
+    let (ours_set, theirs_set) = set_inputs(&inputs, &combination);
+    let our_ids: Vec<_> = outputs.iter().map(|(our, _)| our.id).collect();
+    let their_ids: Vec<_> = outputs.iter().map(|(_, their)| their.id).collect();
+    let our_values = ours.run(&ours_set, &our_ids);
+    let their_values = theirs.run(&theirs_set, &their_ids);
+    if let (Some(our_values), Some(their_values)) = (our_values, their_values) {
+        report.push_str("which give\n");
+        for (((port, _), ours), theirs) in outputs.iter().zip(our_values).zip(their_values) {
+            let mark = if ours == theirs { "" } else { "  <- differs" };
+            report.push_str(&format!(
+                "  {} = {} here and {} in the reference{}\n",
+                port.name,
+                ours.format(Radix::Binary),
+                theirs.format(Radix::Binary),
+                mark
+            ));
+        }
+    }
+    Ok(report)
+}
+
+/// Input components by id, and the values to set them to
+type Settings = Vec<(usize, u64)>;
+
+/// The settings of the inputs on each side for the same values
+fn set_inputs(inputs: &[(Port, Port)], values: &[u64]) -> (Settings, Settings) {
+    inputs
+        .iter()
+        .zip(values)
+        .map(|((our, their), &value)| ((our.id, value), (their.id, value)))
+        .unzip()
+}
+
+/// The value of each input in the first combination the two sides give different outputs for
+fn exhaustive(
+    ours: &Side,
+    theirs: &Side,
+    inputs: &[(Port, Port)],
+    outputs: &[(Port, Port)],
+    bits: u32,
+) -> Result<Option<Vec<u64>>, String> {
+    let our_ids: Vec<_> = outputs.iter().map(|(our, _)| our.id).collect();
+    let their_ids: Vec<_> = outputs.iter().map(|(_, their)| their.id).collect();
+    for combination in 0..1u64 << bits {
+        let mut shift = bits;
+        let values: Vec<u64> = inputs
+            .iter()
+            .map(|(port, _)| {
+                shift -= port.width as u32;
+                combination >> shift & Value::mask(port.width)
+            })
+            .collect();
+        let (ours_set, theirs_set) = set_inputs(inputs, &values);
+        let unsettled = |values: &[u64]| {
+            let values: Vec<_> = inputs
+                .iter()
+                .zip(values)
+                .map(|((port, _), &v)| Value::new(port.width, v).format(Radix::Binary))
+                .collect();
+            format!("doesn't settle for inputs {}", values.join(" "))
+        };
+        let our_values = ours
+            .run(&ours_set, &our_ids)
+            .ok_or_else(|| format!("The circuit {}", unsettled(&values)))?;
+        let their_values = theirs
+            .run(&theirs_set, &their_ids)
+            .ok_or_else(|| format!("The reference {}", unsettled(&values)))?;
+        if our_values != their_values {
+            return Ok(Some(values));
+        }
+    }
+    Ok(None)
+}
+
+/// The value of each input for some combination the two sides give different outputs for,
+/// found by building a decision diagram of every output bit over the input bits
+fn symbolic(
+    ours: &Side,
+    theirs: &Side,
+    inputs: &[(Port, Port)],
+    outputs: &[(Port, Port)],
+) -> Result<Option<Vec<u64>>, String> {
+    let mut bdd = Bdd::new();
+    // the input bits are the variables, the first input's most significant bit first
+    let mut our_vars = HashMap::new();
+    let mut their_vars = HashMap::new();
+    let mut var = 0;
+    for (our, their) in inputs {
+        for bit in (0..our.width).rev() {
+            our_vars.insert((our.id, bit), var);
+            their_vars.insert((their.id, bit), var);
+            var += 1;
+        }
+    }
+
+    let mut differ = FALSE;
+    let mut our_blaster = Blaster::new(ours, our_vars);
+    let mut their_blaster = Blaster::new(theirs, their_vars);
+    for (our, their) in outputs {
+        let our_net = ours.netlist.pins[&our.id][0];
+        let their_net = theirs.netlist.pins[&their.id][0];
+        let width = ours.netlist.width(our_net);
+        if width != theirs.netlist.width(their_net) {
+            return Err(format!(
+                "{} shows {} bits but {} in the reference shows {}",
+                our.name,
+                width,
+                their.name,
+                theirs.netlist.width(their_net)
+            ));
+        }
+        for bit in 0..width {
+            let a = our_blaster.bit(&mut bdd, our_net, bit)?;
+            let b = their_blaster.bit(&mut bdd, their_net, bit)?;
+            let xor = bdd.xor(a, b);
+            differ = bdd.or(differ, xor);
+        }
+    }
+
+    let assignment = match bdd.satisfy(differ) {
+        Some(assignment) => assignment,
+        None => return Ok(None),
+    };
+    let mut var = 0;
+    let values = inputs
+        .iter()
+        .map(|(port, _)| {
+            let mut value = 0;
+            for _ in 0..port.width {
+                value = value << 1 | assignment.get(&var).copied().unwrap_or(false) as u64;
+                var += 1;
+            }
+            value
+        })
+        .collect();
+    Ok(Some(values))
+}
+
+/// Breaks the circuit of one side down into a decision diagram for each bit of each net
+struct Blaster<'a> {
+    side: &'a Side<'a>,
+    /// The variable standing for each bit of each input component
+    vars: HashMap<(usize, u8), u32>,
+    bits: HashMap<(usize, u8), usize>,
+    /// The gates that components which aren't gates already are made of, along with the
+    /// diagrams worked out for their nets so far
+    expansions: HashMap<usize, (GateNetlist, HashMap<usize, usize>)>,
+    /// The net bits being worked out, to catch feedback
+    visiting: Vec<(usize, u8)>,
+}
+
+impl<'a> Blaster<'a> {
+    fn new(side: &'a Side<'a>, vars: HashMap<(usize, u8), u32>) -> Self {
+        Blaster {
+            side,
+            vars,
+            bits: HashMap::new(),
+            expansions: HashMap::new(),
+            visiting: Vec::new(),
+        }
+    }
+
+    /// The diagram for bit `bit` of `net`
+    fn bit(&mut self, bdd: &mut Bdd, net: usize, bit: u8) -> Result<usize, String> {
+        if let Some(&node) = self.bits.get(&(net, bit)) {
+            return Ok(node);
+        }
+        if self.visiting.contains(&(net, bit)) {
+            return Err("The circuits have feedback, so they can't be compared".to_owned());
+        }
+        let netlist = &self.side.netlist;
+        let (id, pin) = match netlist.drivers(net) {
+            [] => return Err("Some of the outputs depend on a net nothing drives".to_owned()),
+            &[driver] => driver,
+            _ => return Err("Nets with more than one driver can't be compared".to_owned()),
+        };
+        self.visiting.push((net, bit));
+        let node = self.component(bdd, id, pin, bit);
+        self.visiting.pop();
+        let node = node?;
+        if bdd.nodes.len() > MAX_BDD_NODES {
+            return Err("The circuits are too large to compare".to_owned());
+        }
+        self.bits.insert((net, bit), node);
+        Ok(node)
+    }
+
+    /// The net connected to each pin of a component
+    fn pin_net(&self, id: usize, pin: usize) -> usize {
+        self.side.netlist.pins[&id][pin]
+    }
+
+    /// The diagram for bit `bit` of output pin `pin` of component `id`
+    fn component(
+        &mut self,
+        bdd: &mut Bdd,
+        id: usize,
+        pin: usize,
+        bit: u8,
+    ) -> Result<usize, String> {
+        let instance = &self.side.components[&id].instance;
+        let kind = instance.ty.kind;
+        let gate = |s: &mut Self, bdd: &mut Bdd, kind| {
+            let inputs = (0..pin)
+                .map(|i| {
+                    let net = s.pin_net(id, i);
+                    s.bit(bdd, net, 0)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(bdd.gate(kind, &inputs))
+        };
+        match kind {
+            ComponentKind::Input { .. } => match self.vars.get(&(id, bit)) {
+                Some(&var) => Ok(bdd.var(var)),
+                None => Err("An input drives a net wider than itself".to_owned()),
+            },
+            ComponentKind::Constant { value, .. } => {
+                Ok(if value >> bit & 1 == 1 { TRUE } else { FALSE })
+            },
+            ComponentKind::Not
+            | ComponentKind::And
+            | ComponentKind::Or
+            | ComponentKind::Nand
+            | ComponentKind::Nor
+            | ComponentKind::Xor => gate(self, bdd, kind),
+            ComponentKind::Split { .. } => {
+                let net = self.pin_net(id, 0);
+                self.bit(bdd, net, pin as u8 - 1)
+            },
+            ComponentKind::Join { .. } => {
+                let net = self.pin_net(id, bit as usize);
+                self.bit(bdd, net, 0)
+            },
+            _ => {
+                if let Entry::Vacant(entry) = self.expansions.entry(id) {
+                    let netlist = kind.expand().ok_or_else(|| {
+                        format!("{} can't be broken down to compare", instance.name(id))
+                    })?;
+                    entry.insert((netlist, HashMap::new()));
+                }
+                let inner = self.expansions[&id].0.pins[pin][bit as usize];
+                self.inner(bdd, id, inner)
+            },
+        }
+    }
+
+    /// The diagram for net `net` inside the gates that component `id` is made of
+    fn inner(&mut self, bdd: &mut Bdd, id: usize, net: usize) -> Result<usize, String> {
+        let (netlist, done) = &self.expansions[&id];
+        if let Some(&node) = done.get(&net) {
+            return Ok(node);
+        }
+        let node = match netlist.gates.iter().find(|gate| gate.output == net) {
+            Some(gate) => {
+                let (kind, inputs) = (gate.kind, gate.inputs.clone());
+                match kind {
+                    ComponentKind::Constant { value, .. } => {
+                        if value & 1 == 1 {
+                            TRUE
+                        } else {
+                            FALSE
+                        }
+                    },
+                    _ => {
+                        let inputs = inputs
+                            .iter()
+                            .map(|&net| self.inner(bdd, id, net))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        bdd.gate(kind, &inputs)
+                    },
+                }
+            },
+            None => {
+                // one of the component's own inputs
+                let ty = &self.side.components[&id].instance.ty;
+                let found = netlist.pins.iter().enumerate().find_map(|(pin, nets)| {
+                    let bit = nets.iter().position(|&n| n == net)?;
+                    (ty.pins[pin].ty == PinType::Input).then_some((pin, bit as u8))
+                });
+                let (pin, bit) = found.ok_or("A component breaks down into loose gates")?;
+                let outer = self.pin_net(id, pin);
+                self.bit(bdd, outer, bit)?
+            },
+        };
+        self.expansions.get_mut(&id).unwrap().1.insert(net, node);
+        Ok(node)
+    }
+}
+
+const FALSE: usize = 0;
+const TRUE: usize = 1;
+
+/// A reduced ordered binary decision diagram, with nodes shared between every function built in
+/// it. Functions are referred to by the index of their root node.
+struct Bdd {
+    /// The variable each node tests and the nodes for when it is low and high. The two terminal
+    /// nodes come first and test no variable.
+    nodes: Vec<(u32, usize, usize)>,
+    unique: HashMap<(u32, usize, usize), usize>,
+    computed: HashMap<(usize, usize, usize), usize>,
+}
+
+impl Bdd {
+    fn new() -> Self {
+        Bdd {
+            nodes: vec![(u32::MAX, FALSE, FALSE), (u32::MAX, TRUE, TRUE)],
+            unique: HashMap::new(),
+            computed: HashMap::new(),
+        }
+    }
+
+    fn node(&mut self, var: u32, low: usize, high: usize) -> usize {
+        if low == high {
+            return low;
+        }
+        if let Some(&node) = self.unique.get(&(var, low, high)) {
+            return node;
+        }
+        self.nodes.push((var, low, high));
+        self.unique.insert((var, low, high), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn var(&mut self, var: u32) -> usize {
+        self.node(var, FALSE, TRUE)
+    }
+
+    /// If `f` then `g` else `h`
+    fn ite(&mut self, f: usize, g: usize, h: usize) -> usize {
+        match (f, g, h) {
+            (TRUE, _, _) => return g,
+            (FALSE, _, _) => return h,
+            (_, TRUE, FALSE) => return f,
+            _ if g == h => return g,
+            _ => {},
+        }
+        if let Some(&node) = self.computed.get(&(f, g, h)) {
+            return node;
+        }
+        let var = [f, g, h].iter().map(|&n| self.nodes[n].0).min().unwrap();
+        let cofactor = |bdd: &Bdd, n: usize, high: bool| {
+            let (v, low_node, high_node) = bdd.nodes[n];
+            match (v == var, high) {
+                (true, false) => low_node,
+                (true, true) => high_node,
+                (false, _) => n,
+            }
+        };
+        let (f0, g0, h0) = (
+            cofactor(self, f, false),
+            cofactor(self, g, false),
+            cofactor(self, h, false),
+        );
+        let (f1, g1, h1) = (
+            cofactor(self, f, true),
+            cofactor(self, g, true),
+            cofactor(self, h, true),
+        );
+        let low = self.ite(f0, g0, h0);
+        let high = self.ite(f1, g1, h1);
+        let node = self.node(var, low, high);
+        self.computed.insert((f, g, h), node);
+        node
+    }
+
+    fn not(&mut self, f: usize) -> usize {
+        self.ite(f, FALSE, TRUE)
+    }
+
+    fn and(&mut self, f: usize, g: usize) -> usize {
+        self.ite(f, g, FALSE)
+    }
+
+    fn or(&mut self, f: usize, g: usize) -> usize {
+        self.ite(f, TRUE, g)
+    }
+
+    fn xor(&mut self, f: usize, g: usize) -> usize {
+        let not_g = self.not(g);
+        self.ite(f, not_g, g)
+    }
+
+    /// The function of a one-bit gate of the given kind
+    fn gate(&mut self, kind: ComponentKind, inputs: &[usize]) -> usize {
+        match kind {
+            ComponentKind::Not => self.not(inputs[0]),
+            ComponentKind::And => self.and(inputs[0], inputs[1]),
+            ComponentKind::Or => self.or(inputs[0], inputs[1]),
+            ComponentKind::Nand => {
+                let and = self.and(inputs[0], inputs[1]);
+                self.not(and)
+            },
+            ComponentKind::Nor => {
+                let or = self.or(inputs[0], inputs[1]);
+                self.not(or)
+            },
+            ComponentKind::Xor => self.xor(inputs[0], inputs[1]),
+            _ => unreachable!("{:?} isn't a gate", kind),
+        }
+    }
+
+    /// Values for the variables that make `f` true, if any do. Variables left out can be
+    /// anything.
+    fn satisfy(&self, mut f: usize) -> Option<HashMap<u32, bool>> {
+        if f == FALSE {
+            return None;
+        }
+        let mut assignment = HashMap::new();
+        while f != TRUE {
+            let (var, low, high) = self.nodes[f];
+            // every node other than false leads to true somewhere
+            if low != FALSE {
+                assignment.insert(var, false);
+                f = low;
+            } else {
+                assignment.insert(var, true);
+                f = high;
+            }
+        }
+        Some(assignment)
+    }
+}
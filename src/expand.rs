@@ -0,0 +1,477 @@
+use std::collections::BTreeMap;
+
+use crate::kind::{shift_bits, ComponentKind};
+
+/// A single-bit gate in an expanded component.
+#[derive(Clone, Debug)]
+pub struct Gate {
+    pub kind: ComponentKind,
+    pub inputs: Vec<usize>,
+    pub output: usize,
+}
+
+/// The gate-level implementation of a component. Every net carries one bit.
+#[derive(Clone, Debug)]
+pub struct GateNetlist {
+    pub nets: usize,
+    pub gates: Vec<Gate>,
+    /// The nets making up each pin of the component, in the order of `ComponentKind::pins`, least
+    /// significant bit first
+    pub pins: Vec<Vec<usize>>,
+}
+
+impl ComponentKind {
+    /// Builds this kind out of one-bit gates, or returns `None` if it is already a gate or can't be
+    /// broken down any further.
+    pub fn expand(&self) -> Option<GateNetlist> {
+        let mut b = Builder::default();
+        let pins = match *self {
+            ComponentKind::Mux { select, width } => {
+                let data: Vec<_> = (0..1 << select).map(|_| b.bus(width)).collect();
+                let s = b.bus(select);
+                let y = (0..width as usize)
+                    .map(|i| {
+                        let bits: Vec<_> = data.iter().map(|d| d[i]).collect();
+                        b.mux(&s, &bits)
+                    })
+                    .collect();
+                let mut pins = data;
+                pins.push(s);
+                pins.push(y);
+                pins
+            },
+            ComponentKind::Demux { select, width } => {
+                let d = b.bus(width);
+                let s = b.bus(select);
+                let mut pins = vec![d.clone(), s.clone()];
+                for line in b.decode(&s) {
+                    pins.push(d.iter().map(|&bit| b.and(bit, line)).collect());
+                }
+                pins
+            },
+            ComponentKind::Decoder { select } => {
+                let a = b.bus(select);
+                let mut pins = vec![a.clone()];
+                pins.extend(b.decode(&a).into_iter().map(|line| vec![line]));
+                pins
+            },
+            ComponentKind::PriorityEncoder { select } => {
+                let d = b.bus(1 << select);
+                // highest[i] is set when d[i] is the highest set input
+                let mut highest = vec![0; d.len()];
+                let mut any_above = None;
+                for i in (0..d.len()).rev() {
+                    highest[i] = match any_above {
+                        Some(above) => {
+                            let none_above = b.not(above);
+                            b.and(d[i], none_above)
+                        },
+                        None => d[i],
+                    };
+                    any_above = Some(match any_above {
+                        Some(above) => b.or(above, d[i]),
+                        None => d[i],
+                    });
+                }
+                let y = (0..select)
+                    .map(|bit| {
+                        let lines: Vec<_> = (0..d.len())
+                            .filter(|i| i >> bit & 1 == 1)
+                            .map(|i| highest[i])
+                            .collect();
+                        b.or_all(&lines)
+                    })
+                    .collect();
+                let v = any_above.unwrap();
+                let mut pins: Vec<_> = d.iter().map(|&bit| vec![bit]).collect();
+                pins.push(y);
+                pins.push(vec![v]);
+                pins
+            },
+            ComponentKind::FullAdder => {
+                let (a, x, cin) = (b.net(), b.net(), b.net());
+                let (s, cout) = b.full_adder(a, x, cin);
+                vec![vec![a], vec![x], vec![cin], vec![s], vec![cout]]
+            },
+            ComponentKind::Adder { width } => {
+                let (a, x, cin) = (b.bus(width), b.bus(width), b.net());
+                let (s, cout) = b.add(&a, &x, Some(cin));
+                vec![a, x, vec![cin], s, vec![cout]]
+            },
+            ComponentKind::Subtractor { width } => {
+                let (a, x, bin) = (b.bus(width), b.bus(width), b.net());
+                let mut borrow = bin;
+                let mut d = Vec::new();
+                for (&ai, &xi) in a.iter().zip(&x) {
+                    // d = a ^ x ^ borrow, borrow out = (!a & x) | (!(a ^ x) & borrow)
+                    let diff = b.xor(ai, xi);
+                    d.push(b.xor(diff, borrow));
+                    let not_a = b.not(ai);
+                    let under = b.and(not_a, xi);
+                    let same = b.not(diff);
+                    let passed = b.and(same, borrow);
+                    borrow = b.or(under, passed);
+                }
+                vec![a, x, vec![bin], d, vec![borrow]]
+            },
+            ComponentKind::Comparator { width } => {
+                let (a, x) = (b.bus(width), b.bus(width));
+                let (mut lt, mut eq, mut gt) = (None, None, None);
+                for (&ai, &xi) in a.iter().zip(&x) {
+                    let not_a = b.not(ai);
+                    let not_x = b.not(xi);
+                    let bit_lt = b.and(not_a, xi);
+                    let bit_gt = b.and(ai, not_x);
+                    let diff = b.xor(ai, xi);
+                    let bit_eq = b.not(diff);
+                    // a more significant bit decides unless it is equal
+                    let (new_lt, new_gt, new_eq) = match (lt, gt, eq) {
+                        (Some(lt), Some(gt), Some(eq)) => {
+                            let lt_below = b.and(bit_eq, lt);
+                            let gt_below = b.and(bit_eq, gt);
+                            (
+                                b.or(bit_lt, lt_below),
+                                b.or(bit_gt, gt_below),
+                                b.and(bit_eq, eq),
+                            )
+                        },
+                        _ => (bit_lt, bit_gt, bit_eq),
+                    };
+                    lt = Some(new_lt);
+                    gt = Some(new_gt);
+                    eq = Some(new_eq);
+                }
+                vec![
+                    a,
+                    x,
+                    vec![lt.unwrap()],
+                    vec![eq.unwrap()],
+                    vec![gt.unwrap()],
+                ]
+            },
+            ComponentKind::Multiplier { width } => {
+                let (a, x) = (b.bus(width), b.bus(width));
+                let rows: Vec<Vec<_>> = x
+                    .iter()
+                    .map(|&xi| a.iter().map(|&ai| b.and(ai, xi)).collect())
+                    .collect();
+                let mut product = rows[0].clone();
+                for (i, row) in rows.iter().enumerate().skip(1) {
+                    let (sum, carry) = b.add(&product[i..], row, None);
+                    product.truncate(i);
+                    product.extend(sum);
+                    product.push(carry);
+                }
+                while product.len() < width as usize * 2 {
+                    let zero = b.zero();
+                    product.push(zero);
+                }
+                vec![a, x, product]
+            },
+            ComponentKind::Shifter { width } => {
+                let (a, s, op) = (b.bus(width), b.bus(shift_bits(width)), b.bus(2));
+                let w = width as usize;
+                let mut y = a.clone();
+                for (stage, &enable) in s.iter().enumerate() {
+                    let dist = 1 << stage;
+                    let zero = b.zero();
+                    let sign = y[w - 1];
+                    y = (0..w)
+                        .map(|i| {
+                            let shl = if i >= dist { y[i - dist] } else { zero };
+                            let shr = if i + dist < w { y[i + dist] } else { zero };
+                            let sar = if i + dist < w { y[i + dist] } else { sign };
+                            let rol = y[(i + w - dist % w) % w];
+                            let shifted = b.mux(&op, &[shl, shr, sar, rol]);
+                            b.mux(&[enable], &[y[i], shifted])
+                        })
+                        .collect();
+                }
+                vec![a, s, op, y]
+            },
+            ComponentKind::Alu { width } => {
+                let (a, x, op) = (b.bus(width), b.bus(width), b.bus(3));
+                let (sum, carry) = b.add(&a, &x, None);
+                let not_x: Vec<_> = x.iter().map(|&xi| b.not(xi)).collect();
+                let one = b.one();
+                let (diff, no_borrow) = b.add(&a, &not_x, Some(one));
+                let borrow = b.not(no_borrow);
+                let zero = b.zero();
+                let y: Vec<_> = (0..width as usize)
+                    .map(|i| {
+                        let and = b.and(a[i], x[i]);
+                        let or = b.or(a[i], x[i]);
+                        let xor = b.xor(a[i], x[i]);
+                        let not = b.not(a[i]);
+                        let slt = if i == 0 { borrow } else { zero };
+                        b.mux(&op, &[sum[i], diff[i], and, or, xor, not, slt, x[i]])
+                    })
+                    .collect();
+                let c = b.mux(&op, &[carry, borrow, zero, zero, zero, zero, zero, zero]);
+                let any = b.or_all(&y);
+                let z = b.not(any);
+                vec![a, x, op, y, vec![c], vec![z]]
+            },
+            _ => return None,
+        };
+        Some(b.finish(pins))
+    }
+}
+
+/// Which gates a gate-level circuit is built from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Style {
+    #[default]
+    Mixed,
+    NandOnly,
+    NorOnly,
+}
+
+/// Builds up a gate-level circuit one gate at a time
+#[derive(Default)]
+pub struct Builder {
+    nets: usize,
+    gates: Vec<Gate>,
+    zero: Option<usize>,
+    one: Option<usize>,
+    style: Style,
+    /// Each net that has been inverted and its inverse, both ways round
+    negations: BTreeMap<usize, usize>,
+}
+
+impl Builder {
+    pub fn new(style: Style) -> Self {
+        Builder {
+            style,
+            ..Builder::default()
+        }
+    }
+
+    /// The circuit built so far, with the nets that make up each of its pins
+    pub fn finish(self, pins: Vec<Vec<usize>>) -> GateNetlist {
+        GateNetlist {
+            nets: self.nets,
+            gates: self.gates,
+            pins,
+        }
+    }
+
+    pub fn net(&mut self) -> usize {
+        self.nets += 1;
+        self.nets - 1
+    }
+
+    fn bus(&mut self, width: u8) -> Vec<usize> {
+        (0..width).map(|_| self.net()).collect()
+    }
+
+    fn gate(&mut self, kind: ComponentKind, inputs: &[usize]) -> usize {
+        let output = self.net();
+        self.gates.push(Gate {
+            kind,
+            inputs: inputs.to_vec(),
+            output,
+        });
+        output
+    }
+
+    pub fn zero(&mut self) -> usize {
+        match self.zero {
+            Some(net) => net,
+            None => {
+                let net = self.gate(ComponentKind::Constant { width: 1, value: 0 }, &[]);
+                self.zero = Some(net);
+                net
+            },
+        }
+    }
+
+    pub fn one(&mut self) -> usize {
+        match self.one {
+            Some(net) => net,
+            None => {
+                let net = self.gate(ComponentKind::Constant { width: 1, value: 1 }, &[]);
+                self.one = Some(net);
+                net
+            },
+        }
+    }
+
+    pub fn nand(&mut self, a: usize, b: usize) -> usize {
+        self.gate(ComponentKind::Nand, &[a, b])
+    }
+
+    pub fn nor(&mut self, a: usize, b: usize) -> usize {
+        self.gate(ComponentKind::Nor, &[a, b])
+    }
+
+    /// A one-bit register, for circuits that keep state
+    pub fn register(&mut self, d: usize, clk: usize, en: usize, rst: usize) -> usize {
+        self.gate(ComponentKind::Register { width: 1 }, &[d, clk, en, rst])
+    }
+
+    /// Inverts a net, reusing the inverse if it has been made before, so that inverting twice
+    /// gives back the net that was started with
+    pub fn not(&mut self, a: usize) -> usize {
+        if let Some(&inverse) = self.negations.get(&a) {
+            return inverse;
+        }
+        let inverse = match self.style {
+            Style::Mixed => self.gate(ComponentKind::Not, &[a]),
+            Style::NandOnly => self.nand(a, a),
+            Style::NorOnly => self.nor(a, a),
+        };
+        self.negations.insert(a, inverse);
+        self.negations.insert(inverse, a);
+        inverse
+    }
+
+    pub fn and(&mut self, a: usize, b: usize) -> usize {
+        match self.style {
+            Style::Mixed => self.gate(ComponentKind::And, &[a, b]),
+            Style::NandOnly => {
+                let nand = self.nand(a, b);
+                self.not(nand)
+            },
+            Style::NorOnly => {
+                let (a, b) = (self.not(a), self.not(b));
+                self.nor(a, b)
+            },
+        }
+    }
+
+    pub fn or(&mut self, a: usize, b: usize) -> usize {
+        match self.style {
+            Style::Mixed => self.gate(ComponentKind::Or, &[a, b]),
+            Style::NandOnly => {
+                let (a, b) = (self.not(a), self.not(b));
+                self.nand(a, b)
+            },
+            Style::NorOnly => {
+                let nor = self.nor(a, b);
+                self.not(nor)
+            },
+        }
+    }
+
+    pub fn xor(&mut self, a: usize, b: usize) -> usize {
+        match self.style {
+            Style::Mixed => self.gate(ComponentKind::Xor, &[a, b]),
+            Style::NandOnly => {
+                let both = self.nand(a, b);
+                let (x, y) = (self.nand(a, both), self.nand(b, both));
+                self.nand(x, y)
+            },
+            Style::NorOnly => {
+                let neither = self.nor(a, b);
+                let (x, y) = (self.nor(a, neither), self.nor(b, neither));
+                let same = self.nor(x, y);
+                self.not(same)
+            },
+        }
+    }
+
+    pub fn or_all(&mut self, nets: &[usize]) -> usize {
+        match nets {
+            [] => self.zero(),
+            [net] => *net,
+            _ => {
+                let (low, high) = nets.split_at(nets.len() / 2);
+                let low = self.or_all(low);
+                let high = self.or_all(high);
+                self.or(low, high)
+            },
+        }
+    }
+
+    pub fn and_all(&mut self, nets: &[usize]) -> usize {
+        match nets {
+            [] => self.one(),
+            [net] => *net,
+            _ => {
+                let (low, high) = nets.split_at(nets.len() / 2);
+                let low = self.and_all(low);
+                let high = self.and_all(high);
+                self.and(low, high)
+            },
+        }
+    }
+
+    fn full_adder(&mut self, a: usize, b: usize, c: usize) -> (usize, usize) {
+        let half = self.xor(a, b);
+        let sum = self.xor(half, c);
+        let both = self.and(a, b);
+        let carried = self.and(half, c);
+        (sum, self.or(both, carried))
+    }
+
+    /// Ripple-carry addition of two operands, which may differ in width. Missing bits of the
+    /// narrower operand count as zero, and so does a missing carry in.
+    fn add(&mut self, a: &[usize], b: &[usize], carry: Option<usize>) -> (Vec<usize>, usize) {
+        let mut carry = carry;
+        let mut sum = Vec::new();
+        for i in 0..a.len().max(b.len()) {
+            let terms: Vec<_> = [a.get(i).copied(), b.get(i).copied(), carry]
+                .iter()
+                .flatten()
+                .copied()
+                .collect();
+            let (s, c) = match terms[..] {
+                [x, y, z] => {
+                    let (s, c) = self.full_adder(x, y, z);
+                    (s, Some(c))
+                },
+                [x, y] => (self.xor(x, y), Some(self.and(x, y))),
+                [x] => (x, None),
+                _ => unreachable!(),
+            };
+            sum.push(s);
+            carry = c;
+        }
+        let carry = match carry {
+            Some(c) => c,
+            None => self.zero(),
+        };
+        (sum, carry)
+    }
+
+    /// Picks one of `inputs` by the value of `select`, least significant bit first. Any inputs
+    /// past the end of the list read as zero.
+    pub fn mux(&mut self, select: &[usize], inputs: &[usize]) -> usize {
+        match select.split_last() {
+            None => inputs.first().copied().unwrap_or_else(|| self.zero()),
+            Some((&high, low)) => {
+                let half = 1 << low.len();
+                let lower = self.mux(low, &inputs[..half.min(inputs.len())]);
+                let upper = if inputs.len() > half {
+                    self.mux(low, &inputs[half..])
+                } else {
+                    self.zero()
+                };
+                let not_high = self.not(high);
+                let pick_lower = self.and(not_high, lower);
+                let pick_upper = self.and(high, upper);
+                self.or(pick_lower, pick_upper)
+            },
+        }
+    }
+
+    /// One line per value of `select`, set only when `select` has that value
+    fn decode(&mut self, select: &[usize]) -> Vec<usize> {
+        let inverted: Vec<_> = select.iter().map(|&s| self.not(s)).collect();
+        (0..1 << select.len())
+            .map(|i| {
+                let literals: Vec<_> = (0..select.len())
+                    .map(|bit| {
+                        if i >> bit & 1 == 1 {
+                            select[bit]
+                        } else {
+                            inverted[bit]
+                        }
+                    })
+                    .collect();
+                self.and_all(&literals)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use druid::im;
+
+use crate::{
+    component::ComponentState,
+    kind::{ComponentKind, PinType, ShiftMode},
+    sim::Netlist,
+    wire::WireState,
+};
+
+/// A circuit broken down into nets and the components between them, for writing out in a
+/// hardware description language
+pub struct Design {
+    pub ports: Vec<Port>,
+    /// The width of each net that anything connects to
+    pub nets: BTreeMap<usize, u8>,
+    pub cells: Vec<Cell>,
+}
+
+/// A port of the module a circuit becomes, standing for a pin of one of the components that the
+/// circuit talks to the outside world through
+pub struct Port {
+    pub name: String,
+    /// Which way signals go, as seen from outside the module
+    pub ty: PinType,
+    pub width: u8,
+    pub net: usize,
+}
+
+/// A component inside the module
+pub struct Cell {
+    /// What the component goes by in the editor, which is also a valid identifier
+    pub name: String,
+    pub kind: ComponentKind,
+    /// Each pin with the net it connects to
+    pub pins: Vec<Connection>,
+    /// The initial contents of a memory
+    pub contents: Vec<u64>,
+}
+
+pub struct Connection {
+    pub name: String,
+    pub ty: PinType,
+    pub width: u8,
+    pub net: usize,
+}
+
+/// Whether components of this kind are how a circuit gets its inputs or shows its outputs, in
+/// which case their pins become ports of the module
+fn is_port(kind: ComponentKind) -> bool {
+    matches!(
+        kind,
+        ComponentKind::Input { .. }
+            | ComponentKind::Clock
+            | ComponentKind::Keyboard
+            | ComponentKind::Probe { .. }
+            | ComponentKind::SevenSegment
+            | ComponentKind::HexDisplay
+            | ComponentKind::DotMatrix { .. }
+            | ComponentKind::Tty { .. }
+    )
+}
+
+/// Works out the nets of the circuit and what connects to them. Inputs, clocks and keyboards
+/// become input ports, and probes and displays output ports, named after their components and
+/// also after the pin where there is more than one.
+pub fn design(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Design {
+    let netlist = Netlist::build(components, wires);
+    let mut design = Design {
+        ports: Vec::new(),
+        nets: BTreeMap::new(),
+        cells: Vec::new(),
+    };
+    for (&id, component) in components.iter() {
+        let instance = &component.instance;
+        let kind = instance.ty.kind;
+        let pins: Vec<Connection> = kind
+            .pins()
+            .into_iter()
+            .zip(&netlist.pins[&id])
+            .map(|(spec, &net)| Connection {
+                name: spec.name,
+                ty: spec.ty,
+                width: netlist.width(net),
+                net,
+            })
+            .collect();
+        for pin in pins.iter() {
+            design.nets.insert(pin.net, pin.width);
+        }
+
+        if is_port(kind) {
+            let single = pins.len() == 1;
+            for pin in pins {
+                let mut name = if single {
+                    instance.name(id)
+                } else {
+                    format!("{}_{}", instance.name(id), pin.name)
+                };
+                // seven segment displays, whose names start with a digit
+                if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    name.insert_str(0, "DISP_");
+                }
+                let ty = match pin.ty {
+                    PinType::Input => PinType::Output,
+                    PinType::Output => PinType::Input,
+                };
+                design.ports.push(Port {
+                    name,
+                    ty,
+                    width: pin.width,
+                    net: pin.net,
+                });
+            }
+        } else {
+            design.cells.push(Cell {
+                name: instance.name(id),
+                kind,
+                pins,
+                contents: instance.contents.iter().copied().collect(),
+            });
+        }
+    }
+    design
+}
+
+/// Whether a component is written out in place, as a gate or an assignment, rather than as an
+/// instance of a module of its own
+pub fn is_inline(kind: ComponentKind) -> bool {
+    matches!(
+        kind,
+        ComponentKind::Not
+            | ComponentKind::And
+            | ComponentKind::Or
+            | ComponentKind::Nand
+            | ComponentKind::Nor
+            | ComponentKind::Xor
+            | ComponentKind::Constant { .. }
+            | ComponentKind::Split { .. }
+            | ComponentKind::Join { .. }
+    )
+}
+
+/// The module or entity a component is an instance of. Memories get a module each for their
+/// contents; everything else shares one with the components of the same kind and size.
+pub fn module_name(cell: &Cell) -> String {
+    let name = match cell.kind {
+        ComponentKind::Mux { select, width } => format!("mux_s{}_w{}", select, width),
+        ComponentKind::Demux { select, width } => format!("demux_s{}_w{}", select, width),
+        ComponentKind::Decoder { select } => format!("decoder_s{}", select),
+        ComponentKind::PriorityEncoder { select } => format!("priority_encoder_s{}", select),
+        ComponentKind::FullAdder => "full_adder".to_owned(),
+        ComponentKind::Adder { width } => format!("adder_w{}", width),
+        ComponentKind::Subtractor { width } => format!("subtractor_w{}", width),
+        ComponentKind::Comparator { width } => format!("comparator_w{}", width),
+        ComponentKind::Multiplier { width } => format!("multiplier_w{}", width),
+        ComponentKind::Shifter { width } => format!("shifter_w{}", width),
+        ComponentKind::Alu { width } => format!("alu_w{}", width),
+        ComponentKind::Register { width } => format!("register_w{}", width),
+        ComponentKind::Counter { width } => format!("counter_w{}", width),
+        ComponentKind::ShiftRegister { width, mode } => {
+            let mode = match mode {
+                ShiftMode::Sipo => "sipo",
+                ShiftMode::Piso => "piso",
+                ShiftMode::Universal => "universal",
+            };
+            format!("shift_register_{}_w{}", mode, width)
+        },
+        ComponentKind::TriState { width } => format!("tristate_w{}", width),
+        ComponentKind::TransmissionGate { width } => format!("tgate_w{}", width),
+        _ => cell.name.to_lowercase(),
+    };
+    format!("logicism_{}", name)
+}
+
+/// An identifier made from a file name, for naming the module after the file it is written to
+pub fn identifier(name: &str) -> String {
+    let mut id: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !id.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        id.insert_str(0, "circuit_");
+    }
+    // VHDL doesn't allow two underscores in a row or one at the end
+    while id.contains("__") {
+        id = id.replace("__", "_");
+    }
+    id.trim_end_matches('_').to_owned()
+}
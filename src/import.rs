@@ -0,0 +1,652 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap},
+    fmt::Write,
+    rc::Rc,
+};
+
+use druid::im;
+
+use crate::{
+    canvas::Coords,
+    component::{ComponentInstance, ComponentState, ComponentType, Orientation},
+    expand::{Builder, GateNetlist, Style},
+    kind::ComponentKind,
+    place::{self, Ports},
+    sim::{Joins, Netlist},
+    wire::{WireSegment, WireState},
+};
+
+/// A circuit read from another program's file and put together on the grid, with notes on how
+/// well it came across
+pub struct Import {
+    pub components: Vec<ComponentInstance>,
+    pub wires: Vec<im::Vector<WireSegment>>,
+    /// What the original called some of the components, by their index in `components`
+    labels: Vec<(usize, String)>,
+    /// The kinds of thing that were left out, with how many of each
+    left_out: BTreeMap<String, usize>,
+    notes: Vec<String>,
+    /// Pins that aren't connected as they were in the original, by component index and pin name
+    miswired: Vec<(usize, String)>,
+}
+
+impl Import {
+    /// What came across and what didn't, given the names the components ended up with
+    pub fn report(&self, names: &[String]) -> String {
+        let mut out = String::new();
+        for note in self.notes.iter() {
+            writeln!(out, "{}", note).unwrap();
+        }
+        if !self.labels.is_empty() {
+            writeln!(out, "\nNames in the original:").unwrap();
+            for (index, label) in self.labels.iter() {
+                writeln!(out, "{}  {}", names[*index], label).unwrap();
+            }
+        }
+        if !self.left_out.is_empty() {
+            writeln!(out, "\nLeft out, with nothing like them here:").unwrap();
+            for (what, &count) in self.left_out.iter() {
+                match count {
+                    1 => writeln!(out, "{}", what),
+                    _ => writeln!(out, "{} × {}", what, count),
+                }
+                .unwrap();
+            }
+        }
+        if !self.miswired.is_empty() {
+            let pins: Vec<_> = self
+                .miswired
+                .iter()
+                .map(|(index, pin)| format!("{}.{}", names[*index], pin))
+                .collect();
+            writeln!(
+                out,
+                "\nThese pins aren't connected as they were in the original, so check the wiring \
+                 around them:\n{}",
+                pins.join(", ")
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+/// Puts a circuit together from the components and wires of another program's file, given in
+/// grid coordinates. Pins that don't fall where the original's did are joined to those points by
+/// new wires, and the result is checked against the original's connections.
+#[derive(Default)]
+pub struct Assembly {
+    components: Vec<ComponentInstance>,
+    /// The point in the original that each pin of each component stands for, if any
+    ports: Vec<Vec<Option<Coords>>>,
+    /// The original's wires, grouped by what they connect to
+    wires: Vec<Vec<(Coords, Coords)>>,
+    /// Points to join with new wires: pins to where they were in the original, and points the
+    /// original connected some other way
+    links: Vec<(Coords, Coords)>,
+    labels: Vec<(usize, String)>,
+    left_out: BTreeMap<String, usize>,
+    notes: Vec<String>,
+}
+
+impl Assembly {
+    pub fn new() -> Self {
+        Assembly::default()
+    }
+
+    /// Adds a component with its pin `pin` at `point`, and returns its index
+    pub fn place(
+        &mut self,
+        kind: ComponentKind,
+        orientation: Orientation,
+        pin: usize,
+        point: Coords,
+    ) -> usize {
+        let ty = Rc::new(ComponentType::new(kind));
+        let pins = ty.pins.len();
+        let mut instance = ComponentInstance::new(Coords::new(0, 0), ty, orientation);
+        let offset = instance.pin_coords(pin);
+        instance.coords = Coords::new(point.x - offset.x, point.y - offset.y);
+        let mut ports = vec![None; pins];
+        ports[pin] = Some(point);
+        self.components.push(instance);
+        self.ports.push(ports);
+        self.components.len() - 1
+    }
+
+    /// Joins a pin of a component to the point in the original that it stands for
+    pub fn connect(&mut self, index: usize, pin: usize, point: Coords) {
+        let from = self.components[index].pin_coords(pin);
+        self.ports[index][pin] = Some(point);
+        if from != point {
+            self.links.push((from, point));
+        }
+    }
+
+    /// Joins two points that the original connected without a wire, such as through a
+    /// subcircuit's pin or a pair of tunnels
+    pub fn link(&mut self, a: Coords, b: Coords) {
+        if a != b {
+            self.links.push((a, b));
+        }
+    }
+
+    /// Adds the straight wires of one circuit, grouped into a wire for each set that touch
+    pub fn add_wires(&mut self, segments: &[(Coords, Coords)]) {
+        let mut joins = Joins::default();
+        for &(a, b) in segments {
+            joins.join(a, b);
+        }
+        for &(a, b) in segments {
+            for &point in [a, b].iter() {
+                for &(c, d) in segments {
+                    if WireSegment::runs_through(c, d, point) {
+                        joins.join(point, c);
+                    }
+                }
+            }
+        }
+        let mut groups: BTreeMap<Coords, Vec<(Coords, Coords)>> = BTreeMap::new();
+        for &(a, b) in segments {
+            groups.entry(joins.find(a)).or_default().push((a, b));
+        }
+        self.wires
+            .extend(groups.into_iter().map(|(_, group)| group));
+    }
+
+    /// Lays out a gate-level circuit with an input for each of its first `inputs` pins and a
+    /// probe for each of the rest, labelled with what the original called them
+    pub fn add_layout(&mut self, netlist: &GateNetlist, inputs: usize, names: &[String]) {
+        let layout = place::place(netlist, Ports::Standalone { inputs }, Coords::new(0, 0));
+        for &(coords, kind) in layout.components.iter() {
+            let ty = ComponentType::new(kind);
+            let index = self.place(kind, Orientation::North, 0, coords + ty.pins[0].pos);
+            for (pin, spec) in ty.pins.iter().enumerate().skip(1) {
+                self.connect(index, pin, coords + spec.pos);
+            }
+            if let Some(port) = layout
+                .ports
+                .iter()
+                .position(|&p| p == coords + ty.pins[0].pos)
+            {
+                self.label(index, &names[port]);
+            }
+        }
+        for segments in layout.wires.iter() {
+            self.add_wires(segments);
+        }
+    }
+
+    /// Notes what the original called a component
+    pub fn label(&mut self, index: usize, label: &str) {
+        if !label.is_empty() {
+            self.components[index].label = label.to_owned();
+            self.labels.push((index, label.to_owned()));
+        }
+    }
+
+    /// Notes something in the original that has no counterpart here
+    pub fn leave_out(&mut self, what: impl Into<String>) {
+        *self.left_out.entry(what.into()).or_default() += 1;
+    }
+
+    pub fn note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
+    }
+
+    /// Routes the new wires and checks every pin is connected as it was in the original
+    pub fn finish(self) -> Import {
+        let pins = self.components.iter().flat_map(|instance| {
+            (0..instance.ty.pins.len()).map(move |pin| instance.pin_coords(pin))
+        });
+        let routed = route_links(pins, self.wires.iter().flatten().copied(), &self.links);
+
+        let wires: Vec<im::Vector<WireSegment>> = self
+            .wires
+            .iter()
+            .chain(routed.iter())
+            .map(|segments| {
+                segments
+                    .iter()
+                    .filter_map(|&(a, b)| WireSegment::new(a, b))
+                    .collect()
+            })
+            .collect();
+        let miswired = self.check(&wires);
+        Import {
+            components: self.components,
+            wires,
+            labels: self.labels,
+            left_out: self.left_out,
+            notes: self.notes,
+            miswired,
+        }
+    }
+
+    /// The pins whose nets, once everything is wired up, don't match the original's
+    fn check(&self, wires: &[im::Vector<WireSegment>]) -> Vec<(usize, String)> {
+        let components: im::OrdMap<usize, ComponentState> = self
+            .components
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                (
+                    i,
+                    ComponentState::new(c.coords, c.ty.clone(), c.orientation),
+                )
+            })
+            .collect();
+        let wires: im::OrdMap<usize, WireState> = wires
+            .iter()
+            .enumerate()
+            .map(|(i, segments)| {
+                let wire = WireState {
+                    segments: segments.clone(),
+                    value: None,
+                };
+                (i, wire)
+            })
+            .collect();
+        let netlist = Netlist::build(&components, &wires);
+
+        // the original's nets, joined the same way but only by its own wires
+        let original: Vec<_> = self.wires.iter().flatten().copied().collect();
+        let mut joins = Joins::default();
+        for &(a, b) in original.iter() {
+            joins.join(a, b);
+        }
+        let mut points: Vec<Coords> = original.iter().flat_map(|&(a, b)| vec![a, b]).collect();
+        points.extend(self.ports.iter().flatten().flatten());
+        points.extend(self.links.iter().flat_map(|&(a, b)| vec![a, b]));
+        for &point in points.iter() {
+            for &(a, b) in original.iter() {
+                if WireSegment::runs_through(a, b, point) {
+                    joins.join(point, a);
+                }
+            }
+        }
+        for &(a, b) in self.links.iter() {
+            joins.join(a, b);
+        }
+
+        let mut pins = Vec::new();
+        let mut expected_by_net: BTreeMap<usize, BTreeSet<Option<Coords>>> = BTreeMap::new();
+        let mut nets_by_expected: BTreeMap<Coords, BTreeSet<usize>> = BTreeMap::new();
+        for (i, ports) in self.ports.iter().enumerate() {
+            for (pin, port) in ports.iter().enumerate() {
+                let net = netlist.pins[&i][pin];
+                let expected = port.map(|point| joins.find(point));
+                expected_by_net.entry(net).or_default().insert(expected);
+                if let Some(expected) = expected {
+                    nets_by_expected.entry(expected).or_default().insert(net);
+                }
+                pins.push((i, pin, net, expected));
+            }
+        }
+        pins.into_iter()
+            .filter(|(_, _, net, expected)| {
+                expected_by_net[net].len() > 1
+                    || expected.map_or(false, |e| nets_by_expected[&e].len() > 1)
+            })
+            .map(|(i, pin, _, _)| (i, self.components[i].ty.pins[pin].name.clone()))
+            .collect()
+    }
+}
+
+/// The pins and wires that a new wire mustn't touch by accident
+#[derive(Default)]
+struct Obstacles {
+    /// Points by column and then row, and by row and then column
+    columns: BTreeSet<(isize, isize)>,
+    rows: BTreeSet<(isize, isize)>,
+    /// The spans of horizontal segments in each row and vertical segments in each column
+    horizontal: BTreeMap<isize, Vec<(isize, isize)>>,
+    vertical: BTreeMap<isize, Vec<(isize, isize)>>,
+}
+
+impl Obstacles {
+    fn add_point(&mut self, point: Coords) {
+        self.columns.insert((point.x, point.y));
+        self.rows.insert((point.y, point.x));
+    }
+
+    fn add_segment(&mut self, (a, b): (Coords, Coords)) {
+        self.add_point(a);
+        self.add_point(b);
+        if a.y == b.y {
+            let span = (a.x.min(b.x), a.x.max(b.x));
+            self.horizontal.entry(a.y).or_default().push(span);
+        } else {
+            let span = (a.y.min(b.y), a.y.max(b.y));
+            self.vertical.entry(a.x).or_default().push(span);
+        }
+    }
+
+    /// Whether a new segment from `a` to `b` would run through anything other than `ends`
+    fn touches(&self, a: Coords, b: Coords, ends: (Coords, Coords)) -> bool {
+        let mut points: Box<dyn Iterator<Item = Coords>> = if a.x == b.x {
+            let range = (a.x, a.y.min(b.y))..=(a.x, a.y.max(b.y));
+            Box::new(self.columns.range(range).map(|&(x, y)| Coords::new(x, y)))
+        } else {
+            let range = (a.y, a.x.min(b.x))..=(a.y, a.x.max(b.x));
+            Box::new(self.rows.range(range).map(|&(y, x)| Coords::new(x, y)))
+        };
+        points.any(|point| point != ends.0 && point != ends.1)
+    }
+
+    /// Whether `point` is somewhere along a horizontal wire, or a vertical one
+    fn is_along(&self, point: Coords, horizontal: bool) -> bool {
+        let (spans, at) = if horizontal {
+            (self.horizontal.get(&point.y), point.x)
+        } else {
+            (self.vertical.get(&point.x), point.y)
+        };
+        spans.map_or(false, |spans| {
+            spans.iter().any(|&(low, high)| low <= at && at <= high)
+        })
+    }
+
+    /// Whether `point` is somewhere along a wire
+    fn is_on_wire(&self, point: Coords) -> bool {
+        self.is_along(point, true) || self.is_along(point, false)
+    }
+
+    /// The corners of a rectangle around everything, with `margin` cells to spare
+    fn bounds(&self, margin: isize) -> Option<(Coords, Coords)> {
+        let (&(x0, _), &(x1, _)) = (self.columns.iter().next()?, self.columns.iter().last()?);
+        let (&(y0, _), &(y1, _)) = (self.rows.iter().next()?, self.rows.iter().last()?);
+        Some((
+            Coords::new(x0 - margin, y0 - margin),
+            Coords::new(x1 + margin, y1 + margin),
+        ))
+    }
+}
+
+/// A path of straight segments from `from` to `to` that touches nothing else on the way, trying
+/// a turn at either end and then detours close by, and then searching further afield. If there's
+/// no way through at all, it's the first of the paths tried.
+fn route(from: Coords, to: Coords, obstacles: &Obstacles) -> Vec<Coords> {
+    let mut paths = vec![
+        vec![from, Coords::new(to.x, from.y), to],
+        vec![from, Coords::new(from.x, to.y), to],
+    ];
+    for d in 1..=4 {
+        for &x in [from.x - d, from.x + d, to.x - d, to.x + d].iter() {
+            paths.push(vec![from, Coords::new(x, from.y), Coords::new(x, to.y), to]);
+        }
+        for &y in [from.y - d, from.y + d, to.y - d, to.y + d].iter() {
+            paths.push(vec![from, Coords::new(from.x, y), Coords::new(to.x, y), to]);
+        }
+    }
+    let paths: Vec<_> = paths.into_iter().map(straighten).collect();
+    let is_clear = |path: &&Vec<Coords>| {
+        let ends = (path[0], path[path.len() - 1]);
+        path.windows(2)
+            .all(|s| !obstacles.touches(s[0], s[1], ends))
+            && path[1..path.len() - 1]
+                .iter()
+                .all(|&corner| !obstacles.is_on_wire(corner))
+    };
+    match paths.iter().find(is_clear) {
+        Some(path) => path.clone(),
+        None => search(from, to, obstacles).unwrap_or_else(|| paths[0].clone()),
+    }
+}
+
+/// The shortest path from `from` to `to` on the grid that touches nothing else on the way, with
+/// each turn counting for several cells so that it doesn't wander, staying within a few cells of
+/// everything else
+fn search(from: Coords, to: Coords, obstacles: &Obstacles) -> Option<Vec<Coords>> {
+    const TURN: usize = 8;
+    const STEPS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    let (low, high) = obstacles.bounds(4)?;
+    let (low, high) = (
+        Coords::new(
+            low.x.min(from.x.min(to.x) - 4),
+            low.y.min(from.y.min(to.y) - 4),
+        ),
+        Coords::new(
+            high.x.max(from.x.max(to.x) + 4),
+            high.y.max(from.y.max(to.y) + 4),
+        ),
+    );
+    let inside = |p: Coords| low.x <= p.x && p.x <= high.x && low.y <= p.y && p.y <= high.y;
+
+    // cheapest first, by where the path has got to and which way it's heading
+    let mut costs: HashMap<(Coords, usize), usize> = HashMap::new();
+    let mut came_from: HashMap<(Coords, usize), (Coords, usize)> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    for direction in 0..STEPS.len() {
+        costs.insert((from, direction), 0);
+        queue.push(Reverse((0, from, direction)));
+    }
+    while let Some(Reverse((cost, point, direction))) = queue.pop() {
+        if point == to {
+            let mut path = vec![point];
+            let mut state = (point, direction);
+            while let Some(&previous) = came_from.get(&state) {
+                path.push(previous.0);
+                state = previous;
+            }
+            path.reverse();
+            return Some(straighten(path));
+        }
+        if costs.get(&(point, direction)).map_or(false, |&c| c < cost) {
+            continue;
+        }
+        for (turn, &(dx, dy)) in STEPS.iter().enumerate() {
+            let turning = turn != direction;
+            // turning back, or turning where it would join a wire
+            if turning
+                && (STEPS[direction] == (-dx, -dy) || point != from && obstacles.is_on_wire(point))
+            {
+                continue;
+            }
+            let next = Coords::new(point.x + dx, point.y + dy);
+            let blocked = next != to
+                && (obstacles.columns.contains(&(next.x, next.y))
+                    || obstacles.is_along(next, dy == 0));
+            if !inside(next) || blocked {
+                continue;
+            }
+            let cost = cost + 1 + if turning && point != from { TURN } else { 0 };
+            if costs.get(&(next, turn)).map_or(true, |&c| cost < c) {
+                costs.insert((next, turn), cost);
+                came_from.insert((next, turn), (point, direction));
+                queue.push(Reverse((cost, next, turn)));
+            }
+        }
+    }
+    None
+}
+
+/// The same path without repeated points or corners that don't turn
+fn straighten(path: Vec<Coords>) -> Vec<Coords> {
+    let mut out: Vec<Coords> = Vec::new();
+    for point in path {
+        if let [.., a, b] = out[..] {
+            if (a.x == b.x && b.x == point.x) || (a.y == b.y && b.y == point.y) {
+                out.pop();
+            }
+        }
+        if out.last() != Some(&point) {
+            out.push(point);
+        }
+    }
+    out
+}
+
+/// Wires joining each pair of points in `links`, routed in turn around the given pins and wires
+/// and the wires routed before them
+pub fn route_links(
+    pins: impl Iterator<Item = Coords>,
+    segments: impl Iterator<Item = (Coords, Coords)>,
+    links: &[(Coords, Coords)],
+) -> Vec<Vec<(Coords, Coords)>> {
+    let mut obstacles = Obstacles::default();
+    for point in pins {
+        obstacles.add_point(point);
+    }
+    for segment in segments {
+        obstacles.add_segment(segment);
+    }
+    let mut routed = Vec::new();
+    for &(from, to) in links {
+        let path = route(from, to, &obstacles);
+        let segments: Vec<_> = path.windows(2).map(|s| (s[0], s[1])).collect();
+        for &segment in segments.iter() {
+            obstacles.add_segment(segment);
+        }
+        routed.push(segments);
+    }
+    routed
+}
+
+/// Gathers the gates of a netlist whose nets go by name, standing in a net of the circuit for
+/// each of the original's. A gate makes its own output net, which is then merged with the net it
+/// drives in the original.
+pub struct Gates {
+    pub builder: Builder,
+    nets: BTreeMap<String, usize>,
+    /// Nets merged into others, each pointing towards the one it was merged into
+    merged: BTreeMap<usize, usize>,
+    /// The original's ports, each bit one pin, with inputs and outputs kept apart
+    inputs: Vec<(String, usize)>,
+    outputs: Vec<(String, usize)>,
+}
+
+impl Gates {
+    pub fn new() -> Self {
+        Gates {
+            builder: Builder::new(Style::Mixed),
+            nets: BTreeMap::new(),
+            merged: BTreeMap::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// The net standing for one of the original's
+    pub fn net(&mut self, name: &str) -> usize {
+        if let Some(&net) = self.nets.get(name) {
+            return net;
+        }
+        let net = self.builder.net();
+        self.nets.insert(name.to_owned(), net);
+        net
+    }
+
+    fn find(&self, mut net: usize) -> usize {
+        while let Some(&next) = self.merged.get(&net) {
+            net = next;
+        }
+        net
+    }
+
+    /// Makes `net` the one that drives the original's net `name`
+    pub fn drive(&mut self, name: &str, net: usize) {
+        let original = self.net(name);
+        let (from, to) = (self.find(original), self.find(net));
+        if from != to {
+            self.merged.insert(from, to);
+        }
+    }
+
+    /// Adds a port of the original, one bit of it per pin, least significant first
+    pub fn port(&mut self, name: &str, input: bool, bits: &[String]) {
+        for (i, bit) in bits.iter().enumerate() {
+            let label = if bits.len() == 1 {
+                name.to_owned()
+            } else {
+                format!("{}[{}]", name, i)
+            };
+            let net = self.net(bit);
+            if input {
+                self.inputs.push((label, net));
+            } else {
+                self.outputs.push((label, net));
+            }
+        }
+    }
+
+    /// Lays out the gates and ports, with the gates in order of what drives what so that the
+    /// layout reads from the inputs up
+    pub fn finish(mut self, assembly: &mut Assembly) {
+        let mut netlist = std::mem::take(&mut self.builder).finish(Vec::new());
+        let merged: Vec<usize> = (0..netlist.nets).map(|net| self.find(net)).collect();
+        for gate in netlist.gates.iter_mut() {
+            for input in gate.inputs.iter_mut() {
+                *input = merged[*input];
+            }
+            gate.output = merged[gate.output];
+        }
+
+        let drivers: BTreeMap<usize, usize> = netlist
+            .gates
+            .iter()
+            .enumerate()
+            .map(|(i, gate)| (gate.output, i))
+            .collect();
+        let mut order = Vec::new();
+        let mut seen = vec![false; netlist.gates.len()];
+        for start in 0..netlist.gates.len() {
+            // registers break loops, so what feeds them can come after them
+            let mut stack = vec![(start, false)];
+            while let Some((i, done)) = stack.pop() {
+                if done {
+                    order.push(i);
+                    continue;
+                }
+                if seen[i] {
+                    continue;
+                }
+                seen[i] = true;
+                stack.push((i, true));
+                let gate = &netlist.gates[i];
+                if !matches!(gate.kind, ComponentKind::Register { .. }) {
+                    for input in gate.inputs.iter() {
+                        if let Some(&driver) = drivers.get(input) {
+                            stack.push((driver, false));
+                        }
+                    }
+                }
+            }
+        }
+        netlist.gates = order
+            .into_iter()
+            .map(|i| netlist.gates[i].clone())
+            .collect();
+
+        let (mut names, mut pins) = (Vec::new(), Vec::new());
+        for (name, net) in self.inputs.iter().chain(self.outputs.iter()) {
+            names.push(name.clone());
+            pins.push(vec![merged[*net]]);
+        }
+        netlist.pins = pins;
+        assembly.add_layout(&netlist, self.inputs.len(), &names);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{analysis, canvas::CanvasState};
+
+    /// The names of the inputs and outputs of an imported circuit, and each row of its truth
+    /// table, after checking that everything came across and is wired as it was
+    pub fn truth_table(import: Import) -> (Vec<String>, Vec<Vec<u64>>) {
+        assert!(import.left_out.is_empty(), "left out {:?}", import.left_out);
+        assert!(import.miswired.is_empty(), "miswired {:?}", import.miswired);
+        let mut canvas = CanvasState::new();
+        canvas.replace_with_import("", import);
+        let table = analysis::truth_table(canvas.components(), canvas.wires()).unwrap();
+        let names = table.inputs.iter().chain(table.outputs.iter());
+        let rows = table.rows.iter();
+        (
+            names.map(|port| port.name.clone()).collect(),
+            rows.map(|row| row.iter().map(|value| value.bits()).collect())
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,432 @@
+use std::rc::Rc;
+
+use druid::{
+    im,
+    kurbo::RoundedRect,
+    piet::{Text, TextLayout, TextLayoutBuilder},
+    widget::{Button, CrossAxisAlignment, Either, Flex, Label, LineBreaking, SizedBox},
+    BoxConstraints, Color, Data, Event, Rect, RenderContext, Size, Widget, WidgetExt,
+};
+
+use crate::{
+    analysis::TruthTable,
+    canvas::CanvasState,
+    logic::{self, Expr, Implicant},
+    synth::{Output, Spec},
+};
+
+/// The most inputs a map is drawn for
+pub const MAX_MAP_INPUTS: usize = 6;
+
+const CELL_SIZE: f64 = 32.0;
+/// Room for the names and Gray codes of the inputs above and to the left of the cells
+const MARGIN: f64 = 48.0;
+/// Colours the groups of the cover take in turn
+const GROUP_COLORS: [(u8, u8, u8); 8] = [
+    (0xe0, 0x30, 0x30),
+    (0x30, 0x80, 0xe0),
+    (0x30, 0xa0, 0x40),
+    (0xe0, 0x90, 0x10),
+    (0x90, 0x40, 0xc0),
+    (0x10, 0xa0, 0xa0),
+    (0xc0, 0x40, 0x90),
+    (0x80, 0x80, 0x20),
+];
+
+/// What an output is for one combination of inputs
+#[derive(Clone, Copy, Data, PartialEq, Eq)]
+pub enum Cell {
+    Zero,
+    One,
+    DontCare,
+}
+
+impl Cell {
+    fn next(self) -> Self {
+        match self {
+            Cell::Zero => Cell::One,
+            Cell::One => Cell::DontCare,
+            Cell::DontCare => Cell::Zero,
+        }
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            Cell::Zero => "0",
+            Cell::One => "1",
+            Cell::DontCare => "x",
+        }
+    }
+}
+
+/// The functions of a circuit with a few one-bit inputs, for drawing as a Karnaugh map. The
+/// first half of the inputs run down the side of the map and the rest along the top.
+#[derive(Clone, Data)]
+pub struct KarnaughMap {
+    inputs: Rc<Vec<String>>,
+    /// The name of each output and its value for every combination of inputs, the first input in
+    /// the most significant bit
+    outputs: im::Vector<(String, im::Vector<Cell>)>,
+    /// The output shown
+    selected: usize,
+}
+
+impl KarnaughMap {
+    /// The map of every output in `table`. Values that come out as errors or floating don't
+    /// matter.
+    pub fn of(table: &TruthTable) -> Result<Self, String> {
+        if table
+            .inputs
+            .iter()
+            .chain(&table.outputs)
+            .any(|port| port.width != 1)
+        {
+            return Err("Karnaugh maps are only drawn for one-bit inputs and outputs".to_owned());
+        }
+        if table.inputs.len() > MAX_MAP_INPUTS {
+            return Err(format!(
+                "Karnaugh maps are drawn for at most {} inputs",
+                MAX_MAP_INPUTS
+            ));
+        }
+        let inputs = table.inputs.len();
+        let outputs = table
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(i, port)| {
+                let cells = table
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let value = row[inputs + i];
+                        if value.has_error() || value.is_floating() {
+                            Cell::DontCare
+                        } else if value.is_high() {
+                            Cell::One
+                        } else {
+                            Cell::Zero
+                        }
+                    })
+                    .collect();
+                (port.name.clone(), cells)
+            })
+            .collect();
+        Ok(KarnaughMap {
+            inputs: Rc::new(table.inputs.iter().map(|p| p.name.clone()).collect()),
+            outputs,
+            selected: 0,
+        })
+    }
+
+    pub fn output_name(&self) -> &str {
+        &self.outputs[self.selected].0
+    }
+
+    pub fn next_output(&mut self) {
+        self.selected = (self.selected + 1) % self.outputs.len();
+    }
+
+    /// How many inputs run down the side of the map and how many along the top
+    fn split(&self) -> (usize, usize) {
+        let rows = self.inputs.len() / 2;
+        (rows, self.inputs.len() - rows)
+    }
+
+    /// The combination of inputs shown in a cell, going down and across in Gray code so that
+    /// neighbouring cells differ in one input
+    fn minterm(&self, row: usize, col: usize) -> u64 {
+        let (_, col_bits) = self.split();
+        (gray(row) << col_bits | gray(col)) as u64
+    }
+
+    fn cells(&self) -> &im::Vector<Cell> {
+        &self.outputs[self.selected].1
+    }
+
+    fn with(&self, cell: Cell) -> Vec<u64> {
+        (0..self.cells().len() as u64)
+            .filter(|&m| self.cells()[m as usize] == cell)
+            .collect()
+    }
+
+    /// The smallest set of prime implicants covering the ones of the output shown
+    pub fn cover(&self) -> Vec<Implicant> {
+        let vars = self.inputs.len() as u8;
+        logic::minimise(vars, &self.with(Cell::One), &self.with(Cell::DontCare))
+    }
+
+    pub fn expression(&self) -> Expr {
+        logic::sum_of_products(&self.cover(), &self.inputs)
+    }
+
+    /// Every output as a function to be built
+    pub fn spec(&self) -> Spec {
+        let outputs = (0..self.outputs.len())
+            .map(|i| {
+                let map = KarnaughMap {
+                    selected: i,
+                    ..self.clone()
+                };
+                Output {
+                    name: map.output_name().to_owned(),
+                    minterms: map.with(Cell::One),
+                    dont_cares: map.with(Cell::DontCare),
+                }
+            })
+            .collect();
+        Spec {
+            inputs: self.inputs.to_vec(),
+            outputs,
+        }
+    }
+
+    /// Takes on new names for the inputs and outputs, in the same order
+    pub fn rename(&mut self, inputs: Vec<String>, outputs: Vec<String>) {
+        self.inputs = Rc::new(inputs);
+        for ((name, _), new) in self.outputs.iter_mut().zip(outputs) {
+            *name = new;
+        }
+    }
+}
+
+fn gray(i: usize) -> usize {
+    i ^ i >> 1
+}
+
+/// The runs of consecutive indices below `len` that `included` holds for
+fn runs(len: usize, included: impl Fn(usize) -> bool) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for i in (0..len).filter(|&i| included(i)) {
+        match runs.last_mut() {
+            Some(run) if run.1 == i => run.1 = i + 1,
+            _ => runs.push((i, i + 1)),
+        }
+    }
+    runs
+}
+
+/// The Karnaugh map of the output being shown, with the groups of its minimal cover ringed in
+/// colour. Clicking a cell changes it from 0 to 1 to don't care and back.
+pub struct KarnaughView;
+
+impl KarnaughView {
+    fn cell_at(map: &KarnaughMap, pos: druid::Point) -> Option<(usize, usize)> {
+        let (row_bits, col_bits) = map.split();
+        let row = ((pos.y - MARGIN) / CELL_SIZE).floor();
+        let col = ((pos.x - MARGIN) / CELL_SIZE).floor();
+        if row < 0.0 || col < 0.0 || row as usize >= 1 << row_bits || col as usize >= 1 << col_bits
+        {
+            return None;
+        }
+        Some((row as usize, col as usize))
+    }
+}
+
+impl Widget<CanvasState> for KarnaughView {
+    fn event(
+        &mut self,
+        ctx: &mut druid::EventCtx,
+        event: &Event,
+        data: &mut CanvasState,
+        _env: &druid::Env,
+    ) {
+        if let Event::MouseDown(m) = event {
+            if let Some(map) = data.karnaugh_mut() {
+                if let Some((row, col)) = KarnaughView::cell_at(map, m.pos) {
+                    let minterm = map.minterm(row, col) as usize;
+                    let selected = map.selected;
+                    let cell = &mut map.outputs[selected].1[minterm];
+                    *cell = cell.next();
+                }
+            }
+            ctx.set_handled();
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut druid::LifeCycleCtx,
+        _event: &druid::LifeCycle,
+        _data: &CanvasState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &CanvasState,
+        data: &CanvasState,
+        _env: &druid::Env,
+    ) {
+        if !old_data.karnaugh().cloned().same(&data.karnaugh().cloned()) {
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut druid::LayoutCtx,
+        bc: &BoxConstraints,
+        data: &CanvasState,
+        _env: &druid::Env,
+    ) -> Size {
+        let (rows, cols) = data.karnaugh().map_or((0, 0), |map| map.split());
+        bc.constrain(Size::new(
+            MARGIN + (1 << cols) as f64 * CELL_SIZE + 4.0,
+            MARGIN + (1 << rows) as f64 * CELL_SIZE + 4.0,
+        ))
+    }
+
+    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &CanvasState, _env: &druid::Env) {
+        let map = match data.karnaugh() {
+            Some(map) => map,
+            None => return,
+        };
+        let (row_bits, col_bits) = map.split();
+        let (rows, cols) = (1usize << row_bits, 1usize << col_bits);
+        let cell_rect = |row: usize, col: usize| {
+            let (x, y) = (
+                MARGIN + col as f64 * CELL_SIZE,
+                MARGIN + row as f64 * CELL_SIZE,
+            );
+            Rect::new(x, y, x + CELL_SIZE, y + CELL_SIZE)
+        };
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::WHITE);
+
+        // which inputs run which way, and their values in each row and column
+        let (row_names, col_names) = map.inputs.split_at(row_bits);
+        let corner = format!("{}\\{}", row_names.join(""), col_names.join(""));
+        let corner = ctx
+            .text()
+            .new_text_layout(corner)
+            .text_color(Color::GRAY)
+            .build()
+            .unwrap();
+        ctx.draw_text(&corner, (2.0, 2.0));
+        let code = |i: usize, bits: usize| {
+            (0..bits)
+                .rev()
+                .map(|b| if gray(i) >> b & 1 == 1 { '1' } else { '0' })
+                .collect::<String>()
+        };
+        for col in 0..cols {
+            let label = ctx
+                .text()
+                .new_text_layout(code(col, col_bits))
+                .text_color(Color::BLACK)
+                .build()
+                .unwrap();
+            let rect = cell_rect(0, col);
+            let x = rect.center().x - label.size().width / 2.0;
+            ctx.draw_text(&label, (x, MARGIN - label.size().height - 2.0));
+        }
+        for row in 0..rows {
+            let label = ctx
+                .text()
+                .new_text_layout(code(row, row_bits))
+                .text_color(Color::BLACK)
+                .build()
+                .unwrap();
+            let rect = cell_rect(row, 0);
+            let y = rect.center().y - label.size().height / 2.0;
+            ctx.draw_text(&label, (MARGIN - label.size().width - 4.0, y));
+        }
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let rect = cell_rect(row, col);
+                ctx.stroke(rect, &Color::GRAY, 1.0);
+                let cell = map.cells()[map.minterm(row, col) as usize];
+                let label = ctx
+                    .text()
+                    .new_text_layout(cell.text())
+                    .text_color(Color::BLACK)
+                    .build()
+                    .unwrap();
+                let size = label.size();
+                let center = rect.center();
+                ctx.draw_text(
+                    &label,
+                    (center.x - size.width / 2.0, center.y - size.height / 2.0),
+                );
+            }
+        }
+
+        // each group is drawn as one box for every block of cells it covers, which is more than
+        // one where it wraps around an edge of the map
+        let row_of = |m: u64| (m >> col_bits) as usize;
+        let col_of = |m: u64| m as usize & (cols - 1);
+        for (i, implicant) in map.cover().iter().enumerate() {
+            let (r, g, b) = GROUP_COLORS[i % GROUP_COLORS.len()];
+            let inset = 3.0 + (i % 4) as f64 * 2.0;
+            let covers = |mask_of: &dyn Fn(u64) -> usize, code: usize| {
+                let value = mask_of(implicant.value);
+                let mask = mask_of(implicant.mask);
+                code & !mask == value
+            };
+            let row_runs = runs(rows, |row| covers(&row_of, gray(row)));
+            let col_runs = runs(cols, |col| covers(&col_of, gray(col)));
+            for &(top, bottom) in row_runs.iter() {
+                for &(left, right) in col_runs.iter() {
+                    let rect = cell_rect(top, left)
+                        .union(cell_rect(bottom - 1, right - 1))
+                        .inset(-inset);
+                    let shape = RoundedRect::from_rect(rect, 6.0);
+                    ctx.fill(shape, &Color::rgba8(r, g, b, 0x30));
+                    ctx.stroke(shape, &Color::rgb8(r, g, b), 2.0);
+                }
+            }
+        }
+    }
+}
+
+/// The map of one output with its minimal expression under it, and buttons to move on to the
+/// next output and to rebuild the circuit from the map. It is empty while no map is shown.
+pub fn panel() -> impl Widget<CanvasState> {
+    let bar = Flex::row()
+        .with_child(Label::dynamic(|data: &CanvasState, _| {
+            data.karnaugh()
+                .map(|map| format!("Karnaugh map of {}", map.output_name()))
+                .unwrap_or_default()
+        }))
+        .with_flex_spacer(1.0)
+        .with_child(
+            Button::new("Close").on_click(|_, data: &mut CanvasState, _| data.close_karnaugh()),
+        );
+    let buttons = Flex::row()
+        .with_child(
+            Button::new("Next output").on_click(|_, data: &mut CanvasState, _| {
+                if let Some(map) = data.karnaugh_mut() {
+                    map.next_output();
+                }
+            }),
+        )
+        .with_child(
+            Button::new("Rebuild circuit")
+                .on_click(|_, data: &mut CanvasState, _| data.rebuild_from_karnaugh()),
+        );
+    let expression = Label::dynamic(|data: &CanvasState, _| {
+        data.karnaugh()
+            .map(|map| format!("{} = {}", map.output_name(), map.expression()))
+            .unwrap_or_default()
+    })
+    .with_line_break_mode(LineBreaking::WordWrap);
+    let panel = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(bar)
+        .with_child(KarnaughView)
+        .with_child(Label::new("Click a cell to change it to 0, 1 or x"))
+        .with_child(expression)
+        .with_child(buttons)
+        .padding(4.0)
+        .fix_width(320.0);
+    Either::new(
+        |data: &CanvasState, _| data.karnaugh().is_some(),
+        panel,
+        SizedBox::empty(),
+    )
+}
@@ -0,0 +1,968 @@
+use crate::value::{Radix, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinType {
+    Input,
+    Output,
+}
+
+/// Which edge of a boxed symbol a pin sits on, when the symbol is oriented north
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    /// Data inputs
+    Bottom,
+    /// Control inputs, like selects and clocks
+    Left,
+    /// Outputs
+    Top,
+}
+
+#[derive(Clone, Debug)]
+pub struct PinSpec {
+    pub name: String,
+    pub ty: PinType,
+    pub width: u8,
+    pub side: Side,
+}
+
+impl PinSpec {
+    fn input(name: impl Into<String>, width: u8) -> Self {
+        PinSpec {
+            name: name.into(),
+            ty: PinType::Input,
+            width,
+            side: Side::Bottom,
+        }
+    }
+
+    fn control(name: impl Into<String>, width: u8) -> Self {
+        PinSpec {
+            name: name.into(),
+            ty: PinType::Input,
+            width,
+            side: Side::Left,
+        }
+    }
+
+    fn output(name: impl Into<String>, width: u8) -> Self {
+        PinSpec {
+            name: name.into(),
+            ty: PinType::Output,
+            width,
+            side: Side::Top,
+        }
+    }
+}
+
+pub const MAX_SELECT: u8 = 4;
+pub const MAX_WIDTH: u8 = 32;
+pub const MAX_ADDRESS: u8 = 16;
+pub const MAX_MATRIX: u8 = 16;
+pub const MAX_TTY_ROWS: u8 = 32;
+pub const MAX_TTY_COLS: u8 = 80;
+/// How many keystrokes a keyboard holds on to before it starts dropping them
+pub const KEYBOARD_BUFFER: usize = 32;
+
+/// The number of bits needed to give a shift distance for a value of the given width
+pub fn shift_bits(width: u8) -> u8 {
+    (8 - (width.max(2) - 1).leading_zeros()) as u8
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShiftMode {
+    /// Serial in, parallel out
+    Sipo,
+    /// Parallel in, serial out
+    Piso,
+    /// Holds, shifts either way or loads in parallel depending on `MODE`
+    Universal,
+}
+
+/// Whatever a component needs to remember between evaluations
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct State {
+    /// The clock input as of the last evaluation
+    pub clock: bool,
+    pub value: u64,
+    /// Keystrokes that a keyboard hasn't had cleared yet, or what a terminal shows
+    pub text: String,
+    /// The contents of a memory, where any words past the end read as zero
+    pub memory: Vec<u64>,
+}
+
+impl State {
+    /// Records the new clock input, returning whether it has just risen
+    fn rising_edge(&mut self, clock: Value) -> bool {
+        let rising = clock.is_high() && !self.clock;
+        self.clock = clock.is_high();
+        rising
+    }
+
+    /// Adds a character to the end of a terminal's text, wrapping at `cols` and scrolling away
+    /// all but the last `rows` lines
+    fn type_char(&mut self, c: u8, rows: usize, cols: usize) {
+        match c {
+            b'\n' => self.text.push('\n'),
+            // backspace, which stops at the start of the line
+            0x08 if !self.text.ends_with('\n') => {
+                self.text.pop();
+            },
+            c if c.is_ascii_graphic() || c == b' ' => {
+                let line = self.text.rsplit('\n').next().unwrap();
+                if line.len() >= cols {
+                    self.text.push('\n');
+                }
+                self.text.push(c as char);
+            },
+            _ => {},
+        }
+        while self.text.matches('\n').count() >= rows {
+            let end = self.text.find('\n').unwrap();
+            self.text.drain(..=end);
+        }
+    }
+
+    fn read(&self, address: Value) -> u64 {
+        self.memory
+            .get(address.bits() as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Stores a word in a memory with `width` address bits, which it never grows past
+    fn write(&mut self, address: Value, width: u8, data: Value) {
+        let address = (address.bits() & Value::mask(width)) as usize;
+        if self.memory.len() <= address {
+            self.memory.resize(address + 1, 0);
+        }
+        self.memory[address] = data.bits();
+    }
+}
+
+/// What a component does, along with any attributes that change its shape.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentKind {
+    Not,
+    And,
+    Or,
+    Nand,
+    Nor,
+    Xor,
+    Constant {
+        width: u8,
+        value: u64,
+    },
+    /// Breaks a bus out into its bits, least significant first
+    Split {
+        width: u8,
+    },
+    /// Gathers bits into a bus, least significant first
+    Join {
+        width: u8,
+    },
+    /// Routes one of `2^select` inputs to the output
+    Mux {
+        select: u8,
+        width: u8,
+    },
+    /// Routes the input to one of `2^select` outputs, the rest are zero
+    Demux {
+        select: u8,
+        width: u8,
+    },
+    /// Sets the one of `2^select` outputs picked by the input
+    Decoder {
+        select: u8,
+    },
+    /// Outputs the index of the highest set input, and whether any input is set at all
+    PriorityEncoder {
+        select: u8,
+    },
+    FullAdder,
+    /// Unsigned addition with carry in and out
+    Adder {
+        width: u8,
+    },
+    /// Unsigned subtraction with borrow in and out
+    Subtractor {
+        width: u8,
+    },
+    /// Unsigned magnitude comparison
+    Comparator {
+        width: u8,
+    },
+    /// Unsigned multiplication, with a product twice as wide as the operands
+    Multiplier {
+        width: u8,
+    },
+    /// Shifts or rotates by a variable distance. `OP` picks between shifting left, shifting right,
+    /// shifting right arithmetically and rotating left.
+    Shifter {
+        width: u8,
+    },
+    /// Performs the operation picked by `OP` out of add, subtract, and, or, xor, not, set if less
+    /// than and pass through `B`, flagging carry or borrow and a zero result
+    Alu {
+        width: u8,
+    },
+    /// Stores `D` on a rising clock edge while enabled, and clears while reset is high
+    Register {
+        width: u8,
+    },
+    /// Counts up or down on a rising clock edge while enabled, or loads `D`. `TC` is high while
+    /// enabled and on the last value before wrapping around.
+    Counter {
+        width: u8,
+    },
+    /// Shifts towards the most significant bit on a rising clock edge, apart from the universal
+    /// kind whose `MODE` picks between holding, shifting up from `SL`, shifting down from `SR`
+    /// and loading `D`
+    ShiftRegister {
+        width: u8,
+        mode: ShiftMode,
+    },
+    /// Outputs the word at the given address
+    Rom {
+        address: u8,
+        data: u8,
+    },
+    /// Outputs the word at the given address, and stores `D` there while `WE` is high, or on a
+    /// rising clock edge while `WE` is high if it is synchronous
+    Ram {
+        address: u8,
+        data: u8,
+        sync: bool,
+    },
+    /// A value that is set by clicking on its bits
+    Input {
+        width: u8,
+    },
+    /// Toggles on every tick of the simulation clock
+    Clock,
+    /// Lights segments `a` to `g` and the decimal point from one input each
+    SevenSegment,
+    /// Shows its input as a hex digit
+    HexDisplay,
+    /// Lights the LEDs whose row and column inputs are both high
+    DotMatrix {
+        rows: u8,
+        cols: u8,
+    },
+    /// Holds keys typed on the canvas while it is selected, showing the oldest on `D` with `RDY`
+    /// high until a rising edge on `CLR` moves on to the next
+    Keyboard,
+    /// Shows the ASCII characters written to it on rising clock edges while `WE` is high, and
+    /// clears while `CLR` is high
+    Tty {
+        rows: u8,
+        cols: u8,
+    },
+    /// Passes its input through while `EN` is high, and lets go of its output otherwise
+    TriState {
+        width: u8,
+    },
+    /// Passes its input through while `N` is high and `P` is low, and lets go of its output while
+    /// `N` is low and `P` is high. Anything else is an error. Only conducts from `A` to `Y`.
+    TransmissionGate {
+        width: u8,
+    },
+    /// Shows the value of the net it touches, whatever its width
+    Probe {
+        radix: Radix,
+    },
+}
+
+impl ComponentKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ComponentKind::Not => "NOT",
+            ComponentKind::And => "AND",
+            ComponentKind::Or => "OR",
+            ComponentKind::Nand => "NAND",
+            ComponentKind::Nor => "NOR",
+            ComponentKind::Xor => "XOR",
+            ComponentKind::Constant { .. } => "CONST",
+            ComponentKind::Split { .. } => "SPLIT",
+            ComponentKind::Join { .. } => "JOIN",
+            ComponentKind::Mux { .. } => "MUX",
+            ComponentKind::Demux { .. } => "DEMUX",
+            ComponentKind::Decoder { .. } => "DEC",
+            ComponentKind::PriorityEncoder { .. } => "PRI",
+            ComponentKind::FullAdder => "FA",
+            ComponentKind::Adder { .. } => "ADD",
+            ComponentKind::Subtractor { .. } => "SUB",
+            ComponentKind::Comparator { .. } => "CMP",
+            ComponentKind::Multiplier { .. } => "MUL",
+            ComponentKind::Shifter { .. } => "SHIFT",
+            ComponentKind::Alu { .. } => "ALU",
+            ComponentKind::Register { .. } => "REG",
+            ComponentKind::Counter { .. } => "CTR",
+            ComponentKind::ShiftRegister { mode, .. } => match mode {
+                ShiftMode::Sipo => "SIPO",
+                ShiftMode::Piso => "PISO",
+                ShiftMode::Universal => "SHIFT REG",
+            },
+            ComponentKind::Rom { .. } => "ROM",
+            ComponentKind::Ram { .. } => "RAM",
+            ComponentKind::Input { .. } => "IN",
+            ComponentKind::Clock => "CLK",
+            ComponentKind::SevenSegment => "7SEG",
+            ComponentKind::HexDisplay => "HEX",
+            ComponentKind::DotMatrix { .. } => "LED",
+            ComponentKind::Keyboard => "KBD",
+            ComponentKind::Tty { .. } => "TTY",
+            ComponentKind::TriState { .. } => "TRI",
+            ComponentKind::TransmissionGate { .. } => "TGATE",
+            ComponentKind::Probe { .. } => "PROBE",
+        }
+    }
+
+    /// Whether this kind remembers anything between evaluations, so that its outputs don't just
+    /// depend on its inputs
+    pub fn is_sequential(&self) -> bool {
+        matches!(
+            self,
+            ComponentKind::Register { .. }
+                | ComponentKind::Counter { .. }
+                | ComponentKind::ShiftRegister { .. }
+                | ComponentKind::Ram { .. }
+                | ComponentKind::Clock
+                | ComponentKind::Keyboard
+                | ComponentKind::Tty { .. }
+        )
+    }
+
+    /// How long a change at an input takes to reach the outputs when propagation delays are
+    /// simulated, roughly in gate delays
+    pub fn delay(&self) -> u64 {
+        match self {
+            ComponentKind::Not
+            | ComponentKind::And
+            | ComponentKind::Or
+            | ComponentKind::Nand
+            | ComponentKind::Nor
+            | ComponentKind::Xor
+            | ComponentKind::TriState { .. }
+            | ComponentKind::TransmissionGate { .. } => 1,
+            ComponentKind::Mux { .. }
+            | ComponentKind::Demux { .. }
+            | ComponentKind::Decoder { .. }
+            | ComponentKind::PriorityEncoder { .. }
+            | ComponentKind::FullAdder
+            | ComponentKind::Register { .. }
+            | ComponentKind::Counter { .. }
+            | ComponentKind::ShiftRegister { .. } => 2,
+            ComponentKind::Adder { .. }
+            | ComponentKind::Subtractor { .. }
+            | ComponentKind::Comparator { .. }
+            | ComponentKind::Shifter { .. }
+            | ComponentKind::Rom { .. }
+            | ComponentKind::Ram { .. } => 4,
+            ComponentKind::Multiplier { .. } | ComponentKind::Alu { .. } => 8,
+            // wiring, sources and displays
+            ComponentKind::Constant { .. }
+            | ComponentKind::Split { .. }
+            | ComponentKind::Join { .. }
+            | ComponentKind::Input { .. }
+            | ComponentKind::Clock
+            | ComponentKind::SevenSegment
+            | ComponentKind::HexDisplay
+            | ComponentKind::DotMatrix { .. }
+            | ComponentKind::Keyboard
+            | ComponentKind::Tty { .. }
+            | ComponentKind::Probe { .. } => 0,
+        }
+    }
+
+    /// The pins of this kind, inputs in the order `evaluate` takes them and outputs in the order
+    /// it returns them.
+    pub fn pins(&self) -> Vec<PinSpec> {
+        match *self {
+            ComponentKind::Not => vec![PinSpec::input("A", 1), PinSpec::output("Y", 1)],
+            ComponentKind::And
+            | ComponentKind::Or
+            | ComponentKind::Nand
+            | ComponentKind::Nor
+            | ComponentKind::Xor => vec![
+                PinSpec::input("A", 1),
+                PinSpec::input("B", 1),
+                PinSpec::output("Y", 1),
+            ],
+            ComponentKind::Constant { width, .. } => vec![PinSpec::output("Y", width)],
+            ComponentKind::Split { width } => {
+                let mut pins = vec![PinSpec::input("D", width)];
+                pins.extend((0..width).map(|i| PinSpec::output(i.to_string(), 1)));
+                pins
+            },
+            ComponentKind::Join { width } => {
+                let mut pins: Vec<_> = (0..width)
+                    .map(|i| PinSpec::input(i.to_string(), 1))
+                    .collect();
+                pins.push(PinSpec::output("Y", width));
+                pins
+            },
+            ComponentKind::Mux { select, width } => {
+                let mut pins: Vec<_> = (0..1 << select)
+                    .map(|i| PinSpec::input(format!("D{}", i), width))
+                    .collect();
+                pins.push(PinSpec::control("S", select));
+                pins.push(PinSpec::output("Y", width));
+                pins
+            },
+            ComponentKind::Demux { select, width } => {
+                let mut pins = vec![PinSpec::input("D", width), PinSpec::control("S", select)];
+                pins.extend((0..1 << select).map(|i| PinSpec::output(format!("Y{}", i), width)));
+                pins
+            },
+            ComponentKind::Decoder { select } => {
+                let mut pins = vec![PinSpec::input("A", select)];
+                pins.extend((0..1 << select).map(|i| PinSpec::output(format!("Y{}", i), 1)));
+                pins
+            },
+            ComponentKind::PriorityEncoder { select } => {
+                let mut pins: Vec<_> = (0..1 << select)
+                    .map(|i| PinSpec::input(format!("D{}", i), 1))
+                    .collect();
+                pins.push(PinSpec::output("Y", select));
+                pins.push(PinSpec::output("V", 1));
+                pins
+            },
+            ComponentKind::FullAdder => vec![
+                PinSpec::input("A", 1),
+                PinSpec::input("B", 1),
+                PinSpec::control("Cin", 1),
+                PinSpec::output("S", 1),
+                PinSpec::output("Cout", 1),
+            ],
+            ComponentKind::Adder { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::input("B", width),
+                PinSpec::control("Cin", 1),
+                PinSpec::output("S", width),
+                PinSpec::output("Cout", 1),
+            ],
+            ComponentKind::Subtractor { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::input("B", width),
+                PinSpec::control("Bin", 1),
+                PinSpec::output("D", width),
+                PinSpec::output("Bout", 1),
+            ],
+            ComponentKind::Comparator { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::input("B", width),
+                PinSpec::output("LT", 1),
+                PinSpec::output("EQ", 1),
+                PinSpec::output("GT", 1),
+            ],
+            ComponentKind::Multiplier { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::input("B", width),
+                PinSpec::output("P", width * 2),
+            ],
+            ComponentKind::Shifter { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::input("S", shift_bits(width)),
+                PinSpec::control("OP", 2),
+                PinSpec::output("Y", width),
+            ],
+            ComponentKind::Alu { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::input("B", width),
+                PinSpec::control("OP", 3),
+                PinSpec::output("Y", width),
+                PinSpec::output("C", 1),
+                PinSpec::output("Z", 1),
+            ],
+            ComponentKind::Register { width } => vec![
+                PinSpec::input("D", width),
+                PinSpec::control("CLK", 1),
+                PinSpec::control("EN", 1),
+                PinSpec::control("RST", 1),
+                PinSpec::output("Q", width),
+            ],
+            ComponentKind::Counter { width } => vec![
+                PinSpec::input("D", width),
+                PinSpec::control("CLK", 1),
+                PinSpec::control("EN", 1),
+                PinSpec::control("LD", 1),
+                PinSpec::control("UP", 1),
+                PinSpec::control("RST", 1),
+                PinSpec::output("Q", width),
+                PinSpec::output("TC", 1),
+            ],
+            ComponentKind::ShiftRegister { width, mode } => match mode {
+                ShiftMode::Sipo => vec![
+                    PinSpec::input("SI", 1),
+                    PinSpec::control("CLK", 1),
+                    PinSpec::control("EN", 1),
+                    PinSpec::control("RST", 1),
+                    PinSpec::output("Q", width),
+                ],
+                ShiftMode::Piso => vec![
+                    PinSpec::input("D", width),
+                    PinSpec::input("SI", 1),
+                    PinSpec::control("CLK", 1),
+                    PinSpec::control("LD", 1),
+                    PinSpec::control("RST", 1),
+                    PinSpec::output("SO", 1),
+                ],
+                ShiftMode::Universal => vec![
+                    PinSpec::input("D", width),
+                    PinSpec::input("SL", 1),
+                    PinSpec::input("SR", 1),
+                    PinSpec::control("CLK", 1),
+                    PinSpec::control("MODE", 2),
+                    PinSpec::control("RST", 1),
+                    PinSpec::output("Q", width),
+                ],
+            },
+            ComponentKind::Rom { address, data } => {
+                vec![PinSpec::input("A", address), PinSpec::output("Q", data)]
+            },
+            ComponentKind::Ram {
+                address,
+                data,
+                sync,
+            } => {
+                let mut pins = vec![
+                    PinSpec::input("A", address),
+                    PinSpec::input("D", data),
+                    PinSpec::control("WE", 1),
+                ];
+                if sync {
+                    pins.push(PinSpec::control("CLK", 1));
+                }
+                pins.push(PinSpec::output("Q", data));
+                pins
+            },
+            ComponentKind::Input { width } => vec![PinSpec::output("Y", width)],
+            ComponentKind::Clock => vec![PinSpec::output("CLK", 1)],
+            ComponentKind::SevenSegment => ["a", "b", "c", "d", "e", "f", "g", "DP"]
+                .iter()
+                .map(|name| PinSpec::input(*name, 1))
+                .collect(),
+            ComponentKind::HexDisplay => vec![PinSpec::input("D", 4), PinSpec::input("DP", 1)],
+            ComponentKind::DotMatrix { rows, cols } => {
+                vec![PinSpec::input("R", rows), PinSpec::input("C", cols)]
+            },
+            ComponentKind::Keyboard => vec![
+                PinSpec::control("CLR", 1),
+                PinSpec::output("D", 7),
+                PinSpec::output("RDY", 1),
+            ],
+            ComponentKind::Tty { .. } => vec![
+                PinSpec::input("D", 7),
+                PinSpec::control("CLK", 1),
+                PinSpec::control("WE", 1),
+                PinSpec::control("CLR", 1),
+            ],
+            ComponentKind::TriState { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::control("EN", 1),
+                PinSpec::output("Y", width),
+            ],
+            ComponentKind::TransmissionGate { width } => vec![
+                PinSpec::input("A", width),
+                PinSpec::control("N", 1),
+                PinSpec::control("P", 1),
+                PinSpec::output("Y", width),
+            ],
+            ComponentKind::Probe { .. } => vec![PinSpec::input("A", 1)],
+        }
+    }
+
+    /// The address and data widths of a memory, or `None` if this kind isn't one
+    pub fn memory_size(&self) -> Option<(u8, u8)> {
+        match *self {
+            ComponentKind::Rom { address, data } | ComponentKind::Ram { address, data, .. } => {
+                Some((address, data))
+            },
+            _ => None,
+        }
+    }
+
+    /// The same kind with its main attribute (select width for the routing parts, address width
+    /// for memories, rows for a dot matrix, bit width for the rest) nudged by `delta`, or `None`
+    /// if it has no such attribute or the result would be out of range.
+    pub fn resized(&self, delta: i8) -> Option<Self> {
+        let nudge = |n: u8, max: u8| {
+            let n = n as i8 + delta;
+            if n >= 1 && n <= max as i8 {
+                Some(n as u8)
+            } else {
+                None
+            }
+        };
+        let select = |n| nudge(n, MAX_SELECT);
+        let width = |n| nudge(n, MAX_WIDTH);
+        match *self {
+            ComponentKind::Constant { width: w, value } => {
+                let w = width(w)?;
+                Some(ComponentKind::Constant {
+                    width: w,
+                    value: value & Value::mask(w),
+                })
+            },
+            ComponentKind::Split { width: w } => Some(ComponentKind::Split { width: width(w)? }),
+            ComponentKind::Join { width: w } => Some(ComponentKind::Join { width: width(w)? }),
+            ComponentKind::Mux { select: s, width } => Some(ComponentKind::Mux {
+                select: select(s)?,
+                width,
+            }),
+            ComponentKind::Demux { select: s, width } => Some(ComponentKind::Demux {
+                select: select(s)?,
+                width,
+            }),
+            ComponentKind::Decoder { select: s } => {
+                Some(ComponentKind::Decoder { select: select(s)? })
+            },
+            ComponentKind::PriorityEncoder { select: s } => {
+                Some(ComponentKind::PriorityEncoder { select: select(s)? })
+            },
+            ComponentKind::Adder { width: w } => Some(ComponentKind::Adder { width: width(w)? }),
+            ComponentKind::Subtractor { width: w } => {
+                Some(ComponentKind::Subtractor { width: width(w)? })
+            },
+            ComponentKind::Comparator { width: w } => {
+                Some(ComponentKind::Comparator { width: width(w)? })
+            },
+            ComponentKind::Multiplier { width: w } => {
+                Some(ComponentKind::Multiplier { width: width(w)? })
+            },
+            ComponentKind::Shifter { width: w } => {
+                Some(ComponentKind::Shifter { width: width(w)? })
+            },
+            ComponentKind::Alu { width: w } => Some(ComponentKind::Alu { width: width(w)? }),
+            ComponentKind::Register { width: w } => {
+                Some(ComponentKind::Register { width: width(w)? })
+            },
+            ComponentKind::Counter { width: w } => {
+                Some(ComponentKind::Counter { width: width(w)? })
+            },
+            ComponentKind::ShiftRegister { width: w, mode } => Some(ComponentKind::ShiftRegister {
+                width: width(w)?,
+                mode,
+            }),
+            ComponentKind::Rom { address, data } => Some(ComponentKind::Rom {
+                address: nudge(address, MAX_ADDRESS)?,
+                data,
+            }),
+            ComponentKind::Ram {
+                address,
+                data,
+                sync,
+            } => Some(ComponentKind::Ram {
+                address: nudge(address, MAX_ADDRESS)?,
+                data,
+                sync,
+            }),
+            ComponentKind::Input { width: w } => Some(ComponentKind::Input { width: width(w)? }),
+            ComponentKind::TriState { width: w } => {
+                Some(ComponentKind::TriState { width: width(w)? })
+            },
+            ComponentKind::TransmissionGate { width: w } => {
+                Some(ComponentKind::TransmissionGate { width: width(w)? })
+            },
+            ComponentKind::DotMatrix { rows, cols } => Some(ComponentKind::DotMatrix {
+                rows: nudge(rows, MAX_MATRIX)?,
+                cols,
+            }),
+            ComponentKind::Tty { rows, cols } => Some(ComponentKind::Tty {
+                rows: nudge(rows, MAX_TTY_ROWS)?,
+                cols,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The same kind with the width of its data (or its columns, for a dot matrix) nudged by
+    /// `delta`, for kinds that have a width as well as their main attribute, or `None` if it has
+    /// no such width or it would be out of range.
+    pub fn widened(&self, delta: i8) -> Option<Self> {
+        let nudge = |n: u8, max: u8| {
+            let n = n as i8 + delta;
+            if n >= 1 && n <= max as i8 {
+                Some(n as u8)
+            } else {
+                None
+            }
+        };
+        let width = |n| nudge(n, MAX_WIDTH);
+        match *self {
+            ComponentKind::Mux { select, width: w } => Some(ComponentKind::Mux {
+                select,
+                width: width(w)?,
+            }),
+            ComponentKind::Demux { select, width: w } => Some(ComponentKind::Demux {
+                select,
+                width: width(w)?,
+            }),
+            ComponentKind::Rom { address, data } => Some(ComponentKind::Rom {
+                address,
+                data: width(data)?,
+            }),
+            ComponentKind::Ram {
+                address,
+                data,
+                sync,
+            } => Some(ComponentKind::Ram {
+                address,
+                data: width(data)?,
+                sync,
+            }),
+            ComponentKind::DotMatrix { rows, cols } => Some(ComponentKind::DotMatrix {
+                rows,
+                cols: nudge(cols, MAX_MATRIX)?,
+            }),
+            ComponentKind::Tty { rows, cols } => Some(ComponentKind::Tty {
+                rows,
+                cols: nudge(cols, MAX_TTY_COLS)?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Works out the outputs of this kind from its inputs, updating `state` if it is sequential
+    pub fn evaluate(&self, state: &mut State, inputs: &[Value]) -> Vec<Value> {
+        match *self {
+            ComponentKind::Not => vec![Value::from_bool(!inputs[0].is_high())],
+            ComponentKind::And => {
+                vec![Value::from_bool(inputs[0].is_high() && inputs[1].is_high())]
+            },
+            ComponentKind::Or => vec![Value::from_bool(inputs[0].is_high() || inputs[1].is_high())],
+            ComponentKind::Nand => {
+                vec![Value::from_bool(
+                    !(inputs[0].is_high() && inputs[1].is_high()),
+                )]
+            },
+            ComponentKind::Nor => {
+                vec![Value::from_bool(
+                    !(inputs[0].is_high() || inputs[1].is_high()),
+                )]
+            },
+            ComponentKind::Xor => {
+                vec![Value::from_bool(inputs[0].is_high() != inputs[1].is_high())]
+            },
+            ComponentKind::Constant { width, value } => vec![Value::new(width, value)],
+            ComponentKind::Split { width } => (0..width)
+                .map(|i| Value::from_bool(inputs[0].bit(i)))
+                .collect(),
+            ComponentKind::Join { width } => {
+                let bits = (0..width).fold(0, |acc, i| acc | (inputs[i as usize].bits() & 1) << i);
+                vec![Value::new(width, bits)]
+            },
+            ComponentKind::Mux { select, width } => {
+                let s = inputs[1 << select];
+                match inputs.get(s.bits() as usize) {
+                    Some(&input) if s.is_defined() && s.bits() < 1 << select => vec![input],
+                    _ => vec![Value::error(width)],
+                }
+            },
+            ComponentKind::Demux { select, width } => {
+                let s = inputs[1].bits() as usize;
+                (0..1 << select)
+                    .map(|i| {
+                        if i == s {
+                            inputs[0]
+                        } else {
+                            Value::zero(width)
+                        }
+                    })
+                    .collect()
+            },
+            ComponentKind::Decoder { select } => {
+                let s = inputs[0].bits() as usize;
+                (0..1 << select).map(|i| Value::from_bool(i == s)).collect()
+            },
+            ComponentKind::PriorityEncoder { select } => {
+                match inputs.iter().rposition(Value::is_high) {
+                    Some(i) => vec![Value::new(select, i as u64), Value::from_bool(true)],
+                    None => vec![Value::zero(select), Value::from_bool(false)],
+                }
+            },
+            ComponentKind::FullAdder => {
+                let sum = inputs.iter().filter(|v| v.is_high()).count();
+                vec![Value::from_bool(sum & 1 == 1), Value::from_bool(sum >= 2)]
+            },
+            ComponentKind::Adder { width } => {
+                let sum = inputs[0].bits() + inputs[1].bits() + inputs[2].bits();
+                vec![
+                    Value::new(width, sum),
+                    Value::from_bool(sum >> width & 1 == 1),
+                ]
+            },
+            ComponentKind::Subtractor { width } => {
+                let (a, b) = (inputs[0].bits(), inputs[1].bits() + inputs[2].bits());
+                vec![
+                    Value::new(width, a.wrapping_sub(b)),
+                    Value::from_bool(a < b),
+                ]
+            },
+            ComponentKind::Comparator { .. } => {
+                let (a, b) = (inputs[0].bits(), inputs[1].bits());
+                vec![
+                    Value::from_bool(a < b),
+                    Value::from_bool(a == b),
+                    Value::from_bool(a > b),
+                ]
+            },
+            ComponentKind::Multiplier { width } => {
+                vec![Value::new(width * 2, inputs[0].bits() * inputs[1].bits())]
+            },
+            ComponentKind::Shifter { width } => {
+                let (a, s) = (inputs[0].bits(), inputs[1].bits());
+                let y = match inputs[2].bits() {
+                    0 if s < width as u64 => a << s,
+                    1 if s < width as u64 => a >> s,
+                    2 => {
+                        let sign = inputs[0].bit(width - 1);
+                        let s = s.min(width as u64 - 1);
+                        let fill = if sign {
+                            Value::mask(width) & !(Value::mask(width) >> s)
+                        } else {
+                            0
+                        };
+                        a >> s | fill
+                    },
+                    3 => {
+                        let s = s % width as u64;
+                        a << s | a >> ((width as u64 - s) % width as u64)
+                    },
+                    _ => 0,
+                };
+                vec![Value::new(width, y)]
+            },
+            ComponentKind::Alu { width } => {
+                let (a, b) = (inputs[0].bits(), inputs[1].bits());
+                let (y, c) = match inputs[2].bits() {
+                    0 => (a + b, (a + b) >> width & 1 == 1),
+                    1 => (a.wrapping_sub(b), a < b),
+                    2 => (a & b, false),
+                    3 => (a | b, false),
+                    4 => (a ^ b, false),
+                    5 => (!a, false),
+                    6 => ((a < b) as u64, false),
+                    _ => (b, false),
+                };
+                let y = Value::new(width, y);
+                vec![y, Value::from_bool(c), Value::from_bool(!y.is_high())]
+            },
+            ComponentKind::Register { width } => {
+                let edge = state.rising_edge(inputs[1]);
+                if inputs[3].is_high() {
+                    state.value = 0;
+                } else if edge && inputs[2].is_high() {
+                    state.value = inputs[0].bits();
+                }
+                vec![Value::new(width, state.value)]
+            },
+            ComponentKind::Counter { width } => {
+                let (enable, up) = (inputs[2].is_high(), inputs[4].is_high());
+                let edge = state.rising_edge(inputs[1]);
+                if inputs[5].is_high() {
+                    state.value = 0;
+                } else if edge && inputs[3].is_high() {
+                    state.value = inputs[0].bits();
+                } else if edge && enable {
+                    let next = if up {
+                        state.value.wrapping_add(1)
+                    } else {
+                        state.value.wrapping_sub(1)
+                    };
+                    state.value = next & Value::mask(width);
+                }
+                let last = if up { Value::mask(width) } else { 0 };
+                vec![
+                    Value::new(width, state.value),
+                    Value::from_bool(enable && state.value == last),
+                ]
+            },
+            ComponentKind::ShiftRegister { width, mode } => {
+                let (clock, reset) = match mode {
+                    ShiftMode::Sipo => (inputs[1], inputs[3]),
+                    ShiftMode::Piso => (inputs[2], inputs[4]),
+                    ShiftMode::Universal => (inputs[3], inputs[5]),
+                };
+                let edge = state.rising_edge(clock);
+                let up = |value: u64, bit: Value| value << 1 | bit.bits();
+                if reset.is_high() {
+                    state.value = 0;
+                } else if edge {
+                    state.value = match mode {
+                        ShiftMode::Sipo if inputs[2].is_high() => up(state.value, inputs[0]),
+                        ShiftMode::Sipo => state.value,
+                        ShiftMode::Piso if inputs[3].is_high() => inputs[0].bits(),
+                        ShiftMode::Piso => up(state.value, inputs[1]),
+                        ShiftMode::Universal => match inputs[4].bits() {
+                            1 => up(state.value, inputs[1]),
+                            2 => state.value >> 1 | inputs[2].bits() << (width - 1),
+                            3 => inputs[0].bits(),
+                            _ => state.value,
+                        },
+                    } & Value::mask(width);
+                }
+                match mode {
+                    ShiftMode::Piso => vec![Value::from_bool(state.value >> (width - 1) & 1 == 1)],
+                    _ => vec![Value::new(width, state.value)],
+                }
+            },
+            ComponentKind::Rom { data, .. } => vec![Value::new(data, state.read(inputs[0]))],
+            ComponentKind::Ram {
+                address,
+                data,
+                sync,
+            } => {
+                let write = if sync {
+                    state.rising_edge(inputs[3]) && inputs[2].is_high()
+                } else {
+                    inputs[2].is_high()
+                };
+                if write {
+                    state.write(inputs[0], address, inputs[1]);
+                }
+                vec![Value::new(data, state.read(inputs[0]))]
+            },
+            ComponentKind::Input { width } => vec![Value::new(width, state.value)],
+            ComponentKind::Clock => vec![Value::new(1, state.value)],
+            // displays only have inputs, which are painted straight from the pins
+            ComponentKind::SevenSegment
+            | ComponentKind::HexDisplay
+            | ComponentKind::DotMatrix { .. }
+            | ComponentKind::Probe { .. } => Vec::new(),
+            ComponentKind::Keyboard => {
+                if state.rising_edge(inputs[0]) && !state.text.is_empty() {
+                    state.text.remove(0);
+                }
+                let key = state.text.bytes().next();
+                vec![
+                    Value::new(7, key.unwrap_or(0) as u64),
+                    Value::from_bool(key.is_some()),
+                ]
+            },
+            ComponentKind::Tty { rows, cols } => {
+                let write = state.rising_edge(inputs[1]) && inputs[2].is_high();
+                if inputs[3].is_high() {
+                    state.text.clear();
+                } else if write {
+                    state.type_char(inputs[0].bits() as u8, rows as usize, cols as usize);
+                }
+                Vec::new()
+            },
+            ComponentKind::TriState { width } => {
+                if inputs[1].is_high() {
+                    vec![inputs[0]]
+                } else {
+                    vec![Value::floating(width)]
+                }
+            },
+            ComponentKind::TransmissionGate { width } => {
+                match (inputs[1].is_high(), inputs[2].is_high()) {
+                    (true, false) => vec![inputs[0]],
+                    (false, true) => vec![Value::floating(width)],
+                    _ => vec![Value::error(width)],
+                }
+            },
+        }
+    }
+}
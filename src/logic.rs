@@ -0,0 +1,559 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+};
+
+/// A boolean expression over named one-bit signals
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Xor(Vec<Expr>),
+}
+
+impl Expr {
+    pub fn not(e: Expr) -> Expr {
+        match e {
+            Expr::Const(b) => Expr::Const(!b),
+            Expr::Not(inner) => *inner,
+            e => Expr::Not(Box::new(e)),
+        }
+    }
+
+    /// Joins `terms` with `op`, pulling in the terms of any that are joined by `op` already
+    fn flat(op: fn(Vec<Expr>) -> Expr, terms: Vec<Expr>) -> Expr {
+        let mut flat = Vec::new();
+        for term in terms {
+            match (op(Vec::new()), term) {
+                (Expr::And(_), Expr::And(inner))
+                | (Expr::Or(_), Expr::Or(inner))
+                | (Expr::Xor(_), Expr::Xor(inner)) => flat.extend(inner),
+                (_, term) => flat.push(term),
+            }
+        }
+        if flat.len() == 1 {
+            flat.pop().unwrap()
+        } else {
+            op(flat)
+        }
+    }
+
+    pub fn and(terms: Vec<Expr>) -> Expr {
+        Expr::flat(Expr::And, terms)
+    }
+
+    pub fn or(terms: Vec<Expr>) -> Expr {
+        Expr::flat(Expr::Or, terms)
+    }
+
+    pub fn xor(terms: Vec<Expr>) -> Expr {
+        Expr::flat(Expr::Xor, terms)
+    }
+
+    /// The names of the signals used, each once, in the order they first appear
+    pub fn vars(&self) -> Vec<String> {
+        fn walk(e: &Expr, vars: &mut Vec<String>) {
+            match e {
+                Expr::Const(_) => {},
+                Expr::Var(name) => {
+                    if !vars.contains(name) {
+                        vars.push(name.clone());
+                    }
+                },
+                Expr::Not(inner) => walk(inner, vars),
+                Expr::And(terms) | Expr::Or(terms) | Expr::Xor(terms) => {
+                    terms.iter().for_each(|t| walk(t, vars))
+                },
+            }
+        }
+        let mut vars = Vec::new();
+        walk(self, &mut vars);
+        vars
+    }
+
+    /// The value of the expression with the signals in `vars` set by the bits of `inputs`, the
+    /// first signal in the most significant bit
+    pub fn eval(&self, vars: &[String], inputs: u64) -> bool {
+        match self {
+            Expr::Const(b) => *b,
+            Expr::Var(name) => {
+                let i = vars.iter().position(|v| v == name).unwrap();
+                inputs >> (vars.len() - 1 - i) & 1 == 1
+            },
+            Expr::Not(inner) => !inner.eval(vars, inputs),
+            Expr::And(terms) => terms.iter().all(|t| t.eval(vars, inputs)),
+            Expr::Or(terms) => terms.iter().any(|t| t.eval(vars, inputs)),
+            Expr::Xor(terms) => terms.iter().fold(false, |a, t| a ^ t.eval(vars, inputs)),
+        }
+    }
+
+    /// The input combinations over `vars` that the expression is true for
+    pub fn minterms(&self, vars: &[String]) -> Vec<u64> {
+        (0..1u64 << vars.len())
+            .filter(|&inputs| self.eval(vars, inputs))
+            .collect()
+    }
+
+    /// The smallest sum of products equivalent to this expression
+    pub fn simplified(&self) -> Expr {
+        let vars = self.vars();
+        let cover = minimise(vars.len() as u8, &self.minterms(&vars), &[]);
+        sum_of_products(&cover, &vars)
+    }
+
+    /// How tightly the expression binds, so that it can be told when to bracket its terms
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(_) => 1,
+            Expr::Xor(_) => 2,
+            Expr::And(_) => 3,
+            Expr::Const(_) | Expr::Var(_) | Expr::Not(_) => 4,
+        }
+    }
+
+    /// Typesets the expression for LaTeX maths mode, with bars over negated terms
+    pub fn to_latex(&self) -> String {
+        let term = |t: &Expr| {
+            if t.precedence() < self.precedence() {
+                format!("\\left({}\\right)", t.to_latex())
+            } else {
+                t.to_latex()
+            }
+        };
+        let join = |terms: &[Expr], op: &str| terms.iter().map(term).collect::<Vec<_>>().join(op);
+        match self {
+            Expr::Const(b) => (*b as u8).to_string(),
+            Expr::Var(name) => format!("\\mathrm{{{}}}", name.replace('_', "\\_")),
+            Expr::Not(inner) => format!("\\overline{{{}}}", inner.to_latex()),
+            Expr::And(terms) => join(terms, " \\cdot "),
+            Expr::Or(terms) => join(terms, " + "),
+            Expr::Xor(terms) => join(terms, " \\oplus "),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let term = |t: &Expr, parent: u8| {
+            if t.precedence() < parent {
+                format!("({})", t)
+            } else {
+                t.to_string()
+            }
+        };
+        let join = |terms: &[Expr], op: &str| {
+            terms
+                .iter()
+                .map(|t| term(t, self.precedence()))
+                .collect::<Vec<_>>()
+                .join(op)
+        };
+        match self {
+            Expr::Const(b) => write!(f, "{}", *b as u8),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Not(inner) if inner.precedence() < 4 => write!(f, "!({})", inner),
+            Expr::Not(inner) => write!(f, "!{}", inner),
+            Expr::And(terms) => write!(f, "{}", join(terms, " & ")),
+            Expr::Or(terms) => write!(f, "{}", join(terms, " | ")),
+            Expr::Xor(terms) => write!(f, "{}", join(terms, " ^ ")),
+        }
+    }
+}
+
+/// Reads an expression such as `(a & !b) | c`. Products can also be written with `*`, `.` or by
+/// putting terms side by side, sums with `+`, and negation with `~` or a trailing `'`.
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        at: 0,
+    };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(c) => Err(format!("Unexpected '{}' in \"{}\"", c, text.trim())),
+    }
+}
+
+/// Reads an expression by recursive descent, loosest binding operator first
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    /// The next character that isn't whitespace
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.at).map_or(false, |c| c.is_whitespace()) {
+            self.at += 1;
+        }
+        self.chars.get(self.at).copied()
+    }
+
+    fn eat(&mut self, ops: &[char]) -> bool {
+        match self.peek() {
+            Some(c) if ops.contains(&c) => {
+                self.at += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.xor()?];
+        while self.eat(&['|', '+']) {
+            terms.push(self.xor()?);
+        }
+        Ok(Expr::or(terms))
+    }
+
+    fn xor(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.and()?];
+        while self.eat(&['^']) {
+            terms.push(self.and()?);
+        }
+        Ok(Expr::xor(terms))
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.not()?];
+        // terms side by side are multiplied too
+        while self.eat(&['&', '*', '.'])
+            || self
+                .peek()
+                .map_or(false, |c| c.is_alphanumeric() || "_(!~".contains(c))
+        {
+            terms.push(self.not()?);
+        }
+        Ok(Expr::and(terms))
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat(&['!', '~']) {
+            return Ok(Expr::not(self.not()?));
+        }
+        let mut term = self.term()?;
+        while self.eat(&['\'']) {
+            term = Expr::not(term);
+        }
+        Ok(term)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('(') => {
+                self.at += 1;
+                let inner = self.or()?;
+                if self.eat(&[')']) {
+                    Ok(inner)
+                } else {
+                    Err("Missing ')'".to_owned())
+                }
+            },
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                let start = self.at;
+                while self
+                    .chars
+                    .get(self.at)
+                    .map_or(false, |&c| c.is_alphanumeric() || c == '_')
+                {
+                    self.at += 1;
+                }
+                let name: String = self.chars[start..self.at].iter().collect();
+                match name.as_str() {
+                    "0" => Ok(Expr::Const(false)),
+                    "1" => Ok(Expr::Const(true)),
+                    _ if c.is_ascii_digit() => Err(format!("Bad constant {}", name)),
+                    _ => Ok(Expr::Var(name)),
+                }
+            },
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err("Expression ends too soon".to_owned()),
+        }
+    }
+}
+
+/// A product of some of the variables of a function: those in `mask` are left out, and the rest
+/// must have the values they have in `value`. Variable 0 is the most significant bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Implicant {
+    pub value: u64,
+    pub mask: u64,
+}
+
+impl Implicant {
+    pub fn covers(&self, minterm: u64) -> bool {
+        minterm & !self.mask == self.value
+    }
+
+    fn literals(&self, vars: u8) -> u32 {
+        vars as u32 - self.mask.count_ones()
+    }
+}
+
+/// Every product that can't be made any smaller without covering something outside `terms`,
+/// found by Quine–McCluskey
+pub fn prime_implicants(vars: u8, terms: &[u64]) -> Vec<Implicant> {
+    let mut primes = BTreeSet::new();
+    let mut level: HashSet<Implicant> = terms
+        .iter()
+        .map(|&value| Implicant { value, mask: 0 })
+        .collect();
+    while !level.is_empty() {
+        let mut next = HashSet::new();
+        let mut combined = HashSet::new();
+        for &implicant in level.iter() {
+            for bit in (0..vars).map(|i| 1 << i) {
+                if implicant.mask & bit != 0 || implicant.value & bit != 0 {
+                    continue;
+                }
+                let partner = Implicant {
+                    value: implicant.value | bit,
+                    ..implicant
+                };
+                if level.contains(&partner) {
+                    combined.insert(implicant);
+                    combined.insert(partner);
+                    next.insert(Implicant {
+                        value: implicant.value,
+                        mask: implicant.mask | bit,
+                    });
+                }
+            }
+        }
+        primes.extend(level.difference(&combined).copied());
+        level = next;
+    }
+    primes.into_iter().collect()
+}
+
+/// How many branches the search for the smallest cover may take before settling for what it has
+const COVER_BUDGET: usize = 100_000;
+
+/// The fewest prime implicants, then the fewest literals, that cover `minterms` without covering
+/// anything outside them and `dont_cares`. Very large functions may get a cover that is only
+/// close to the smallest.
+pub fn minimise(vars: u8, minterms: &[u64], dont_cares: &[u64]) -> Vec<Implicant> {
+    let mut terms = minterms.to_vec();
+    terms.extend_from_slice(dont_cares);
+    let primes = prime_implicants(vars, &terms);
+
+    // primes that are the only ones covering some minterm have to be used
+    let mut chosen = Vec::new();
+    let mut left: Vec<u64> = minterms.to_vec();
+    loop {
+        let essential = left.iter().find_map(|&m| {
+            let mut covering = primes.iter().filter(|p| p.covers(m));
+            match (covering.next(), covering.next()) {
+                (Some(&p), None) => Some(p),
+                _ => None,
+            }
+        });
+        match essential {
+            Some(p) => {
+                chosen.push(p);
+                left.retain(|&m| !p.covers(m));
+            },
+            None => break,
+        }
+    }
+
+    let candidates: Vec<_> = primes
+        .into_iter()
+        .filter(|p| !chosen.contains(p) && left.iter().any(|&m| p.covers(m)))
+        .collect();
+    let mut search = Search {
+        vars,
+        candidates: &candidates,
+        best: None,
+        budget: COVER_BUDGET,
+    };
+    search.cover(&left, &mut Vec::new());
+    chosen.extend(search.best.unwrap_or_default());
+    chosen.sort();
+    chosen
+}
+
+/// A branch and bound search for the cheapest set of candidates covering some minterms
+struct Search<'a> {
+    vars: u8,
+    candidates: &'a [Implicant],
+    best: Option<Vec<Implicant>>,
+    budget: usize,
+}
+
+impl Search<'_> {
+    fn cost(&self, cover: &[Implicant]) -> (usize, u32) {
+        let literals = cover.iter().map(|p| p.literals(self.vars)).sum();
+        (cover.len(), literals)
+    }
+
+    fn cover(&mut self, left: &[u64], chosen: &mut Vec<Implicant>) {
+        if left.is_empty() {
+            let better = match &self.best {
+                Some(best) => self.cost(chosen) < self.cost(best),
+                None => true,
+            };
+            if better {
+                self.best = Some(chosen.clone());
+            }
+            return;
+        }
+        // anything left needs at least one more implicant
+        if let Some(best) = &self.best {
+            if chosen.len() + 1 > best.len() {
+                return;
+            }
+        }
+        if self.budget == 0 {
+            if self.best.is_some() {
+                return;
+            }
+        } else {
+            self.budget -= 1;
+        }
+
+        // branch on the minterm with the fewest ways of covering it, trying the biggest first
+        let m = *left
+            .iter()
+            .min_by_key(|&&m| self.candidates.iter().filter(|p| p.covers(m)).count())
+            .unwrap();
+        let mut options: Vec<_> = self
+            .candidates
+            .iter()
+            .filter(|p| p.covers(m))
+            .copied()
+            .collect();
+        options.sort_by_key(|p| std::cmp::Reverse(p.mask.count_ones()));
+        for p in options {
+            let rest: Vec<_> = left.iter().copied().filter(|&m| !p.covers(m)).collect();
+            chosen.push(p);
+            self.cover(&rest, chosen);
+            chosen.pop();
+            if self.budget == 0 && self.best.is_some() {
+                return;
+            }
+        }
+    }
+}
+
+/// The expression made by OR-ing the products of `cover`, over the variables `vars`
+pub fn sum_of_products(cover: &[Implicant], vars: &[String]) -> Expr {
+    let n = vars.len();
+    let products = cover
+        .iter()
+        .map(|p| {
+            let literals = (0..n)
+                .filter(|i| p.mask >> (n - 1 - i) & 1 == 0)
+                .map(|i| {
+                    let var = Expr::Var(vars[i].clone());
+                    if p.value >> (n - 1 - i) & 1 == 1 {
+                        var
+                    } else {
+                        Expr::not(var)
+                    }
+                })
+                .collect::<Vec<_>>();
+            if literals.is_empty() {
+                Expr::Const(true)
+            } else {
+                Expr::and(literals)
+            }
+        })
+        .collect::<Vec<_>>();
+    if products.is_empty() {
+        Expr::Const(false)
+    } else {
+        Expr::or(products)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(names: &str) -> Vec<String> {
+        names.chars().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_every_notation() {
+        let expr = parse("a b' + ~(c . d) ^ 1").unwrap();
+        assert_eq!(expr.to_string(), "a & !b | !(c & d) ^ 1");
+        assert_eq!(parse(&expr.to_string()), Ok(expr));
+        assert!(parse("a &").is_err());
+        assert!(parse("(a | b").is_err());
+        assert!(parse("2a").is_err());
+    }
+
+    #[test]
+    fn prime_implicants_of_majority() {
+        let primes = prime_implicants(3, &[3, 5, 6, 7]);
+        let expected = vec![
+            Implicant { value: 3, mask: 4 },
+            Implicant { value: 5, mask: 2 },
+            Implicant { value: 6, mask: 1 },
+        ];
+        assert_eq!(primes, expected);
+        assert_eq!(
+            sum_of_products(&primes, &vars("abc")).to_string(),
+            "b & c | a & c | a & b"
+        );
+    }
+
+    #[test]
+    fn minimises_a_cyclic_cover() {
+        // every minterm is covered by two primes and none are essential
+        let minterms = [0, 1, 2, 5, 6, 7];
+        assert_eq!(prime_implicants(3, &minterms).len(), 6);
+        let cover = minimise(3, &minterms, &[]);
+        assert_eq!(cover.len(), 3);
+        assert!(cover.iter().all(|p| p.literals(3) == 2));
+        let expr = sum_of_products(&cover, &vars("abc"));
+        assert_eq!(expr.minterms(&vars("abc")), minterms);
+    }
+
+    #[test]
+    fn uses_dont_cares() {
+        let cover = minimise(2, &[1], &[3]);
+        assert_eq!(
+            sum_of_products(&cover, &vars("ab")),
+            Expr::Var("b".to_owned())
+        );
+        assert_eq!(
+            sum_of_products(&minimise(2, &[], &[3]), &vars("ab")),
+            Expr::Const(false)
+        );
+        assert_eq!(
+            sum_of_products(&minimise(2, &[0, 1, 2], &[3]), &vars("ab")),
+            Expr::Const(true)
+        );
+    }
+
+    #[test]
+    fn simplifies_to_an_equivalent() {
+        assert_eq!(
+            parse("a & b | a & !b").unwrap().simplified(),
+            Expr::Var("a".to_owned())
+        );
+        for text in [
+            "a ^ b ^ c",
+            "!(a | b) & (c | !d)",
+            "a b c d + a' b' c' d' + a b' c d'",
+        ]
+        .iter()
+        {
+            let expr = parse(text).unwrap();
+            let vars = expr.vars();
+            assert_eq!(
+                expr.simplified().minterms(&vars),
+                expr.minterms(&vars),
+                "{}",
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,1231 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::Path,
+    rc::Rc,
+};
+
+use druid::{im, FileSpec, Selector};
+
+use crate::{
+    canvas::Coords,
+    component::{ComponentInstance, ComponentState, ComponentType, Orientation},
+    import::{Assembly, Import},
+    kind::{ComponentKind, PinType, MAX_WIDTH},
+    place::{self, Ports},
+    sim::{Joins, Netlist},
+    value::Radix,
+    wire::{WireSegment, WireState},
+};
+
+pub const IMPORT_LOGISIM: Selector<druid::FileInfo> = Selector::new("logicism/import-logisim");
+
+pub const LOGISIM: FileSpec = FileSpec::new("Logisim circuit", &["circ"]);
+
+/// The space left around each circuit that is laid out, in cells
+const MARGIN: isize = 4;
+
+/// Pixels to a cell of the grid, which puts cells on Logisim's grid lines
+const SCALE: isize = 10;
+
+/// A point in Logisim's coordinates, which are in pixels with grid lines every ten
+type Point = (isize, isize);
+
+fn point(text: &str) -> Result<Point, String> {
+    let error = || format!("\"{}\" isn't a point", text);
+    let inner = text.trim().trim_start_matches('(').trim_end_matches(')');
+    let (x, y) = inner.split_once(',').ok_or_else(error)?;
+    let parse = |n: &str| n.trim().parse::<isize>().map_err(|_| error());
+    Ok((parse(x)?, parse(y)?))
+}
+
+/// The cell of the grid nearest a point
+fn cell((x, y): Point) -> Coords {
+    let round = |n: isize| (n as f64 / SCALE as f64).round() as isize;
+    Coords::new(round(x), round(y))
+}
+
+fn facing(text: Option<&str>) -> Orientation {
+    match text {
+        Some("north") => Orientation::North,
+        Some("south") => Orientation::South,
+        Some("west") => Orientation::West,
+        _ => Orientation::East,
+    }
+}
+
+fn reverse(orientation: Orientation) -> Orientation {
+    match orientation {
+        Orientation::North => Orientation::South,
+        Orientation::East => Orientation::West,
+        Orientation::South => Orientation::North,
+        Orientation::West => Orientation::East,
+    }
+}
+
+/// An offset worked out for something facing east, turned to match something facing `facing`
+fn turn((u, v): Point, facing: Orientation) -> Point {
+    match facing {
+        Orientation::East => (u, v),
+        Orientation::South => (-v, u),
+        Orientation::West => (-u, -v),
+        Orientation::North => (v, -u),
+    }
+}
+
+/// An offset for something facing `facing`, turned back to what it would be facing east
+fn unturn(offset: Point, facing: Orientation) -> Point {
+    match facing {
+        Orientation::North => turn(offset, Orientation::South),
+        Orientation::South => turn(offset, Orientation::North),
+        _ => turn(offset, facing),
+    }
+}
+
+/// The attributes of an element, which Logisim gives as `<a name=".." val=".."/>` children
+fn attributes(node: roxmltree::Node) -> BTreeMap<String, String> {
+    node.children()
+        .filter(|child| child.has_tag_name("a"))
+        .filter_map(|a| {
+            let value = a.attribute("val").or_else(|| a.text()).unwrap_or("");
+            Some((a.attribute("name")?.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+/// A component in a Logisim circuit
+struct Comp {
+    /// The library it comes from, which is missing for the project's own circuits
+    lib: Option<String>,
+    name: String,
+    loc: Point,
+    attrs: BTreeMap<String, String>,
+}
+
+impl Comp {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(String::as_str)
+    }
+
+    fn facing(&self) -> Orientation {
+        facing(self.attr("facing"))
+    }
+
+    fn width(&self) -> u32 {
+        self.attr("width").and_then(|w| w.parse().ok()).unwrap_or(1)
+    }
+
+    fn label(&self) -> &str {
+        self.attr("label").unwrap_or("")
+    }
+}
+
+/// A custom look for a subcircuit, drawn in Logisim's appearance editor
+struct Appearance {
+    /// The centre of the anchor, which is where an instance is placed, and the way it faces
+    anchor: Point,
+    facing: Orientation,
+    /// The location of each pin in the subcircuit, with the centre of its port
+    ports: Vec<(Point, Point)>,
+}
+
+impl Appearance {
+    fn parse(node: roxmltree::Node) -> Result<Option<Self>, String> {
+        let centre = |shape: roxmltree::Node| -> Result<Point, String> {
+            let get = |name| {
+                shape
+                    .attribute(name)
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .ok_or_else(|| format!("A {} has no {}", shape.tag_name().name(), name))
+            };
+            let (x, y) = (get("x")?, get("y")?);
+            let (width, height) = (get("width")?, get("height")?);
+            Ok((
+                (x + width / 2.0).round() as isize,
+                (y + height / 2.0).round() as isize,
+            ))
+        };
+        let anchor = match node.children().find(|n| n.has_tag_name("circ-anchor")) {
+            Some(anchor) => anchor,
+            None => return Ok(None),
+        };
+        let mut ports = Vec::new();
+        for port in node.children().filter(|n| n.has_tag_name("circ-port")) {
+            ports.push((point(port.attribute("pin").unwrap_or(""))?, centre(port)?));
+        }
+        Ok(Some(Appearance {
+            anchor: centre(anchor)?,
+            facing: facing(anchor.attribute("facing")),
+            ports,
+        }))
+    }
+}
+
+struct Circuit {
+    attrs: BTreeMap<String, String>,
+    wires: Vec<(Point, Point)>,
+    comps: Vec<Comp>,
+    appearance: Option<Appearance>,
+}
+
+impl Circuit {
+    fn parse(node: roxmltree::Node) -> Result<Self, String> {
+        let mut circuit = Circuit {
+            attrs: attributes(node),
+            wires: Vec::new(),
+            comps: Vec::new(),
+            appearance: None,
+        };
+        for child in node.children() {
+            let at = |name| point(child.attribute(name).unwrap_or(""));
+            match child.tag_name().name() {
+                "wire" => circuit.wires.push((at("from")?, at("to")?)),
+                "comp" => circuit.comps.push(Comp {
+                    lib: child.attribute("lib").map(str::to_owned),
+                    name: child.attribute("name").unwrap_or("").to_owned(),
+                    loc: at("loc")?,
+                    attrs: attributes(child),
+                }),
+                "appear" => circuit.appearance = Appearance::parse(child)?,
+                _ => {},
+            }
+        }
+        Ok(circuit)
+    }
+
+    /// The corners of a box around everything in the circuit, with room to spare, in cells
+    fn extent(&self) -> (Coords, Coords) {
+        let points: Vec<Coords> = self
+            .wires
+            .iter()
+            .flat_map(|&(a, b)| vec![a, b])
+            .chain(self.comps.iter().map(|comp| comp.loc))
+            .map(cell)
+            .collect();
+        let min = |f: fn(&Coords) -> isize| points.iter().map(f).min().unwrap_or(0);
+        let max = |f: fn(&Coords) -> isize| points.iter().map(f).max().unwrap_or(0);
+        (
+            Coords::new(min(|c| c.x) - MARGIN, min(|c| c.y) - MARGIN),
+            Coords::new(max(|c| c.x) + MARGIN, max(|c| c.y) + MARGIN),
+        )
+    }
+
+    /// Whether a wire runs through `point`, or another component is placed there
+    fn is_wired(&self, point: Point) -> bool {
+        let at = |(x, y): Point| Coords::new(x, y);
+        self.wires
+            .iter()
+            .any(|&(a, b)| WireSegment::runs_through(at(a), at(b), at(point)))
+            || self.comps.iter().any(|comp| comp.loc == point)
+    }
+
+    /// Whether something drives the wires through `point`: an input pin, or the output of a gate,
+    /// constant or clock
+    fn is_driven(&self, point: Point) -> bool {
+        let at = |(x, y): Point| Coords::new(x, y);
+        let mut joins = Joins::default();
+        for &(a, b) in self.wires.iter() {
+            joins.join(at(a), at(b));
+        }
+        let net = joins.find(at(point));
+        self.comps.iter().any(|comp| {
+            let drives = match comp.name.as_str() {
+                "Pin" => comp.attr("output") != Some("true") && comp.attr("type") != Some("output"),
+                "Constant" | "Clock" => true,
+                name => name.ends_with(" Gate"),
+            };
+            drives && comp.lib.is_some() && joins.find(at(comp.loc)) == net
+        })
+    }
+
+    /// Where each port of an instance of this circuit is, from the instance's location as if it
+    /// faced east, along with the location of the pin inside that it stands for
+    fn ports(&self) -> Result<Vec<(Point, Point)>, String> {
+        if let Some(appearance) = &self.appearance {
+            let (ax, ay) = appearance.anchor;
+            return Ok(appearance
+                .ports
+                .iter()
+                .map(|&(pin, (x, y))| (pin, unturn((x - ax, y - ay), appearance.facing)))
+                .collect());
+        }
+        match self.attrs.get("appearance").map(String::as_str) {
+            None | Some("classic") => Ok(self.classic_ports()),
+            Some(_) => Err(
+                "its pins are placed by Logisim-evolution's own appearance, so give \
+                            it a custom appearance"
+                    .to_owned(),
+            ),
+        }
+    }
+
+    /// The ports of the box that Logisim draws for a subcircuit by default, with each pin on
+    /// the side opposite the way it faces, in order along the side
+    fn classic_ports(&self) -> Vec<(Point, Point)> {
+        let (mut north, mut south, mut east, mut west) = (vec![], vec![], vec![], vec![]);
+        for pin in self
+            .comps
+            .iter()
+            .filter(|c| c.lib.is_some() && c.name == "Pin")
+        {
+            match reverse(pin.facing()) {
+                Orientation::North => north.push(pin.loc),
+                Orientation::South => south.push(pin.loc),
+                Orientation::East => east.push(pin.loc),
+                Orientation::West => west.push(pin.loc),
+            }
+        }
+        for side in [&mut east, &mut west].iter_mut() {
+            side.sort_by_key(|&(x, y)| (y, x));
+        }
+        north.sort();
+        south.sort();
+
+        let count = |side: &Vec<Point>| side.len() as isize;
+        let (n, s, e, w) = (count(&north), count(&south), count(&east), count(&west));
+        let (vertical, horizontal) = (n.max(s), e.max(w));
+        let dimension = |along: isize, across: isize| match along {
+            _ if along < 3 => 30,
+            _ if across == 0 => 10 * along,
+            _ => 10 * along + 10,
+        };
+        let offset = |facing: isize, opposite: isize, across: isize| {
+            let most = facing.max(opposite);
+            let start = match most {
+                0 | 1 if across == 0 => 15,
+                0 | 1 | 2 => 10,
+                _ if across == 0 => 5,
+                _ => 10,
+            };
+            start + 10 * ((most - facing) / 2)
+        };
+        let (width, height) = (
+            dimension(vertical, horizontal),
+            dimension(horizontal, vertical),
+        );
+        let (off_n, off_s) = (offset(n, s, horizontal), offset(s, n, horizontal));
+        let (off_e, off_w) = (offset(e, w, vertical), offset(w, e, vertical));
+        let (ax, ay) = if e > 0 {
+            (width, off_e)
+        } else if n > 0 {
+            (off_n, 0)
+        } else if w > 0 {
+            (0, off_w)
+        } else if s > 0 {
+            (off_s, height)
+        } else {
+            (0, 0)
+        };
+
+        let mut ports = Vec::new();
+        let mut side = |pins: Vec<Point>, (x, y): Point, (dx, dy): Point| {
+            for (i, pin) in pins.into_iter().enumerate() {
+                let i = i as isize;
+                ports.push((pin, (x + dx * i - ax, y + dy * i - ay)));
+            }
+        };
+        side(west, (0, off_w), (0, 10));
+        side(east, (width, off_e), (0, 10));
+        side(north, (off_n, 0), (10, 0));
+        side(south, (off_s, height), (10, 0));
+        ports
+    }
+}
+
+/// Where the inputs of a Logisim gate are, from its output as if it faced east, when its body
+/// is `axis` long
+fn gate_inputs(size: isize, inputs: isize, axis: isize) -> Vec<Point> {
+    let (start, step, lower) = match inputs {
+        _ if inputs <= 3 && size < 40 => (-5, 10, 10),
+        _ if inputs <= 3 && (size < 60 || inputs <= 2) => (-10, 20, 20),
+        _ if inputs <= 3 => (-15, 30, 30),
+        4 if size >= 60 => (-5, 20, 0),
+        _ => (-5, 10, 10),
+    };
+    (0..inputs)
+        .map(|i| {
+            let dy = if inputs % 2 == 1 {
+                start * (inputs - 1) + step * i
+            } else if i >= inputs / 2 {
+                start * inputs + step * i + lower
+            } else {
+                start * inputs + step * i
+            };
+            (-axis, dy)
+        })
+        .collect()
+}
+
+struct Importer<'a> {
+    circuits: &'a BTreeMap<String, Circuit>,
+    /// Whether the file is from the original Logisim, where gates have five inputs unless they
+    /// say otherwise
+    legacy: bool,
+    assembly: Assembly,
+    /// Where subcircuits are laid out: the left edge of their column and the top of the space
+    /// still free in it
+    column: isize,
+    next_row: isize,
+    /// The circuits being imported, innermost last, to catch one that contains itself
+    stack: Vec<String>,
+    instances: usize,
+}
+
+impl<'a> Importer<'a> {
+    /// Brings across a circuit with the top left of its coordinates at `origin`. Only the main
+    /// circuit gets components for its pins, since a subcircuit's are wired up to its instance.
+    fn circuit(&mut self, name: &str, origin: Coords, main: bool) -> Result<(), String> {
+        if self.stack.iter().any(|outer| outer == name) {
+            return Err(format!("The circuit {} contains itself", name));
+        }
+        self.stack.push(name.to_owned());
+        let circuits = self.circuits;
+        let circuit = &circuits[name];
+        let wires: Vec<_> = circuit
+            .wires
+            .iter()
+            .map(|&(a, b)| (origin + cell(a), origin + cell(b)))
+            .collect();
+        self.assembly.add_wires(&wires);
+
+        let mut tunnels: BTreeMap<&str, Coords> = BTreeMap::new();
+        for comp in circuit.comps.iter() {
+            let loc = origin + cell(comp.loc);
+            if comp.lib.is_none() && circuits.contains_key(&comp.name) {
+                self.subcircuit(comp, loc)?;
+                continue;
+            }
+            match comp.name.as_str() {
+                "Pin" if main => self.pin(comp, loc),
+                "Pin" => {},
+                "AND Gate" => self.gate(circuit, comp, origin, ComponentKind::And),
+                "OR Gate" => self.gate(circuit, comp, origin, ComponentKind::Or),
+                "NAND Gate" => self.gate(circuit, comp, origin, ComponentKind::Nand),
+                "NOR Gate" => self.gate(circuit, comp, origin, ComponentKind::Nor),
+                "XOR Gate" => self.gate(circuit, comp, origin, ComponentKind::Xor),
+                "NOT Gate" => self.not_gate(circuit, comp, origin),
+                "Splitter" => self.splitter(circuit, comp, origin),
+                "Constant" => {
+                    let value = comp.attr("value").unwrap_or("0x1");
+                    let value = match value.strip_prefix("0x") {
+                        Some(hex) => u64::from_str_radix(hex, 16),
+                        None => value.parse(),
+                    };
+                    match (value, self.width(comp)) {
+                        (Ok(value), Some(width)) => {
+                            let kind = ComponentKind::Constant { width, value };
+                            self.assembly.place(kind, comp.facing(), 0, loc);
+                        },
+                        _ => self.assembly.leave_out("Constant wider than 32 bits"),
+                    }
+                },
+                "Clock" => {
+                    let index = self
+                        .assembly
+                        .place(ComponentKind::Clock, comp.facing(), 0, loc);
+                    self.assembly.label(index, comp.label());
+                },
+                "Probe" => {
+                    let radix = match comp.attr("radix") {
+                        Some("2") => Radix::Binary,
+                        Some("10signed") | Some("10unsigned") => Radix::Decimal,
+                        _ => Radix::Hex,
+                    };
+                    let kind = ComponentKind::Probe { radix };
+                    let index = self.assembly.place(kind, reverse(comp.facing()), 0, loc);
+                    self.assembly.label(index, comp.label());
+                },
+                "Tunnel" => match tunnels.get(comp.label()) {
+                    Some(&first) => self.assembly.link(first, loc),
+                    None => {
+                        tunnels.insert(comp.label(), loc);
+                    },
+                },
+                // notes on the drawing
+                "Text" => {},
+                other => self.assembly.leave_out(other),
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// The width of a component, if it isn't too wide
+    fn width(&self, comp: &Comp) -> Option<u8> {
+        match comp.width() {
+            width if width <= MAX_WIDTH as u32 => Some(width as u8),
+            _ => None,
+        }
+    }
+
+    fn pin(&mut self, comp: &Comp, loc: Coords) {
+        let width = match self.width(comp) {
+            Some(width) => width,
+            None => return self.assembly.leave_out("Pin wider than 32 bits"),
+        };
+        let is_output = comp.attr("output") == Some("true") || comp.attr("type") == Some("output");
+        let index = if is_output {
+            let kind = ComponentKind::Probe { radix: Radix::Hex };
+            self.assembly.place(kind, reverse(comp.facing()), 0, loc)
+        } else {
+            self.assembly
+                .place(ComponentKind::Input { width }, comp.facing(), 0, loc)
+        };
+        self.assembly.label(index, comp.label());
+    }
+
+    /// Brings across a gate, which only has two inputs here, as long as no more than two of
+    /// its inputs are wired up
+    fn gate(&mut self, circuit: &Circuit, comp: &Comp, origin: Coords, kind: ComponentKind) {
+        if comp.width() != 1 {
+            return self
+                .assembly
+                .leave_out(format!("{} wider than one bit", comp.name));
+        }
+        let negated = comp
+            .attrs
+            .iter()
+            .any(|(name, value)| name.starts_with("negate") && value == "true");
+        if negated {
+            return self
+                .assembly
+                .leave_out(format!("{} with inverted inputs", comp.name));
+        }
+        let size = match comp.attr("size") {
+            Some("narrow") | Some("30") => 30,
+            Some("wide") | Some("70") => 70,
+            _ => 50,
+        };
+        let inputs = comp
+            .attr("inputs")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(if self.legacy { 5 } else { 2 });
+        let axis = match kind {
+            ComponentKind::Xor => size + 10,
+            ComponentKind::Nand | ComponentKind::Nor => size + 10,
+            _ => size,
+        };
+        let facing = comp.facing();
+        let wired: Vec<Point> = gate_inputs(size, inputs, axis)
+            .into_iter()
+            .map(|offset| {
+                let (dx, dy) = turn(offset, facing);
+                (comp.loc.0 + dx, comp.loc.1 + dy)
+            })
+            .filter(|&input| circuit.is_wired(input))
+            .collect();
+        let inputs = match wired[..] {
+            [a, b] => vec![a, b],
+            // the same input twice passes it through, except for XOR
+            [a] if kind != ComponentKind::Xor => vec![a, a],
+            [] => vec![],
+            [_] => return self.assembly.leave_out("XOR Gate with one input"),
+            _ => {
+                return self
+                    .assembly
+                    .leave_out(format!("{} with more than two inputs", comp.name))
+            },
+        };
+        let index = self
+            .assembly
+            .place(kind, facing, 2, origin + cell(comp.loc));
+        for (pin, input) in inputs.into_iter().enumerate() {
+            self.assembly.connect(index, pin, origin + cell(input));
+        }
+    }
+
+    fn not_gate(&mut self, circuit: &Circuit, comp: &Comp, origin: Coords) {
+        if comp.width() != 1 {
+            return self.assembly.leave_out("NOT Gate wider than one bit");
+        }
+        let size = match comp.attr("size") {
+            Some("narrow") | Some("20") => 20,
+            _ => 30,
+        };
+        let facing = comp.facing();
+        let (dx, dy) = turn((-size, 0), facing);
+        let input = (comp.loc.0 + dx, comp.loc.1 + dy);
+        let index = self
+            .assembly
+            .place(ComponentKind::Not, facing, 1, origin + cell(comp.loc));
+        if circuit.is_wired(input) {
+            self.assembly.connect(index, 0, origin + cell(input));
+        }
+    }
+
+    /// Brings across a splitter that gives each bit an end of its own, in order, as a split if
+    /// its combined end is driven and as a join if one of the other ends is
+    fn splitter(&mut self, circuit: &Circuit, comp: &Comp, origin: Coords) {
+        let count = |name| comp.attr(name).and_then(|n| n.parse().ok()).unwrap_or(2);
+        let incoming: u32 = count("incoming");
+        if count("fanout") != incoming {
+            return self
+                .assembly
+                .leave_out("Splitter with more than one bit on an end");
+        }
+        let width = match incoming {
+            n if (1..=MAX_WIDTH as u32).contains(&n) => n as u8,
+            _ => return self.assembly.leave_out("Splitter wider than 32 bits"),
+        };
+        let in_order = comp
+            .attrs
+            .iter()
+            .all(|(name, value)| name.strip_prefix("bit").map_or(true, |bit| bit == value));
+        let plain = matches!(comp.attr("appear"), None | Some("left"))
+            && matches!(comp.attr("spacing"), None | Some("1"));
+        if !in_order || !plain {
+            return self
+                .assembly
+                .leave_out("Splitter with bits out of order or ends moved");
+        }
+        // the ends fan out a row apart below the combined end, two cells along
+        let facing = comp.facing();
+        let ends: Vec<Point> = (0..width as isize)
+            .map(|bit| {
+                let (dx, dy) = turn((2 * SCALE, SCALE * (bit + 1)), facing);
+                (comp.loc.0 + dx, comp.loc.1 + dy)
+            })
+            .collect();
+        let split = circuit.is_driven(comp.loc) || !ends.iter().any(|&end| circuit.is_driven(end));
+        let loc = origin + cell(comp.loc);
+        if split {
+            // a split facing the same way has its bits a row apart too, so they land on the ends
+            let kind = ComponentKind::Split { width };
+            let index = self.assembly.place(kind, facing, 1, origin + cell(ends[0]));
+            self.assembly.connect(index, 0, loc);
+            for (bit, &end) in ends.iter().enumerate().skip(1) {
+                self.assembly.connect(index, bit + 1, origin + cell(end));
+            }
+        } else {
+            let kind = ComponentKind::Join { width };
+            let index = self
+                .assembly
+                .place(kind, reverse(facing), width as usize, loc);
+            for (bit, &end) in ends.iter().enumerate() {
+                self.assembly.connect(index, bit, origin + cell(end));
+            }
+        }
+    }
+
+    /// Lays out the circuit an instance stands for in the next free space to the right, and
+    /// wires its pins to where the instance's ports were
+    fn subcircuit(&mut self, comp: &Comp, loc: Coords) -> Result<(), String> {
+        let circuits = self.circuits;
+        let sub = &circuits[&comp.name];
+        let ports = match sub.ports() {
+            Ok(ports) => ports,
+            Err(reason) => {
+                self.assembly
+                    .leave_out(format!("{}, as {}", comp.name, reason));
+                return Ok(());
+            },
+        };
+        let (min, max) = sub.extent();
+        let origin = Coords::new(self.column - min.x, self.next_row - min.y);
+        self.next_row += max.y - min.y;
+        self.circuit(&comp.name, origin, false)?;
+        self.instances += 1;
+
+        let facing = comp.facing();
+        for (pin, offset) in ports {
+            self.assembly
+                .link(loc + cell(turn(offset, facing)), origin + cell(pin));
+        }
+        Ok(())
+    }
+}
+
+/// Brings across the main circuit of a Logisim or Logisim-evolution project, with any
+/// subcircuits in it laid out alongside
+pub fn import(text: &str) -> Result<Import, String> {
+    let document = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+    let project = document.root_element();
+    if !project.has_tag_name("project") {
+        return Err("This isn't a Logisim project".to_owned());
+    }
+
+    let mut circuits = BTreeMap::new();
+    let mut first = None;
+    for node in project.children().filter(|n| n.has_tag_name("circuit")) {
+        let name = node.attribute("name").unwrap_or("").to_owned();
+        first.get_or_insert_with(|| name.clone());
+        circuits.insert(name, Circuit::parse(node)?);
+    }
+    let main = project
+        .children()
+        .find(|n| n.has_tag_name("main"))
+        .and_then(|n| n.attribute("name"))
+        .map(str::to_owned)
+        .or(first)
+        .ok_or_else(|| "The project has no circuits".to_owned())?;
+    let (min, max) = circuits
+        .get(&main)
+        .ok_or_else(|| format!("The main circuit {} is missing", main))?
+        .extent();
+
+    let mut importer = Importer {
+        circuits: &circuits,
+        legacy: project
+            .attribute("source")
+            .map_or(false, |version| version.starts_with("2.")),
+        assembly: Assembly::new(),
+        column: max.x,
+        next_row: min.y,
+        stack: Vec::new(),
+        instances: 0,
+    };
+    importer.circuit(&main, Coords::new(0, 0), true)?;
+
+    let mut assembly = importer.assembly;
+    assembly.note(format!("Imported the circuit {} from Logisim.", main));
+    match importer.instances {
+        0 => {},
+        1 => assembly.note("Its subcircuit is laid out to the right, wired to where it was."),
+        n => assembly.note(format!(
+            "Its {} subcircuits are laid out to the right, each wired to where it was.",
+            n
+        )),
+    }
+    Ok(assembly.finish())
+}
+
+pub fn read_logisim(path: &Path) -> Result<Import, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    import(&text)
+}
+
+pub const EXPORT_LOGISIM: Selector<druid::FileInfo> = Selector::new("logicism/export-logisim");
+
+fn px(coords: Coords) -> Point {
+    (coords.x * SCALE, coords.y * SCALE)
+}
+
+fn facing_name(orientation: Orientation) -> &'static str {
+    match orientation {
+        Orientation::North => "north",
+        Orientation::East => "east",
+        Orientation::South => "south",
+        Orientation::West => "west",
+    }
+}
+
+/// Text made safe to go in an XML attribute or element
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Whether components of this kind are written out as a circuit of their own, built from gates,
+/// since Logisim has nothing with the same pins
+fn has_circuit(kind: ComponentKind) -> bool {
+    matches!(
+        kind,
+        ComponentKind::Split { .. } | ComponentKind::Join { .. }
+    ) || kind.expand().is_some()
+}
+
+fn circuit_name(kind: ComponentKind) -> String {
+    match kind {
+        ComponentKind::Mux { select, width } | ComponentKind::Demux { select, width } => {
+            format!("{}_s{}_w{}", kind.name(), select, width)
+        },
+        ComponentKind::Decoder { select } | ComponentKind::PriorityEncoder { select } => {
+            format!("{}_s{}", kind.name(), select)
+        },
+        ComponentKind::Split { width }
+        | ComponentKind::Join { width }
+        | ComponentKind::Adder { width }
+        | ComponentKind::Subtractor { width }
+        | ComponentKind::Comparator { width }
+        | ComponentKind::Multiplier { width }
+        | ComponentKind::Shifter { width }
+        | ComponentKind::Alu { width } => format!("{}_w{}", kind.name(), width),
+        _ => kind.name().to_owned(),
+    }
+}
+
+/// A circuit being written out for Logisim
+#[derive(Default)]
+struct Sheet {
+    /// Each component as a `<comp>` element
+    comps: Vec<String>,
+    wires: Vec<(Point, Point)>,
+    /// Everywhere that something connects, where a wire running through has to be cut
+    points: BTreeSet<Point>,
+    /// The kinds of component written out as circuits of their own, by circuit name
+    circuits: BTreeMap<String, ComponentKind>,
+}
+
+impl Sheet {
+    fn comp(&mut self, lib: Option<&str>, name: &str, loc: Point, attrs: &[(&str, &str)]) {
+        let lib = lib.map_or(String::new(), |lib| format!(" lib=\"{}\"", lib));
+        let mut comp = format!(
+            "    <comp{} loc=\"({},{})\" name=\"{}\">\n",
+            lib,
+            loc.0,
+            loc.1,
+            escape(name)
+        );
+        for (name, value) in attrs {
+            writeln!(
+                comp,
+                "      <a name=\"{}\" val=\"{}\"/>",
+                name,
+                escape(value)
+            )
+            .unwrap();
+        }
+        comp.push_str("    </comp>\n");
+        self.comps.push(comp);
+        self.points.insert(loc);
+    }
+
+    fn wire(&mut self, a: Point, b: Point) {
+        if a != b {
+            self.wires.push((a, b));
+            self.points.insert(a);
+            self.points.insert(b);
+        }
+    }
+
+    /// Adds the Logisim component most like `instance`, with its pins where the instance's are, or
+    /// returns false if Logisim has nothing like it. `width` is for a probe, which takes the
+    /// width of whatever it is on.
+    fn component(&mut self, instance: &ComponentInstance, label: &str, width: u8) -> bool {
+        let kind = instance.ty.kind;
+        let facing = facing_name(instance.orientation);
+        let pin = |i| px(instance.pin_coords(i));
+        match kind {
+            ComponentKind::And | ComponentKind::Or => {
+                let name = format!("{} Gate", kind.name());
+                let attrs = [("facing", facing), ("size", "30"), ("inputs", "2")];
+                self.comp(Some("1"), &name, pin(2), &attrs);
+            },
+            // the bubble or the extra curve makes these a cell longer, so the output sticks out
+            ComponentKind::Nand | ComponentKind::Nor | ComponentKind::Xor => {
+                let name = format!("{} Gate", kind.name());
+                let attrs = [("facing", facing), ("size", "30"), ("inputs", "2")];
+                let (dx, dy) = turn((SCALE, 0), instance.orientation);
+                let output = (pin(2).0 + dx, pin(2).1 + dy);
+                self.comp(Some("1"), &name, output, &attrs);
+                self.wire(pin(2), output);
+            },
+            ComponentKind::Not => {
+                let attrs = [("facing", facing), ("size", "30")];
+                self.comp(Some("1"), "NOT Gate", pin(1), &attrs);
+            },
+            ComponentKind::Constant { width, value } => {
+                let (width, value) = (width.to_string(), format!("{:#x}", value));
+                let attrs = [("facing", facing), ("width", &width), ("value", &value)];
+                self.comp(Some("0"), "Constant", pin(0), &attrs);
+            },
+            ComponentKind::Input { width } => {
+                let width = width.to_string();
+                let attrs = [("facing", facing), ("width", &width), ("label", label)];
+                self.comp(Some("0"), "Pin", pin(0), &attrs);
+            },
+            ComponentKind::Clock => {
+                let attrs = [("facing", facing), ("label", label)];
+                self.comp(Some("0"), "Clock", pin(0), &attrs);
+            },
+            ComponentKind::Probe { .. } => {
+                let width = width.to_string();
+                let attrs = [
+                    ("facing", facing_name(reverse(instance.orientation))),
+                    ("output", "true"),
+                    ("width", &width),
+                    ("label", label),
+                ];
+                self.comp(Some("0"), "Pin", pin(0), &attrs);
+            },
+            _ if has_circuit(kind) => {
+                let name = circuit_name(kind);
+                self.comp(None, &name, px(instance.coords), &[("facing", facing)]);
+                self.circuits.insert(name, kind);
+            },
+            _ => return false,
+        }
+        for i in 0..instance.ty.pins.len() {
+            self.points.insert(pin(i));
+        }
+        true
+    }
+
+    /// The wires, cut wherever something connects partway along them, since Logisim only joins
+    /// things at the ends of wires
+    fn cut_wires(&self) -> Vec<(Point, Point)> {
+        let at = |(x, y): Point| Coords::new(x, y);
+        let points: BTreeSet<Coords> = self.points.iter().map(|&p| at(p)).collect();
+        self.wires
+            .iter()
+            .flat_map(|&(a, b)| WireSegment::cut(at(a), at(b), &points))
+            .map(|(a, b)| ((a.x, a.y), (b.x, b.y)))
+            .collect()
+    }
+
+    fn xml(&self, name: &str, appearance: Option<String>) -> String {
+        let mut out = String::new();
+        writeln!(out, "  <circuit name=\"{}\">", escape(name)).unwrap();
+        writeln!(out, "    <a name=\"circuit\" val=\"{}\"/>", escape(name)).unwrap();
+        if let Some(appearance) = appearance {
+            writeln!(out, "    <a name=\"appearance\" val=\"custom\"/>").unwrap();
+            out.push_str(&appearance);
+        }
+        for ((x1, y1), (x2, y2)) in self.cut_wires() {
+            writeln!(
+                out,
+                "    <wire from=\"({},{})\" to=\"({},{})\"/>",
+                x1, y1, x2, y2
+            )
+            .unwrap();
+        }
+        for comp in self.comps.iter() {
+            out.push_str(comp);
+        }
+        writeln!(out, "  </circuit>").unwrap();
+        out
+    }
+}
+
+/// The inside of the circuit that stands in for components of `kind`, along with the location of
+/// the Logisim pin for each of the component's pins
+fn inside(kind: ComponentKind) -> (Sheet, Vec<Point>) {
+    let mut sheet = Sheet::default();
+    // each pin with the way it faces, which is towards what it connects to
+    let pins: Vec<(Point, &str)> = match kind {
+        ComponentKind::Split { width } | ComponentKind::Join { width } => {
+            // the bus comes in from the west, with a bit leaving to the east on each row below
+            let bus = (6 * SCALE, 4 * SCALE);
+            let width_text = width.to_string();
+            let attrs = [
+                ("facing", "east"),
+                ("fanout", &width_text),
+                ("incoming", &width_text),
+                ("appear", "left"),
+            ];
+            sheet.comp(Some("0"), "Splitter", bus, &attrs);
+            let bits: Vec<(Point, &str)> = (0..width as isize)
+                .map(|bit| {
+                    let end = (bus.0 + 2 * SCALE, bus.1 + SCALE * (bit + 1));
+                    // every other bit goes further out, so that the pins don't overlap
+                    let pin = (end.0 + SCALE * (2 + 2 * (bit % 2)), end.1);
+                    sheet.wire(end, pin);
+                    (pin, "west")
+                })
+                .collect();
+            match kind {
+                ComponentKind::Split { .. } => std::iter::once((bus, "east")).chain(bits).collect(),
+                _ => bits
+                    .into_iter()
+                    .chain(std::iter::once((bus, "east")))
+                    .collect(),
+            }
+        },
+        _ => {
+            let netlist = kind.expand().unwrap();
+            let layout = place::place(&netlist, Ports::Of(kind), Coords::new(0, 0));
+            // the layout grows up from the origin, so move it down clear of Logisim's edges
+            let top = layout
+                .wires
+                .iter()
+                .flatten()
+                .flat_map(|&(a, b)| vec![a.y, b.y])
+                .chain(layout.components.iter().map(|(coords, _)| coords.y))
+                .min()
+                .unwrap_or(0);
+            let shift = Coords::new(4, 4 - top);
+            for &(coords, kind) in layout.components.iter() {
+                let ty = Rc::new(ComponentType::new(kind));
+                let instance = ComponentInstance::new(coords + shift, ty, Orientation::North);
+                sheet.component(&instance, "", 1);
+            }
+            for &(a, b) in layout.wires.iter().flatten() {
+                sheet.wire(px(a + shift), px(b + shift));
+            }
+            kind.pins()
+                .iter()
+                .zip(layout.ports.iter())
+                .map(|(spec, &port)| {
+                    let facing = match spec.ty {
+                        PinType::Input => "north",
+                        PinType::Output => "south",
+                    };
+                    (px(port + shift), facing)
+                })
+                .collect()
+        },
+    };
+
+    for (spec, &(loc, facing)) in kind.pins().iter().zip(pins.iter()) {
+        let width = spec.width.to_string();
+        let output = if spec.ty == PinType::Output {
+            "true"
+        } else {
+            "false"
+        };
+        let attrs = [
+            ("facing", facing),
+            ("output", output),
+            ("width", &width),
+            ("label", &spec.name),
+        ];
+        sheet.comp(Some("0"), "Pin", loc, &attrs);
+    }
+    (sheet, pins.into_iter().map(|(loc, _)| loc).collect())
+}
+
+/// Draws the symbol of `kind` for the circuit standing in for it, with the anchor where the
+/// component's coordinates are and a port on each pin, so that an instance takes the
+/// component's place exactly
+fn appearance(kind: ComponentKind, pins: &[Point]) -> String {
+    let ty = ComponentType::new(kind);
+    // boxed symbols have their coordinates a cell in from the bottom left corner
+    let (cols, rows) = (
+        (ty.size.width / 16.0) as isize,
+        (ty.size.height / 16.0) as isize,
+    );
+    let (left, top) = (-1, 1 - rows);
+    let (ax, ay) = (SCALE * (2 - left), SCALE * (2 - top));
+
+    let mut out = String::from("    <appear>\n");
+    writeln!(
+        out,
+        "      <rect fill=\"none\" height=\"{}\" stroke=\"#000000\" stroke-width=\"2\" \
+         width=\"{}\" x=\"{}\" y=\"{}\"/>",
+        SCALE * rows,
+        SCALE * cols,
+        ax + SCALE * left,
+        ay + SCALE * top,
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "      <text font-family=\"SansSerif\" font-size=\"12\" text-anchor=\"middle\" \
+         x=\"{}\" y=\"{}\">{}</text>",
+        ax + SCALE * left + SCALE * cols / 2,
+        ay + SCALE * top + SCALE * rows / 2 + 4,
+        kind.name(),
+    )
+    .unwrap();
+    for (pin, loc) in ty.pins.iter().zip(pins) {
+        let radius = match pin.ty {
+            PinType::Input => 4,
+            PinType::Output => 5,
+        };
+        writeln!(
+            out,
+            "      <circ-port height=\"{}\" pin=\"({},{})\" width=\"{}\" x=\"{}\" y=\"{}\"/>",
+            2 * radius,
+            loc.0,
+            loc.1,
+            2 * radius,
+            ax + SCALE * pin.pos.x - radius,
+            ay + SCALE * pin.pos.y - radius,
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "      <circ-anchor facing=\"north\" height=\"6\" width=\"6\" x=\"{}\" y=\"{}\"/>",
+        ax - 3,
+        ay - 3
+    )
+    .unwrap();
+    out.push_str("    </appear>\n");
+    out
+}
+
+/// Writes the circuit out as a Logisim-evolution project with the components where they are,
+/// giving each kind of component that Logisim has nothing like a circuit of its own built from
+/// gates. Returns the names of the components that couldn't be written out at all.
+pub fn export(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> (String, Vec<String>) {
+    let netlist = Netlist::build(components, wires);
+    let mut main = Sheet::default();
+    let mut left_out = Vec::new();
+    for (&id, component) in components.iter() {
+        let instance = &component.instance;
+        let width = netlist.pins[&id]
+            .first()
+            .map_or(1, |&net| netlist.width(net));
+        if !main.component(instance, &instance.name(id), width) {
+            left_out.push(instance.name(id));
+        }
+    }
+    for wire in wires.values() {
+        for segment in wire.segments.iter() {
+            let (a, b) = segment.ends();
+            main.wire(px(a), px(b));
+        }
+    }
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <project source=\"3.8.0\" version=\"1.0\">\n  \
+         <lib desc=\"#Wiring\" name=\"0\"/>\n  \
+         <lib desc=\"#Gates\" name=\"1\"/>\n  \
+         <main name=\"main\"/>\n",
+    );
+    out.push_str(&main.xml("main", None));
+    let mut queue: Vec<_> = main.circuits.into_iter().collect();
+    let mut written = BTreeSet::new();
+    while let Some((name, kind)) = queue.pop() {
+        if written.insert(name.clone()) {
+            let (sheet, pins) = inside(kind);
+            out.push_str(&sheet.xml(&name, Some(appearance(kind, &pins))));
+            queue.extend(sheet.circuits);
+        }
+    }
+    out.push_str("</project>\n");
+    (out, left_out)
+}
+
+pub fn write_logisim(
+    path: &Path,
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Result<Vec<String>, String> {
+    let (text, left_out) = export(components, wires);
+    std::fs::write(path, text).map_err(|e| e.to_string())?;
+    Ok(left_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canvas::CanvasState, import::tests::truth_table, value::Value};
+
+    /// A project with a circuit for each name, holding the wires and components given for it
+    fn project(circuits: &[(&str, &str)]) -> String {
+        let mut out = String::from(
+            "<project source=\"3.8.0\" version=\"1.0\">\n  <lib desc=\"#Wiring\" name=\"0\"/>\n  \
+             <lib desc=\"#Gates\" name=\"1\"/>\n  <lib desc=\"#Memory\" name=\"4\"/>\n",
+        );
+        for (name, body) in circuits {
+            writeln!(out, "  <circuit name=\"{}\">\n{}\n  </circuit>", name, body).unwrap();
+        }
+        out.push_str("</project>\n");
+        out
+    }
+
+    /// A half adder, with its inputs on the left and the gates' outputs wired to output pins
+    const HALF_ADDER: &str = r#"
+    <wire from="(100,100)" to="(120,100)"/>
+    <wire from="(120,100)" to="(150,100)"/>
+    <wire from="(120,100)" to="(120,200)"/>
+    <wire from="(120,200)" to="(140,200)"/>
+    <wire from="(100,140)" to="(110,140)"/>
+    <wire from="(110,140)" to="(150,140)"/>
+    <wire from="(110,140)" to="(110,240)"/>
+    <wire from="(110,240)" to="(140,240)"/>
+    <wire from="(200,120)" to="(250,120)"/>
+    <wire from="(200,220)" to="(250,220)"/>
+    <comp lib="0" loc="(100,100)" name="Pin"><a name="label" val="a"/></comp>
+    <comp lib="0" loc="(100,140)" name="Pin"><a name="label" val="b"/></comp>
+    <comp lib="1" loc="(200,120)" name="AND Gate"/>
+    <comp lib="0" loc="(250,120)" name="Pin">
+      <a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="c"/>
+    </comp>
+    <comp lib="1" loc="(200,220)" name="XOR Gate"/>
+    <comp lib="0" loc="(250,220)" name="Pin">
+      <a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="s"/>
+    </comp>"#;
+
+    #[test]
+    fn imports_a_half_adder() {
+        let import = import(&project(&[("main", HALF_ADDER)])).unwrap();
+        let (names, rows) = truth_table(import);
+        assert_eq!(names, ["a", "b", "c", "s"]);
+        assert_eq!(
+            rows,
+            [[0, 0, 0, 0], [0, 1, 0, 1], [1, 0, 0, 1], [1, 1, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn imports_a_subcircuit() {
+        // the half adder's pins sit on the edges of its instance, in the classic appearance
+        let main = r#"
+    <wire from="(40,60)" to="(60,60)"/>
+    <wire from="(40,80)" to="(50,80)"/>
+    <wire from="(50,80)" to="(50,70)"/>
+    <wire from="(50,70)" to="(60,70)"/>
+    <comp lib="0" loc="(40,60)" name="Pin"><a name="label" val="x"/></comp>
+    <comp lib="0" loc="(40,80)" name="Pin"><a name="label" val="y"/></comp>
+    <comp loc="(90,60)" name="half"/>
+    <comp lib="0" loc="(120,60)" name="Pin">
+      <a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="carry"/>
+    </comp>
+    <comp lib="0" loc="(120,70)" name="Pin">
+      <a name="facing" val="west"/><a name="output" val="true"/><a name="label" val="sum"/>
+    </comp>
+    <wire from="(90,60)" to="(120,60)"/>
+    <wire from="(90,70)" to="(120,70)"/>"#;
+        let import = import(&project(&[("main", main), ("half", HALF_ADDER)])).unwrap();
+        let (names, rows) = truth_table(import);
+        assert_eq!(names, ["x", "y", "carry", "sum"]);
+        assert_eq!(
+            rows,
+            [[0, 0, 0, 0], [0, 1, 0, 1], [1, 0, 0, 1], [1, 1, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn leaves_out_what_it_cannot_import() {
+        let body = r#"<comp lib="4" loc="(100,100)" name="Register"/>"#;
+        let report = import(&project(&[("main", body)])).unwrap().report(&[]);
+        assert!(report.contains("Register"), "{}", report);
+        assert!(import("<circuit/>").is_err());
+    }
+
+    #[test]
+    fn splits_and_joins_round_trip() {
+        let mut canvas = CanvasState::new();
+        let mut add = |kind, x| {
+            let ty = Rc::new(ComponentType::new(kind));
+            canvas.add_component(Coords::new(x, 0), ty, Orientation::North)
+        };
+        let input = add(ComponentKind::Input { width: 3 }, 0);
+        let split = add(ComponentKind::Split { width: 3 }, 6);
+        let join = add(ComponentKind::Join { width: 3 }, 14);
+        let probe = add(ComponentKind::Probe { radix: Radix::Hex }, 22);
+        let pin = |id, pin| canvas.components()[&id].instance.pin_coords(pin);
+        // the bits cross over, so that they have to come back in the right order
+        let links = [
+            (pin(input, 0), pin(split, 0)),
+            (pin(split, 1), pin(join, 2)),
+            (pin(split, 2), pin(join, 1)),
+            (pin(split, 3), pin(join, 0)),
+            (pin(join, 3), pin(probe, 0)),
+        ];
+        let pins: Vec<_> = [input, split, join, probe]
+            .iter()
+            .flat_map(|&id| (0..4).map(move |i| (id, i)))
+            .filter(|&(id, i)| i < canvas.components()[&id].instance.ty.pins.len())
+            .map(|(id, i)| pin(id, i))
+            .collect();
+        for segments in crate::import::route_links(pins.into_iter(), None.into_iter(), &links) {
+            let segments = segments
+                .into_iter()
+                .filter_map(|(a, b)| WireSegment::new(a, b))
+                .collect();
+            canvas.add_wire(segments);
+        }
+        let before = crate::analysis::truth_table(canvas.components(), canvas.wires()).unwrap();
+        assert_eq!(before.rows[1][1], Value::new(3, 4));
+
+        let (text, left_out) = export(canvas.components(), canvas.wires());
+        assert!(left_out.is_empty());
+        let (_, rows) = truth_table(import(&text).unwrap());
+        let before: Vec<Vec<u64>> = before
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|value| value.bits()).collect())
+            .collect();
+        assert_eq!(rows, before);
+    }
+}
@@ -1,13 +1,42 @@
-use std::rc::Rc;
+use std::{path::Path, rc::Rc};
 
 use component::ComponentType;
-use druid::{Affine, AppLauncher, Widget, WindowDesc};
+use druid::{
+    commands,
+    widget::{Button, CrossAxisAlignment, Flex, Label, Scroll},
+    Affine, AppDelegate, AppLauncher, Command, DelegateCtx, Env, FileDialogOptions, Handled,
+    Target, Widget, WidgetExt, WindowDesc,
+};
 
+mod analysis;
+mod blif;
 mod canvas;
 mod component;
+mod digital;
+mod equiv;
+mod expand;
+mod hdl;
+mod import;
+mod karnaugh;
+mod kind;
+mod logic;
+mod logisim;
+mod memory;
+mod picture;
+mod place;
+mod sim;
+mod synth;
+mod tikz;
+mod value;
+mod vcd;
+mod verilog;
+mod vhdl;
+mod waveform;
 mod wire;
+mod yosys;
 
 use canvas::{Canvas, CanvasState};
+use import::Import;
 
 const IDENTITY: Affine = Affine::scale(1.0);
 
@@ -19,10 +48,351 @@ fn main() {
         .window_size((800.0, 600.0));
 
     AppLauncher::with_window(window)
+        .delegate(Delegate)
         .launch(CanvasState::new())
         .expect("Failed to launch application");
 }
 
-fn root_widget(component_icons: Rc<Vec<Rc<ComponentType>>>) -> impl Widget<CanvasState> {
-    Canvas::new(component_icons)
+fn root_widget(component_types: Rc<Vec<Rc<ComponentType>>>) -> impl Widget<CanvasState> {
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(palette(&component_types))
+        .with_flex_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_flex_child(Canvas::new(component_types), 1.0)
+                .with_child(waveform::panel())
+                .with_child(Label::dynamic(|data: &CanvasState, _| {
+                    data.status().to_owned()
+                })),
+            1.0,
+        )
+        .with_child(memory::editor())
+        .with_child(analysis::panel())
+        .with_child(synth::panel())
+        .with_child(karnaugh::panel())
+}
+
+/// Handles the files picked in open and save dialogs
+struct Delegate;
+
+impl AppDelegate<CanvasState> for Delegate {
+    fn command(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut CanvasState,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(file) = cmd.get(memory::LOAD_MEMORY) {
+            if let Some((address, width, _)) = data.edited_memory() {
+                match memory::read_image(file.path(), address, width) {
+                    Ok(words) => data.set_edited_memory(words),
+                    Err(error) => data.set_memory_error(error),
+                }
+            }
+            Handled::Yes
+        } else if let Some(file) = cmd.get(memory::SAVE_MEMORY) {
+            if let Some((_, width, words)) = data.edited_memory() {
+                if let Err(error) = memory::write_image(file.path(), &words, width) {
+                    data.set_memory_error(error);
+                }
+            }
+            Handled::Yes
+        } else if let Some(file) = cmd.get(analysis::EXPORT_ANALYSIS) {
+            if let Some(analysis) = data.analysis() {
+                let result = analysis::export(analysis, file.path());
+                data.set_status(exported(file.path(), result.map(|()| Vec::new())));
+            }
+            Handled::Yes
+        } else if let Some(file) = cmd.get(verilog::EXPORT_VERILOG) {
+            let result = verilog::write_verilog(file.path(), data.components(), data.wires());
+            data.set_status(exported(file.path(), result.map(|()| Vec::new())));
+            Handled::Yes
+        } else if let Some(file) = cmd.get(vhdl::EXPORT_VHDL) {
+            let result = vhdl::write_vhdl(file.path(), data.components(), data.wires());
+            data.set_status(exported(file.path(), result.map(|()| Vec::new())));
+            Handled::Yes
+        } else if let Some(file) = cmd.get(logisim::EXPORT_LOGISIM) {
+            let result = logisim::write_logisim(file.path(), data.components(), data.wires());
+            data.set_status(exported(file.path(), result));
+            Handled::Yes
+        } else if let Some(file) = cmd.get(digital::EXPORT_DIGITAL) {
+            let result = digital::write_digital(file.path(), data.components(), data.wires());
+            data.set_status(exported(file.path(), result));
+            Handled::Yes
+        } else if let Some(file) = cmd.get(digital::IMPORT_DIGITAL) {
+            let result = digital::read_digital(file.path());
+            imported(data, "Digital import", file.path(), result);
+            Handled::Yes
+        } else if let Some(file) = cmd.get(logisim::IMPORT_LOGISIM) {
+            let result = logisim::read_logisim(file.path());
+            imported(data, "Logisim import", file.path(), result);
+            Handled::Yes
+        } else if let Some(file) = cmd.get(yosys::IMPORT_YOSYS) {
+            let result = yosys::read_yosys(file.path());
+            imported(data, "Yosys import", file.path(), result);
+            Handled::Yes
+        } else if let Some(file) = cmd.get(blif::IMPORT_BLIF) {
+            let result = blif::read_blif(file.path());
+            imported(data, "BLIF import", file.path(), result);
+            Handled::Yes
+        } else if let Some(file) = cmd.get(equiv::LOAD_REFERENCE) {
+            match equiv::read_reference(file.path()) {
+                Ok(import) => data.set_reference_from_import(file.path(), import),
+                Err(error) => data.set_status(error),
+            }
+            Handled::Yes
+        } else if let Some(file) = cmd.get(picture::EXPORT_SVG) {
+            let result =
+                picture::write_svg(file.path(), data.components(), data.wires(), data.picture());
+            data.set_status(exported(file.path(), result.map(|()| Vec::new())));
+            Handled::Yes
+        } else if let Some(file) = cmd.get(picture::EXPORT_PNG) {
+            let result =
+                picture::write_png(file.path(), data.components(), data.wires(), data.picture());
+            data.set_status(exported(file.path(), result.map(|()| Vec::new())));
+            Handled::Yes
+        } else if let Some(file) = cmd.get(tikz::EXPORT_TIKZ) {
+            let result = tikz::write_tikz(file.path(), data.components(), data.wires());
+            data.set_status(exported(file.path(), result.map(|()| Vec::new())));
+            Handled::Yes
+        } else if let Some(file) = cmd.get(vcd::EXPORT_VCD) {
+            let traces: Vec<_> = data.traces().iter().cloned().collect();
+            let result = vcd::write_vcd(file.path(), &traces, data.time());
+            data.set_status(exported(file.path(), result.map(|()| Vec::new())));
+            Handled::Yes
+        } else {
+            Handled::No
+        }
+    }
+}
+
+/// The status after exporting the circuit to `path`, naming any parts that had to be left out
+fn exported(path: &Path, result: Result<Vec<String>, String>) -> String {
+    match result {
+        Ok(left_out) if left_out.is_empty() => format!("Exported {}", path.display()),
+        Ok(left_out) => format!(
+            "Exported {}, leaving out {}",
+            path.display(),
+            left_out.join(", ")
+        ),
+        Err(error) => error,
+    }
+}
+
+/// Replaces the circuit with one read from `path`, or says why it couldn't be read
+fn imported(data: &mut CanvasState, title: &str, path: &Path, result: Result<Import, String>) {
+    match result {
+        Ok(import) => {
+            data.replace_with_import(title, import);
+            data.set_status(format!("Imported {}", path.display()));
+        },
+        Err(error) => data.set_status(error),
+    }
+}
+
+/// A button for each component type, for when there are too many to pick with the number keys
+fn palette(component_types: &[Rc<ComponentType>]) -> impl Widget<CanvasState> {
+    let mut column = Flex::column().cross_axis_alignment(CrossAxisAlignment::Fill);
+    for ty in component_types {
+        let ty = Rc::clone(ty);
+        column.add_child(
+            Button::new(ty.kind.name())
+                .on_click(move |_ctx, data: &mut CanvasState, _env| {
+                    data.select_tool(Rc::clone(&ty))
+                })
+                .padding(2.0),
+        );
+    }
+
+    // analyses of the circuit
+    column.add_spacer(8.0);
+    column.add_child(
+        Button::new("Truth table")
+            .on_click(|_ctx, data: &mut CanvasState, _env| data.show_truth_table())
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Expressions")
+            .on_click(|_ctx, data: &mut CanvasState, _env| data.show_expressions())
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Set reference")
+            .on_click(|_ctx, data: &mut CanvasState, _env| data.set_reference())
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Open reference")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![
+                        logisim::LOGISIM,
+                        digital::DIGITAL,
+                        yosys::YOSYS,
+                        blif::BLIF,
+                    ])
+                    .accept_command(equiv::LOAD_REFERENCE);
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Compare")
+            .on_click(|_ctx, data: &mut CanvasState, _env| data.compare_with_reference())
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("K-map")
+            .on_click(|_ctx, data: &mut CanvasState, _env| data.show_karnaugh())
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Synthesise")
+            .on_click(|_ctx, data: &mut CanvasState, _env| data.open_synthesis())
+            .padding(2.0),
+    );
+
+    // the circuit in other formats
+    column.add_spacer(8.0);
+    column.add_child(
+        Button::new("Verilog")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![verilog::VERILOG])
+                    .default_type(verilog::VERILOG)
+                    .accept_command(verilog::EXPORT_VERILOG);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("VHDL")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![vhdl::VHDL])
+                    .default_type(vhdl::VHDL)
+                    .accept_command(vhdl::EXPORT_VHDL);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Logisim")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![logisim::LOGISIM])
+                    .default_type(logisim::LOGISIM)
+                    .accept_command(logisim::EXPORT_LOGISIM);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Open .circ")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![logisim::LOGISIM])
+                    .accept_command(logisim::IMPORT_LOGISIM);
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Digital")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![digital::DIGITAL])
+                    .default_type(digital::DIGITAL)
+                    .accept_command(digital::EXPORT_DIGITAL);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Open .dig")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![digital::DIGITAL])
+                    .accept_command(digital::IMPORT_DIGITAL);
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    // synthesised netlists, laid out afresh
+    column.add_child(
+        Button::new("Open .json")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![yosys::YOSYS])
+                    .accept_command(yosys::IMPORT_YOSYS);
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("Open .blif")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![blif::BLIF])
+                    .accept_command(blif::IMPORT_BLIF);
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+
+    // pictures of the circuit, or of the selection if there is one
+    column.add_spacer(8.0);
+    column.add_child(
+        Button::new("SVG")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![picture::SVG])
+                    .default_type(picture::SVG)
+                    .accept_command(picture::EXPORT_SVG);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("PNG")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![picture::PNG])
+                    .default_type(picture::PNG)
+                    .accept_command(picture::EXPORT_PNG);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::new("TikZ")
+            .on_click(|ctx, _data: &mut CanvasState, _env| {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![tikz::TIKZ])
+                    .default_type(tikz::TIKZ)
+                    .accept_command(tikz::EXPORT_TIKZ);
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+            })
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::dynamic(|data: &CanvasState, _| format!("PNG {}×", data.picture().scale))
+            .on_click(|_ctx, data: &mut CanvasState, _env| data.next_picture_scale())
+            .padding(2.0),
+    );
+    column.add_child(
+        Button::dynamic(|data: &CanvasState, _| {
+            if data.picture().grid {
+                "Dots on".to_owned()
+            } else {
+                "Dots off".to_owned()
+            }
+        })
+        .on_click(|_ctx, data: &mut CanvasState, _env| data.toggle_grid_dots())
+        .padding(2.0),
+    );
+    Scroll::new(column).vertical().fix_width(80.0)
 }
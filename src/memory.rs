@@ -0,0 +1,470 @@
+use std::{fmt::Write, path::Path, rc::Rc};
+
+use druid::{
+    commands,
+    widget::{Button, CrossAxisAlignment, Either, Flex, Label, SizedBox, TextBox},
+    Data, FileDialogOptions, FileInfo, FileSpec, FontDescriptor, FontFamily, Lens, Selector,
+    Widget, WidgetExt,
+};
+
+use crate::{canvas::CanvasState, value::Value};
+
+/// Sent by the open dialog of the memory editor, to load an image into the memory being edited
+pub const LOAD_MEMORY: Selector<FileInfo> = Selector::new("logicism/load-memory");
+/// Sent by the save dialog of the memory editor, to save the memory being edited as an image
+pub const SAVE_MEMORY: Selector<FileInfo> = Selector::new("logicism/save-memory");
+
+const RAW: FileSpec = FileSpec::new("Raw binary", &["bin"]);
+const INTEL_HEX: FileSpec = FileSpec::new("Intel HEX", &["hex", "ihex", "ihx"]);
+const LOGISIM: FileSpec = FileSpec::new("Logisim image", &["txt", "img"]);
+
+/// The file formats a memory image can be stored in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Each word as little-endian bytes, as many as it takes to hold the data width
+    Raw,
+    /// Byte records addressed from the start of the memory, with each word split up as for `Raw`
+    IntelHex,
+    /// A `v2.0 raw` header followed by words in hex, where `N*value` stands for `N` of the same
+    Logisim,
+}
+
+impl Format {
+    /// Picks a format from a file extension, falling back to Logisim's since it's plain text
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        if RAW.extensions.contains(&&*extension) {
+            Format::Raw
+        } else if INTEL_HEX.extensions.contains(&&*extension) {
+            Format::IntelHex
+        } else {
+            Format::Logisim
+        }
+    }
+}
+
+fn bytes_per_word(width: u8) -> usize {
+    (width as usize + 7) / 8
+}
+
+fn check_fits(words: &[u64], address: u8, width: u8) -> Result<(), String> {
+    if words.len() > 1 << address {
+        return Err(format!(
+            "{} words don't fit in a memory of {}",
+            words.len(),
+            1 << address
+        ));
+    }
+    match words.iter().find(|&&w| w & !Value::mask(width) != 0) {
+        Some(w) => Err(format!("{:x} doesn't fit in {} bits", w, width)),
+        None => Ok(()),
+    }
+}
+
+fn words_from_bytes(bytes: &[u8], width: u8) -> Vec<u64> {
+    bytes
+        .chunks(bytes_per_word(width))
+        .map(|chunk| {
+            chunk
+                .iter()
+                .rev()
+                .fold(0, |word, &byte| word << 8 | byte as u64)
+                & Value::mask(width)
+        })
+        .collect()
+}
+
+fn bytes_from_words(words: &[u64], width: u8) -> Vec<u8> {
+    let n = bytes_per_word(width);
+    words
+        .iter()
+        .flat_map(|word| (0..n).map(move |i| (word >> (8 * i)) as u8))
+        .collect()
+}
+
+/// Reads an image for a memory with the given address and data widths. Files that start with
+/// Logisim's header are read as such whatever they're called.
+pub fn read_image(path: &Path, address: u8, width: u8) -> Result<Vec<u64>, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let format = if bytes.starts_with(b"v2.0 raw") {
+        Format::Logisim
+    } else {
+        Format::from_path(path)
+    };
+    let words = match format {
+        Format::Raw => words_from_bytes(&bytes, width),
+        Format::IntelHex => read_intel_hex(&String::from_utf8_lossy(&bytes), address, width)?,
+        Format::Logisim => read_logisim(&String::from_utf8_lossy(&bytes), address)?,
+    };
+    check_fits(&words, address, width)?;
+    Ok(words)
+}
+
+/// Writes an image in the format picked by the extension of `path`
+pub fn write_image(path: &Path, words: &[u64], width: u8) -> Result<(), String> {
+    let bytes = match Format::from_path(path) {
+        Format::Raw => bytes_from_words(words, width),
+        Format::IntelHex => write_intel_hex(words, width).into_bytes(),
+        Format::Logisim => write_logisim(words).into_bytes(),
+    };
+    std::fs::write(path, bytes).map_err(|e| e.to_string())
+}
+
+fn read_intel_hex(text: &str, address: u8, width: u8) -> Result<Vec<u64>, String> {
+    let size = bytes_per_word(width) << address;
+    let mut bytes = Vec::new();
+    let mut base = 0;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", n + 1, message);
+        let hex = line
+            .strip_prefix(':')
+            .ok_or_else(|| error("records start with ':'"))?;
+        if hex.len() % 2 != 0 || hex.len() < 10 {
+            return Err(error("record is too short"));
+        }
+        let record = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| error("not a hex digit"))?;
+        if record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(error("bad checksum"));
+        }
+        let len = record[0] as usize;
+        if record.len() != len + 5 {
+            return Err(error("length doesn't match the record"));
+        }
+        let offset = (record[1] as usize) << 8 | record[2] as usize;
+        let data = &record[4..4 + len];
+        let data_u16 = || -> Result<usize, String> {
+            match *data {
+                [hi, lo] => Ok((hi as usize) << 8 | lo as usize),
+                _ => Err(error("address records have two bytes")),
+            }
+        };
+        match record[3] {
+            0x00 => {
+                let start = base + offset;
+                if start + len > size {
+                    return Err(error("data past the end of the memory"));
+                }
+                if bytes.len() < start + len {
+                    bytes.resize(start + len, 0);
+                }
+                bytes[start..start + len].copy_from_slice(data);
+            },
+            0x01 => break,
+            0x02 => base = data_u16()? << 4,
+            0x04 => base = data_u16()? << 16,
+            // start addresses mean nothing to a memory
+            0x03 | 0x05 => {},
+            ty => return Err(error(&format!("unknown record type {:02X}", ty))),
+        }
+    }
+    Ok(words_from_bytes(&bytes, width))
+}
+
+fn write_intel_hex(words: &[u64], width: u8) -> String {
+    fn record(out: &mut String, ty: u8, offset: u16, data: &[u8]) {
+        let mut bytes = vec![data.len() as u8, (offset >> 8) as u8, offset as u8, ty];
+        bytes.extend_from_slice(data);
+        let checksum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_sub(*b));
+        out.push(':');
+        for b in bytes.iter().chain(Some(&checksum)) {
+            write!(out, "{:02X}", b).unwrap();
+        }
+        out.push('\n');
+    }
+
+    let mut out = String::new();
+    let mut upper = 0;
+    for (i, chunk) in bytes_from_words(words, width).chunks(16).enumerate() {
+        let address = i * 16;
+        if address >> 16 != upper {
+            upper = address >> 16;
+            record(&mut out, 0x04, 0, &[(upper >> 8) as u8, upper as u8]);
+        }
+        record(&mut out, 0x00, address as u16, chunk);
+    }
+    record(&mut out, 0x01, 0, &[]);
+    out
+}
+
+fn read_logisim(text: &str, address: u8) -> Result<Vec<u64>, String> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("v2.0 raw") {
+        return Err("missing the 'v2.0 raw' header".to_owned());
+    }
+    let mut words = Vec::new();
+    for line in lines {
+        let line = line.split('#').next().unwrap();
+        for token in line.split_whitespace() {
+            let parse = |s: &str, radix| {
+                u64::from_str_radix(s, radix).map_err(|_| format!("bad value '{}'", token))
+            };
+            match token.split_once('*') {
+                Some((count, value)) => {
+                    let (count, value) = (parse(count, 10)? as usize, parse(value, 16)?);
+                    if words
+                        .len()
+                        .checked_add(count)
+                        .map_or(true, |n| n > 1 << address)
+                    {
+                        return Err(format!(
+                            "more words than a memory of {} holds",
+                            1 << address
+                        ));
+                    }
+                    words.extend(std::iter::repeat(value).take(count));
+                },
+                None => words.push(parse(token, 16)?),
+            }
+        }
+    }
+    Ok(words)
+}
+
+fn write_logisim(words: &[u64]) -> String {
+    let mut out = String::from("v2.0 raw\n");
+    let mut tokens = 0;
+    let mut i = 0;
+    while i < words.len() {
+        let run = words[i..].iter().take_while(|&&w| w == words[i]).count();
+        if run >= 4 {
+            write!(out, "{}*{:x}", run, words[i]).unwrap();
+            i += run;
+        } else {
+            write!(out, "{:x}", words[i]).unwrap();
+            i += 1;
+        }
+        tokens += 1;
+        out.push(if tokens % 8 == 0 { '\n' } else { ' ' });
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+const WORDS_PER_LINE: usize = 8;
+
+/// Shows words in hex, a line at a time, each line headed by the address of its first word.
+/// Lines of zeroes at the end are left off since that's what missing words read as anyway.
+pub fn dump(words: &[u64], address: u8, width: u8) -> String {
+    let address_digits = ((address as usize + 3) / 4).max(1);
+    let digits = ((width as usize + 3) / 4).max(1);
+    let used = words.iter().rposition(|&w| w != 0).map_or(0, |i| i + 1);
+
+    let mut out = String::new();
+    for (i, line) in words[..used].chunks(WORDS_PER_LINE).enumerate() {
+        write!(out, "{:01$x}:", i * WORDS_PER_LINE, address_digits).unwrap();
+        for word in line {
+            write!(out, " {:01$x}", word, digits).unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Reads back what `dump` shows, or anything like it. An address followed by a colon moves on to
+/// that address, which mustn't go back over words already given, and everything else is taken to
+/// be words in hex.
+pub fn parse_dump(text: &str, address: u8, width: u8) -> Result<Vec<u64>, String> {
+    let mut words = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let error = |message: String| format!("line {}: {}", n + 1, message);
+        let mut rest = line.split('#').next().unwrap();
+        if let Some((at, tail)) = rest.split_once(':') {
+            let at = usize::from_str_radix(at.trim(), 16)
+                .ok()
+                .filter(|&at| at < 1 << address)
+                .ok_or_else(|| error(format!("bad address '{}'", at.trim())))?;
+            if at < words.len() {
+                return Err(error(format!(
+                    "address {:x} comes before words already given",
+                    at
+                )));
+            }
+            words.resize(at, 0);
+            rest = tail;
+        }
+        for token in rest.split_whitespace() {
+            let word = u64::from_str_radix(token, 16)
+                .map_err(|_| error(format!("bad value '{}'", token)))?;
+            words.push(word);
+        }
+        check_fits(&words, address, width).map_err(error)?;
+    }
+    Ok(words)
+}
+
+/// The contents of the memory component being edited, as text that may not have been applied yet
+#[derive(Clone, Data)]
+pub struct MemoryEditor {
+    pub id: usize,
+    pub text: String,
+    pub error: Option<String>,
+    /// Whether the text is the initial contents of the memory, to be edited, rather than what it
+    /// holds now in the simulation
+    pub initial: bool,
+    /// The words the text was last made from
+    pub shown: Rc<Vec<u64>>,
+}
+
+struct EditorText;
+
+impl Lens<CanvasState, String> for EditorText {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &CanvasState, f: F) -> V {
+        match data.memory_editor() {
+            Some(editor) => f(&editor.text),
+            None => f(&String::new()),
+        }
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut CanvasState, f: F) -> V {
+        match data.memory_editor_mut() {
+            Some(editor) => f(&mut editor.text),
+            None => f(&mut String::new()),
+        }
+    }
+}
+
+/// A panel for the contents of the selected memory, which is empty while there isn't one
+pub fn editor() -> impl Widget<CanvasState> {
+    let open = |ctx: &mut druid::EventCtx, _: &mut CanvasState, _: &druid::Env| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![LOGISIM, INTEL_HEX, RAW])
+            .accept_command(LOAD_MEMORY);
+        ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+    };
+    let save = |ctx: &mut druid::EventCtx, _: &mut CanvasState, _: &druid::Env| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![LOGISIM, INTEL_HEX, RAW])
+            .default_type(LOGISIM)
+            .accept_command(SAVE_MEMORY);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+    };
+    let initial = Flex::row()
+        .with_child(
+            Button::new("Apply")
+                .on_click(|_, data: &mut CanvasState, _| data.apply_memory_editor()),
+        )
+        .with_child(Button::new("Load").on_click(open))
+        .with_child(Button::new("Save").on_click(save))
+        .with_child(
+            Button::new("Now")
+                .on_click(|_, data: &mut CanvasState, _| data.edit_initial_memory(false)),
+        );
+    let now = Flex::row()
+        .with_child(
+            Button::new("Edit initial")
+                .on_click(|_, data: &mut CanvasState, _| data.edit_initial_memory(true)),
+        )
+        .with_child(Button::new("Save").on_click(save));
+    let is_initial =
+        |data: &CanvasState| data.memory_editor().map_or(false, |editor| editor.initial);
+    let buttons = Either::new(move |data: &CanvasState, _| is_initial(data), initial, now);
+
+    let panel = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(buttons)
+        .with_child(Label::dynamic(move |data: &CanvasState, _| {
+            if is_initial(data) {
+                "Initial contents, which it starts from".to_owned()
+            } else {
+                "Contents now, as simulated".to_owned()
+            }
+        }))
+        .with_flex_child(
+            TextBox::multiline()
+                .with_font(FontDescriptor::new(FontFamily::MONOSPACE))
+                .lens(EditorText)
+                .expand(),
+            1.0,
+        )
+        .with_child(Label::dynamic(|data: &CanvasState, _| {
+            data.memory_editor()
+                .and_then(|editor| editor.error.clone())
+                .unwrap_or_default()
+        }))
+        .padding(4.0)
+        .fix_width(240.0);
+    Either::new(
+        |data: &CanvasState, _| data.memory_editor().is_some(),
+        panel,
+        SizedBox::empty(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: [u64; 9] = [0x123, 0xfff, 0, 0, 0, 0, 0, 0x7, 0x800];
+
+    #[test]
+    fn raw_round_trip() {
+        assert_eq!(words_from_bytes(&bytes_from_words(&WORDS, 12), 12), WORDS);
+    }
+
+    #[test]
+    fn intel_hex_round_trip() {
+        let text = write_intel_hex(&WORDS, 12);
+        assert_eq!(read_intel_hex(&text, 4, 12), Ok(WORDS.to_vec()));
+    }
+
+    #[test]
+    fn intel_hex_past_64k() {
+        let words: Vec<u64> = (0..1 << 16).collect();
+        let text = write_intel_hex(&words, 16);
+        assert!(text.contains(":020000040001F9\n"));
+        assert_eq!(read_intel_hex(&text, 16, 16), Ok(words));
+    }
+
+    #[test]
+    fn intel_hex_record() {
+        let words = read_intel_hex(":0B0010006164647265737320676170A7\n:00000001FF\n", 5, 8);
+        let mut expected = vec![0; 16];
+        expected.extend(b"address gap".iter().map(|&b| b as u64));
+        assert_eq!(words, Ok(expected));
+        assert!(read_intel_hex(":0B0010006164647265737320676170A8\n", 5, 8).is_err());
+    }
+
+    #[test]
+    fn logisim_round_trip() {
+        let text = write_logisim(&WORDS);
+        assert_eq!(text, "v2.0 raw\n123 fff 5*0 7 800\n");
+        assert_eq!(read_logisim(&text, 4), Ok(WORDS.to_vec()));
+    }
+
+    #[test]
+    fn logisim_runs_past_the_end() {
+        assert!(read_logisim("v2.0 raw\n17*0\n", 4).is_err());
+        assert!(read_logisim("v2.0 raw\n1 18446744073709551615*0\n", 4).is_err());
+    }
+
+    #[test]
+    fn dump_round_trip() {
+        let text = dump(&WORDS, 4, 12);
+        assert_eq!(text, "0: 123 fff 000 000 000 000 000 007\n8: 800\n");
+        assert_eq!(parse_dump(&text, 4, 12), Ok(WORDS.to_vec()));
+    }
+
+    #[test]
+    fn dump_addresses() {
+        assert_eq!(
+            parse_dump("1\n4: 2 # skips ahead", 4, 8),
+            Ok(vec![1, 0, 0, 0, 2])
+        );
+        assert!(parse_dump("4: 1\n2: 2\n", 4, 8).is_err());
+        assert!(parse_dump("10: 1\n", 4, 8).is_err());
+        assert!(parse_dump("0: 100\n", 4, 8).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use std::{fmt::Write, path::Path};
+
+use druid::{im, Color, Data, FileSpec, Point, Rect, Selector, Size};
+
+use crate::{
+    component::{icon, segment_shapes, shown_line, ComponentState, Symbol, LIT, SEGMENTS, UNLIT},
+    kind::ComponentKind,
+    logisim::escape,
+    value::Value,
+    wire::{self, WireState},
+};
+
+pub const EXPORT_SVG: Selector<druid::FileInfo> = Selector::new("logicism/export-svg");
+pub const EXPORT_PNG: Selector<druid::FileInfo> = Selector::new("logicism/export-png");
+
+pub const SVG: FileSpec = FileSpec::new("SVG image", &["svg"]);
+pub const PNG: FileSpec = FileSpec::new("PNG image", &["png"]);
+
+/// How pictures of the circuit are drawn
+#[derive(Clone, Copy, Data)]
+pub struct Picture {
+    /// Whether the grid's dots are drawn behind the circuit
+    pub grid: bool,
+    /// Pixels in a PNG to a pixel on the canvas
+    pub scale: u8,
+}
+
+impl Default for Picture {
+    fn default() -> Self {
+        Picture {
+            grid: false,
+            scale: 2,
+        }
+    }
+}
+
+impl Picture {
+    /// The next scale along, going back to 1 after 4
+    pub fn next_scale(&mut self) {
+        self.scale = self.scale % 4 + 1;
+    }
+}
+
+fn hex(color: &Color) -> String {
+    let (r, g, b, _) = color.as_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// A string of numbers for an SVG attribute, without needless decimals
+fn numbers(values: &[f64]) -> String {
+    let values: Vec<_> = values.iter().map(|v| format!("{}", v)).collect();
+    values.join(" ")
+}
+
+/// The components and wires to draw: the selected components if there are any, along with the
+/// wires that stay within a cell of them, or else everything
+fn chosen<'a>(
+    components: &'a im::OrdMap<usize, ComponentState>,
+    wires: &'a im::OrdMap<usize, WireState>,
+) -> (Vec<&'a ComponentState>, Vec<&'a WireState>) {
+    let selected: Vec<_> = components.values().filter(|c| c.is_selected()).collect();
+    if selected.is_empty() {
+        return (components.values().collect(), wires.values().collect());
+    }
+    let areas: Vec<Rect> = selected
+        .iter()
+        .map(|c| c.instance.bounding_rect().inflate(16.0, 16.0))
+        .collect();
+    let within = |point: Point| areas.iter().any(|area| area.contains(point));
+    let wires = wires
+        .values()
+        .filter(|wire| {
+            wire.segments.iter().all(|segment| {
+                let (a, b) = segment.ends();
+                within(a.to_canvas_space()) && within(b.to_canvas_space())
+            })
+        })
+        .collect();
+    (selected, wires)
+}
+
+/// Draws the circuit as an SVG image the way it looks on the canvas, with wires coloured by their
+/// values, but without the pin markers that are only there for editing. Only the selected
+/// components are drawn if there are any.
+pub fn svg(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+    picture: Picture,
+) -> Result<(String, Size), String> {
+    let (components, wires) = chosen(components, wires);
+    let rects = components
+        .iter()
+        .map(|c| c.instance.bounding_rect())
+        .chain(wires.iter().flat_map(|w| {
+            w.segments.iter().map(|s| {
+                let (a, b) = s.ends();
+                Rect::from_points(a.to_canvas_space(), b.to_canvas_space())
+            })
+        }));
+    let bounds = rects
+        .reduce(|a, b| a.union(b))
+        .ok_or_else(|| "There's nothing to draw".to_owned())?;
+    // a cell of margin all round, lined up with the grid so that the dots fall where they do on
+    // the canvas
+    let bounds = Rect::new(
+        (bounds.x0 / 16.0).floor() * 16.0 - 16.0,
+        (bounds.y0 / 16.0).floor() * 16.0 - 16.0,
+        (bounds.x1 / 16.0).ceil() * 16.0 + 16.0,
+        (bounds.y1 / 16.0).ceil() * 16.0 + 16.0,
+    );
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{}\">",
+        bounds.width(),
+        bounds.height(),
+        numbers(&[bounds.x0, bounds.y0, bounds.width(), bounds.height()])
+    )
+    .unwrap();
+    let area = format!(
+        "x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+        bounds.x0,
+        bounds.y0,
+        bounds.width(),
+        bounds.height()
+    );
+    writeln!(out, "  <rect {} fill=\"#ffffff\"/>", area).unwrap();
+    if picture.grid {
+        out.push_str(
+            "  <pattern id=\"dots\" width=\"16\" height=\"16\" patternUnits=\"userSpaceOnUse\">\n",
+        );
+        writeln!(
+            out,
+            "    <rect x=\"7\" y=\"7\" width=\"2\" height=\"2\" fill=\"{}\"/>",
+            hex(&Color::GRAY)
+        )
+        .unwrap();
+        out.push_str("  </pattern>\n");
+        writeln!(out, "  <rect {} fill=\"url(#dots)\"/>", area).unwrap();
+    }
+
+    for wire in wires.iter() {
+        let color = hex(&wire::color(wire.value));
+        for segment in wire.segments.iter() {
+            let (a, b) = segment.ends();
+            let (a, b) = (a.to_canvas_space(), b.to_canvas_space());
+            writeln!(
+                out,
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\" \
+                 stroke-linecap=\"square\"/>",
+                a.x, a.y, b.x, b.y, color
+            )
+            .unwrap();
+        }
+    }
+
+    for component in components.iter() {
+        let instance = &component.instance;
+        let origin = instance.bounding_rect().origin().to_vec2();
+        let transform = druid::Affine::translate(origin) * instance.rotate_about_anchor();
+        writeln!(
+            out,
+            "  <g transform=\"matrix({})\">",
+            numbers(&transform.as_coeffs())
+        )
+        .unwrap();
+        let size = instance.ty.size;
+        let outline = format!(
+            "    <rect x=\"1\" y=\"1\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#000000\" \
+             stroke-width=\"2\"/>",
+            size.width - 2.0,
+            size.height - 2.0
+        );
+        let text = |out: &mut String, at: Point, family: &str, text: &str| {
+            writeln!(
+                out,
+                "    <text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"12\" \
+                 text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>",
+                at.x,
+                at.y,
+                family,
+                escape(text)
+            )
+            .unwrap();
+        };
+        match instance.ty.symbol {
+            Symbol::Svg(_) => {
+                // the icon as it is, less its XML declaration
+                let source = icon(instance.ty.kind).unwrap_or("");
+                let start = source.find("<svg").unwrap_or(0);
+                writeln!(out, "    {}", source[start..].trim_end()).unwrap();
+            },
+            Symbol::Box => {
+                out.push_str(&outline);
+                out.push('\n');
+                let center = Point::new(size.width / 2.0, size.height / 2.0);
+                text(&mut out, center, "sans-serif", instance.ty.kind.name());
+            },
+            Symbol::Display => {
+                out.push_str(&outline);
+                out.push('\n');
+                let value = component
+                    .values
+                    .get(0)
+                    .copied()
+                    .unwrap_or_else(|| Value::zero(1));
+                match instance.ty.kind {
+                    ComponentKind::Input { width } => {
+                        let left = (size.width - width as f64 * 16.0) / 2.0;
+                        for i in 0..width {
+                            let bit = if value.bit(width - 1 - i) { "1" } else { "0" };
+                            let cell = Point::new(left + i as f64 * 16.0 + 8.0, size.height / 2.0);
+                            text(&mut out, cell, "sans-serif", bit);
+                        }
+                    },
+                    ComponentKind::Probe { radix } => {
+                        let center = Point::new(size.width / 2.0, size.height / 2.0);
+                        text(&mut out, center, "monospace", &value.format(radix));
+                    },
+                    ComponentKind::SevenSegment => {
+                        let lit = |i: usize| component.values.get(i).map_or(false, Value::is_high);
+                        let segments = (0..7).fold(0, |s, i| s | (lit(i) as u8) << i);
+                        digit(&mut out, size, segments, lit(7));
+                    },
+                    ComponentKind::HexDisplay => {
+                        let dp = component.values.get(1).map_or(false, Value::is_high);
+                        digit(&mut out, size, SEGMENTS[value.bits() as usize & 0xf], dp);
+                    },
+                    ComponentKind::DotMatrix { rows, cols } => {
+                        let columns = component
+                            .values
+                            .get(1)
+                            .copied()
+                            .unwrap_or_else(|| Value::zero(1));
+                        for r in 0..rows {
+                            for c in 0..cols {
+                                let lit = value.bit(r) && columns.bit(c);
+                                writeln!(
+                                    out,
+                                    "    <circle cx=\"{}\" cy=\"{}\" r=\"6\" fill=\"{}\"/>",
+                                    (c + 1) as f64 * 16.0,
+                                    (r + 1) as f64 * 16.0,
+                                    hex(if lit { &LIT } else { &UNLIT })
+                                )
+                                .unwrap();
+                            }
+                        }
+                    },
+                    ComponentKind::Keyboard | ComponentKind::Tty { .. } => {
+                        for (i, line) in component.state.text.split('\n').enumerate() {
+                            writeln!(
+                                out,
+                                "    <text x=\"8\" y=\"{}\" font-family=\"monospace\" \
+                                 font-size=\"12\" dominant-baseline=\"hanging\" \
+                                 xml:space=\"preserve\">{}</text>",
+                                i as f64 * 16.0 + 8.0,
+                                escape(&shown_line(line))
+                            )
+                            .unwrap();
+                        }
+                    },
+                    _ => {},
+                }
+            },
+        }
+        out.push_str("  </g>\n");
+    }
+    out.push_str("</svg>\n");
+    Ok((out, bounds.size()))
+}
+
+/// A seven-segment digit in the middle of a component of size `size`, drawn as it is on the canvas
+fn digit(out: &mut String, size: Size, segments: u8, dp: bool) {
+    let (lines, point, t) = segment_shapes(Rect::from_origin_size(Point::ORIGIN, size));
+    for (i, line) in lines.iter().enumerate() {
+        let color = if segments >> i & 1 == 1 { &LIT } else { &UNLIT };
+        writeln!(
+            out,
+            "    <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            line.p0.x,
+            line.p0.y,
+            line.p1.x,
+            line.p1.y,
+            hex(color),
+            t
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "    <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>",
+        point.center.x,
+        point.center.y,
+        point.radius,
+        hex(if dp { &LIT } else { &UNLIT })
+    )
+    .unwrap();
+}
+
+pub fn write_svg(
+    path: &Path,
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+    picture: Picture,
+) -> Result<(), String> {
+    let (text, _) = svg(components, wires, picture)?;
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
+
+/// Points the generic font families that pictures use at fonts that are there, trying the usual
+/// ones on each system before settling for any font at all
+fn find_fonts(fontdb: &mut usvg::fontdb::Database) {
+    let sans_serif = [
+        "Arial",
+        "Helvetica",
+        "DejaVu Sans",
+        "Liberation Sans",
+        "Noto Sans",
+    ];
+    let monospace = [
+        "Courier New",
+        "Menlo",
+        "DejaVu Sans Mono",
+        "Liberation Mono",
+    ];
+    let pick = |fontdb: &usvg::fontdb::Database, names: &[&str]| {
+        let faces = fontdb.faces();
+        names
+            .iter()
+            .find(|&&name| faces.iter().any(|face| face.family == name))
+            .map(|&name| name.to_owned())
+            .or_else(|| faces.first().map(|face| face.family.clone()))
+    };
+    if let Some(family) = pick(fontdb, &sans_serif) {
+        fontdb.set_sans_serif_family(family);
+    }
+    if let Some(family) = pick(fontdb, &monospace) {
+        fontdb.set_monospace_family(family);
+    }
+}
+
+/// Draws the circuit as `svg` does and renders that at the picture's scale, using the system's
+/// fonts for the labels
+pub fn write_png(
+    path: &Path,
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+    picture: Picture,
+) -> Result<(), String> {
+    let (text, size) = svg(components, wires, picture)?;
+    let mut options = usvg::Options::default();
+    options.fontdb.load_system_fonts();
+    find_fonts(&mut options.fontdb);
+    let tree = usvg::Tree::from_str(&text, &options.to_ref()).map_err(|e| e.to_string())?;
+
+    let scale = picture.scale as f64;
+    let (width, height) = (size.width * scale, size.height * scale);
+    let mut pixmap = tiny_skia::Pixmap::new(width.ceil() as u32, height.ceil() as u32)
+        .ok_or_else(|| "The picture is too big".to_owned())?;
+    resvg::render(
+        &tree,
+        usvg::FitTo::Zoom(scale as f32),
+        tiny_skia::Transform::default(),
+        pixmap.as_mut(),
+    )
+    .ok_or_else(|| "The picture couldn't be drawn".to_owned())?;
+    pixmap.save_png(path).map_err(|e| e.to_string())
+}
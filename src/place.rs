@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    canvas::Coords,
+    component::ComponentType,
+    expand::GateNetlist,
+    kind::{ComponentKind, PinType},
+    value::Radix,
+};
+
+/// What the pins of a circuit being laid out connect to
+pub enum Ports {
+    /// The pins of the component the circuit stands in for, left as bare wire ends to be joined
+    /// up to whatever the component was wired to
+    Of(ComponentKind),
+    /// The first `inputs` pins are fed by inputs and the rest are shown on probes, for a circuit
+    /// that stands on its own
+    Standalone { inputs: usize },
+}
+
+/// A gate-level circuit laid out on the grid, ready to be added to a canvas
+pub struct Layout {
+    pub components: Vec<(Coords, ComponentKind)>,
+    /// The segments of each wire, one wire per net
+    pub wires: Vec<Vec<(Coords, Coords)>>,
+    /// Where each pin of the circuit ended up, in the order of the netlist's pins
+    pub ports: Vec<Coords>,
+}
+
+/// Something that sits in a layer of the layout. Ports without a component are bare wire ends.
+struct Node {
+    kind: Option<ComponentKind>,
+    layer: usize,
+    /// Pin positions relative to the anchor, and the nets they connect to
+    pins: Vec<(Coords, usize, PinType)>,
+    /// For a node standing for a pin of the circuit, where that pin is relative to the anchor
+    port: Option<Coords>,
+}
+
+impl Node {
+    fn component(kind: ComponentKind, layer: usize, nets: &[Option<usize>]) -> Self {
+        let ty = ComponentType::new(kind);
+        let pins = ty
+            .pins
+            .iter()
+            .zip(nets)
+            .filter_map(|(pin, net)| Some((pin.pos, (*net)?, pin.ty)))
+            .collect();
+        Node {
+            kind: Some(kind),
+            layer,
+            pins,
+            port: None,
+        }
+    }
+
+    /// Marks the component's pin at `index` as the pin of the circuit it stands for
+    fn port(mut self, index: usize) -> Self {
+        let ty = ComponentType::new(self.kind.unwrap());
+        self.port = Some(ty.pins[index].pos);
+        self
+    }
+}
+
+/// Whether a pin is an input up the left side of a component rather than along its bottom
+fn is_left(&(pos, _, ty): &(Coords, usize, PinType)) -> bool {
+    ty == PinType::Input && pos.y < 1
+}
+
+/// Lays out the gates of `netlist` in layers by logic depth, with signals flowing north from the
+/// input ports at the bottom to the output ports at the top, and its bottom-left corner at
+/// `origin`. Multi-bit pins of a component become splitters and joiners so that they can be wired
+/// up to a bus.
+///
+/// Every net gets its own vertical rail to the left of the gates, and every pin reaches its rail
+/// along its own row in the channel next to it, so wires only ever cross without touching.
+pub fn place(netlist: &GateNetlist, ports: Ports, origin: Coords) -> Layout {
+    let mut nodes = Vec::new();
+    let mut driver_layer = vec![0; netlist.nets];
+
+    for gate in netlist.gates.iter() {
+        let layer = 1 + gate
+            .inputs
+            .iter()
+            .map(|&net| driver_layer[net])
+            .max()
+            .unwrap_or(0);
+        driver_layer[gate.output] = layer;
+        let mut nets: Vec<_> = gate.inputs.iter().map(|&net| Some(net)).collect();
+        nets.push(Some(gate.output));
+        nodes.push(Node::component(gate.kind, layer, &nets));
+    }
+
+    let top = nodes.iter().map(|n| n.layer).max().unwrap_or(0) + 1;
+    let types: Vec<PinType> = match ports {
+        Ports::Of(kind) => kind.pins().iter().map(|spec| spec.ty).collect(),
+        Ports::Standalone { inputs } => (0..netlist.pins.len())
+            .map(|i| {
+                if i < inputs {
+                    PinType::Input
+                } else {
+                    PinType::Output
+                }
+            })
+            .collect(),
+    };
+    let standalone = matches!(ports, Ports::Standalone { .. });
+    for (&ty, nets) in types.iter().zip(&netlist.pins) {
+        let node = match (ty, nets.len()) {
+            (PinType::Input, 1) if standalone => {
+                Node::component(ComponentKind::Input { width: 1 }, 0, &[Some(nets[0])]).port(0)
+            },
+            (PinType::Output, 1) if standalone => Node::component(
+                ComponentKind::Probe { radix: Radix::Hex },
+                top,
+                &[Some(nets[0])],
+            )
+            .port(0),
+            (PinType::Input, 1) => Node {
+                kind: None,
+                layer: 0,
+                pins: vec![(Coords::new(0, -2), nets[0], PinType::Output)],
+                port: Some(Coords::new(0, -2)),
+            },
+            (PinType::Input, width) => {
+                let mut bits = vec![None];
+                bits.extend(nets.iter().map(|&net| Some(net)));
+                Node::component(ComponentKind::Split { width: width as u8 }, 0, &bits).port(0)
+            },
+            (PinType::Output, 1) => Node {
+                kind: None,
+                layer: top,
+                pins: vec![(Coords::new(0, 1), nets[0], PinType::Input)],
+                port: Some(Coords::new(0, 1)),
+            },
+            (PinType::Output, width) => {
+                let mut bits: Vec<_> = nets.iter().map(|&net| Some(net)).collect();
+                bits.push(None);
+                Node::component(ComponentKind::Join { width: width as u8 }, top, &bits).port(width)
+            },
+        };
+        nodes.push(node);
+    }
+
+    // only nets with something at both ends need wiring
+    let mut connections = vec![0; netlist.nets];
+    for node in nodes.iter() {
+        for &(_, net, _) in node.pins.iter() {
+            connections[net] += 1;
+        }
+    }
+    let rails: BTreeMap<usize, isize> = (0..netlist.nets)
+        .filter(|&net| connections[net] > 1)
+        .enumerate()
+        .map(|(i, net)| (net, origin.x + i as isize))
+        .collect();
+    let left = origin.x + rails.len() as isize + 2;
+
+    // channel k runs between layers k and k + 1; outputs from below take its lower rows, inputs
+    // from above take its upper rows so that their stubs never overlap
+    let mut outputs_below = vec![0; top + 1];
+    let mut inputs_above = vec![0; top + 1];
+    for node in nodes.iter() {
+        for &(_, net, ty) in node.pins.iter() {
+            if rails.contains_key(&net) {
+                match ty {
+                    PinType::Output => outputs_below[node.layer] += 1,
+                    PinType::Input => inputs_above[node.layer - 1] += 1,
+                }
+            }
+        }
+    }
+    // gates stand two rows tall above their anchors, but boxed components can stand taller
+    let mut heights = vec![2; top + 1];
+    for node in nodes.iter() {
+        let height = -node.pins.iter().map(|p| p.0.y).min().unwrap_or(0);
+        heights[node.layer] = heights[node.layer].max(height);
+    }
+    let mut layer_rows = vec![origin.y - 1];
+    for k in 0..top {
+        let tracks = (outputs_below[k] + inputs_above[k]) as isize;
+        layer_rows.push(layer_rows[k] - heights[k] - 2 - tracks);
+    }
+
+    let mut layout = Layout {
+        components: Vec::new(),
+        wires: Vec::new(),
+        ports: Vec::new(),
+    };
+    let mut wires: BTreeMap<usize, Vec<(Coords, Coords)>> = BTreeMap::new();
+    let mut next_output_track = vec![0; top + 1];
+    let mut next_input_track = outputs_below.clone();
+    let mut cursor = vec![left; top + 1];
+    for node in nodes.iter() {
+        // inputs up the left side need a column each to get down past the ones below them
+        let left = node.pins.iter().filter(|p| is_left(p)).count() as isize;
+        let min_x = node.pins.iter().map(|p| p.0.x).min().unwrap_or(0).min(-1) - left;
+        let max_x = node.pins.iter().map(|p| p.0.x).max().unwrap_or(0).max(1);
+        let anchor = Coords::new(cursor[node.layer] - min_x, layer_rows[node.layer]);
+        cursor[node.layer] = anchor.x + max_x + 2;
+        if let Some(kind) = node.kind {
+            layout.components.push((anchor, kind));
+        }
+        if let Some(pos) = node.port {
+            layout.ports.push(anchor + pos);
+        }
+
+        for &(pos, net, ty) in node.pins.iter() {
+            let rail = match rails.get(&net) {
+                Some(&rail) => rail,
+                None => continue,
+            };
+            let (channel, track) = match ty {
+                PinType::Output => (node.layer, &mut next_output_track[node.layer]),
+                PinType::Input => (node.layer - 1, &mut next_input_track[node.layer - 1]),
+            };
+            let row = layer_rows[channel] - heights[channel] - 1 - *track;
+            *track += 1;
+
+            let mut pin = anchor + pos;
+            let segments = wires.entry(net).or_default();
+            if is_left(&(pos, net, ty)) {
+                let aside = Coords::new(pin.x - (1 - pos.y), pin.y);
+                segments.push((pin, aside));
+                pin = aside;
+            }
+            let turn = Coords::new(pin.x, row);
+            segments.push((pin, turn));
+            segments.push((turn, Coords::new(rail, row)));
+        }
+    }
+
+    for (net, mut segments) in wires {
+        let rows = segments.iter().map(|(_, end)| end.y);
+        let (top, bottom) = (rows.clone().min().unwrap(), rows.max().unwrap());
+        let rail = rails[&net];
+        segments.push((Coords::new(rail, top), Coords::new(rail, bottom)));
+        layout.wires.push(segments);
+    }
+    layout
+}
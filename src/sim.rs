@@ -0,0 +1,506 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
+
+use druid::{im, Data};
+
+use crate::{
+    canvas::Coords,
+    component::{ComponentState, ComponentType, Orientation},
+    kind::{ComponentKind, PinType, State},
+    value::Value,
+    wire::{WireSegment, WireState},
+};
+
+/// How many rounds of evaluation a change may ripple through before the circuit is taken not to
+/// settle
+const MAX_ITERATIONS: usize = 1000;
+/// How many of the last rounds before giving up are looked at to find what doesn't settle
+const LOOKBACK: usize = 100;
+
+impl Data for Value {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl Data for State {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// Something a net can be found from, which stays put when the circuit is edited
+#[derive(Clone, Copy, Data, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    Wire(usize),
+    /// A component id and the index of one of its pins
+    Pin(usize, usize),
+}
+
+/// The components and wires that were still changing when a circuit was given up on for not
+/// settling, like a NOT gate feeding itself
+#[derive(Clone, Data, Default)]
+pub struct Oscillation {
+    pub components: im::OrdSet<usize>,
+    pub wires: im::OrdSet<usize>,
+}
+
+/// What a circuit is wired up like, so it can be told when the netlist is out of date
+struct Shape {
+    components: Vec<(usize, Coords, Orientation, Rc<ComponentType>)>,
+    wires: Vec<(usize, Vec<(Coords, Coords)>)>,
+}
+
+impl PartialEq for Shape {
+    fn eq(&self, other: &Self) -> bool {
+        self.wires == other.wires
+            && self.components.len() == other.components.len()
+            && self
+                .components
+                .iter()
+                .zip(&other.components)
+                .all(|(a, b)| (a.0, a.1, a.2) == (b.0, b.1, b.2) && Rc::ptr_eq(&a.3, &b.3))
+    }
+}
+
+impl Shape {
+    fn of(
+        components: &im::OrdMap<usize, ComponentState>,
+        wires: &im::OrdMap<usize, WireState>,
+    ) -> Self {
+        Shape {
+            components: components
+                .iter()
+                .map(|(id, c)| {
+                    let instance = &c.instance;
+                    let ty = Rc::clone(&instance.ty);
+                    (*id, instance.coords, instance.orientation, ty)
+                })
+                .collect(),
+            wires: wires
+                .iter()
+                .map(|(id, w)| (*id, w.segments.iter().map(WireSegment::ends).collect()))
+                .collect(),
+        }
+    }
+}
+
+/// Finds which points are joined together, by a tree of representative points
+#[derive(Default)]
+pub struct Joins(BTreeMap<Coords, Coords>);
+
+impl Joins {
+    pub fn find(&mut self, c: Coords) -> Coords {
+        let parent = *self.0.entry(c).or_insert(c);
+        if parent == c {
+            c
+        } else {
+            let root = self.find(parent);
+            self.0.insert(c, root);
+            root
+        }
+    }
+
+    pub fn join(&mut self, a: Coords, b: Coords) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.0.insert(a, b);
+    }
+}
+
+/// Which net every pin and wire of a circuit is on. A pin or the end of a wire joins any wire
+/// that runs through it, but wires that only cross in the middle don't touch.
+pub struct Netlist {
+    shape: Shape,
+    pub nets: usize,
+    /// The net of each pin of each component, by component id
+    pub pins: BTreeMap<usize, Vec<usize>>,
+    /// The net of each wire, by wire id
+    pub wires: BTreeMap<usize, usize>,
+    /// The components with an input on each net
+    readers: Vec<BTreeSet<usize>>,
+    /// The outputs on each net, as component id and pin index
+    drivers: Vec<Vec<(usize, usize)>>,
+    /// The widest pin on each net
+    widths: Vec<u8>,
+    /// Output changes on their way through propagation delays, as component id, pin index and
+    /// value, by the time they arrive
+    pending: BTreeMap<u64, Vec<(usize, usize, Value)>>,
+    /// The value each output will have once everything pending has arrived
+    projected: BTreeMap<(usize, usize), Value>,
+}
+
+impl Netlist {
+    pub fn build(
+        components: &im::OrdMap<usize, ComponentState>,
+        wires: &im::OrdMap<usize, WireState>,
+    ) -> Self {
+        let mut joins = Joins::default();
+        let segments: Vec<_> = wires
+            .values()
+            .flat_map(|w| w.segments.iter().map(WireSegment::ends))
+            .collect();
+
+        // every segment of a wire is one conductor
+        for wire in wires.values() {
+            let ends: Vec<_> = wire.segments.iter().map(WireSegment::ends).collect();
+            for &(start, end) in ends.iter() {
+                joins.join(ends[0].0, start);
+                joins.join(ends[0].0, end);
+            }
+        }
+
+        let mut points: Vec<Coords> = segments.iter().flat_map(|&(a, b)| vec![a, b]).collect();
+        for component in components.values() {
+            let instance = &component.instance;
+            points.extend((0..instance.ty.pins.len()).map(|i| instance.pin_coords(i)));
+        }
+        for &point in points.iter() {
+            joins.find(point);
+            for &(start, end) in segments.iter() {
+                if WireSegment::runs_through(start, end, point) {
+                    joins.join(point, start);
+                }
+            }
+        }
+
+        let mut net_ids = BTreeMap::new();
+        let mut net_of = |c: Coords| {
+            let root = joins.find(c);
+            let next = net_ids.len();
+            *net_ids.entry(root).or_insert(next)
+        };
+        let pins: BTreeMap<_, _> = components
+            .iter()
+            .map(|(id, c)| {
+                let instance = &c.instance;
+                let nets = (0..instance.ty.pins.len())
+                    .map(|i| net_of(instance.pin_coords(i)))
+                    .collect::<Vec<_>>();
+                (*id, nets)
+            })
+            .collect();
+        let wire_nets: BTreeMap<_, _> = wires
+            .iter()
+            .filter_map(|(id, w)| Some((*id, net_of(w.segments.front()?.ends().0))))
+            .collect();
+
+        let nets = net_ids.len();
+        let mut readers = vec![BTreeSet::new(); nets];
+        let mut drivers = vec![Vec::new(); nets];
+        let mut widths = vec![1; nets];
+        for (id, component) in components.iter() {
+            for (i, (pin, &net)) in component.instance.ty.pins.iter().zip(&pins[id]).enumerate() {
+                match pin.ty {
+                    PinType::Input => {
+                        readers[net].insert(*id);
+                    },
+                    PinType::Output => drivers[net].push((*id, i)),
+                }
+                widths[net] = widths[net].max(pin.width);
+            }
+        }
+
+        Netlist {
+            shape: Shape::of(components, wires),
+            nets,
+            pins,
+            wires: wire_nets,
+            readers,
+            drivers,
+            widths,
+            pending: BTreeMap::new(),
+            projected: BTreeMap::new(),
+        }
+    }
+
+    /// Whether this still matches the circuit, or it has been edited since
+    pub fn is_current(
+        &self,
+        components: &im::OrdMap<usize, ComponentState>,
+        wires: &im::OrdMap<usize, WireState>,
+    ) -> bool {
+        self.shape == Shape::of(components, wires)
+    }
+
+    pub fn width(&self, net: usize) -> u8 {
+        self.widths[net]
+    }
+
+    /// The outputs driving a net, as component id and pin index
+    pub fn drivers(&self, net: usize) -> &[(usize, usize)] {
+        &self.drivers[net]
+    }
+
+    /// The net at a point on the grid, along with the pin or wire it was found on
+    pub fn net_at(
+        &self,
+        point: Coords,
+        components: &im::OrdMap<usize, ComponentState>,
+        wires: &im::OrdMap<usize, WireState>,
+    ) -> Option<(usize, Anchor)> {
+        for (id, component) in components.iter() {
+            let instance = &component.instance;
+            let pins = 0..instance.ty.pins.len();
+            if let Some(pin) = pins.into_iter().find(|&i| instance.pin_coords(i) == point) {
+                let anchor = Anchor::Pin(*id, pin);
+                return Some((self.net_of(anchor)?, anchor));
+            }
+        }
+        wires.iter().find_map(|(id, wire)| {
+            let on_wire = wire.segments.iter().any(|segment| {
+                let (start, end) = segment.ends();
+                WireSegment::runs_through(start, end, point)
+            });
+            if on_wire {
+                let anchor = Anchor::Wire(*id);
+                Some((self.net_of(anchor)?, anchor))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The net a pin or wire is on, or `None` if it has gone
+    pub fn net_of(&self, anchor: Anchor) -> Option<usize> {
+        match anchor {
+            Anchor::Wire(id) => self.wires.get(&id).copied(),
+            Anchor::Pin(id, pin) => self.pins.get(&id)?.get(pin).copied(),
+        }
+    }
+
+    /// The value on a net, given the values at the pins of every component. A net with no
+    /// drivers, or whose drivers have all let go of it, is floating.
+    pub fn value(&self, net: usize, components: &im::OrdMap<usize, ComponentState>) -> Value {
+        self.drivers[net]
+            .iter()
+            .filter_map(|&(id, pin)| components[&id].values.get(pin).copied())
+            .fold(Value::floating(self.widths[net]), Value::resolve)
+    }
+
+    /// Works out what a component does given the values on the nets, updating its state. Returns
+    /// the value at each of its pins.
+    fn evaluate(&self, id: usize, component: &mut ComponentState, values: &[Value]) -> Vec<Value> {
+        let ty = Rc::clone(&component.instance.ty);
+        let mut pin_values: Vec<Value> = ty
+            .pins
+            .iter()
+            .zip(&self.pins[&id])
+            .map(|(pin, &net)| match pin.ty {
+                // a pin on a net of another width can't make sense of it, though probes show
+                // whatever is on theirs
+                PinType::Input
+                    if values[net].width() != pin.width
+                        && !matches!(ty.kind, ComponentKind::Probe { .. }) =>
+                {
+                    Value::error(pin.width)
+                },
+                PinType::Input => values[net],
+                PinType::Output => Value::zero(pin.width),
+            })
+            .collect();
+        let inputs: Vec<_> = ty
+            .pins
+            .iter()
+            .zip(pin_values.iter())
+            .filter(|(pin, _)| pin.ty == PinType::Input)
+            .map(|(_, value)| *value)
+            .collect();
+
+        let mut state = component.state.clone();
+        let mut outputs = ty.kind.evaluate(&mut state, &inputs).into_iter();
+        for (i, pin) in ty.pins.iter().enumerate() {
+            if pin.ty == PinType::Output {
+                pin_values[i] = outputs.next().unwrap();
+            }
+        }
+        if state != component.state {
+            component.state = state;
+        }
+        pin_values
+    }
+
+    fn oscillation(&self, components: BTreeSet<usize>, nets: BTreeSet<usize>) -> Oscillation {
+        Oscillation {
+            components: components.into_iter().collect(),
+            wires: self
+                .wires
+                .iter()
+                .filter(|(_, net)| nets.contains(net))
+                .map(|(id, _)| *id)
+                .collect(),
+        }
+    }
+
+    fn update_wires(&self, values: &[Value], wires: &mut im::OrdMap<usize, WireState>) {
+        for (&id, &net) in self.wires.iter() {
+            let wire = wires.get_mut(&id).unwrap();
+            if wire.value != Some(values[net]) {
+                wire.value = Some(values[net]);
+            }
+        }
+    }
+
+    /// Evaluates components until their outputs stop changing, starting with every component so
+    /// that edits to the circuit are taken into account. Every component responds at once, so this
+    /// is the fast way of simulating. Gives up after `MAX_ITERATIONS` rounds if the circuit doesn't
+    /// settle, with what was still changing.
+    pub fn settle(
+        &self,
+        components: &mut im::OrdMap<usize, ComponentState>,
+        wires: &mut im::OrdMap<usize, WireState>,
+    ) -> Result<(), Oscillation> {
+        let mut values: Vec<_> = (0..self.nets)
+            .map(|net| self.value(net, components))
+            .collect();
+        let mut dirty: BTreeSet<usize> = components.keys().copied().collect();
+        let (mut unstable, mut unstable_nets) = (BTreeSet::new(), BTreeSet::new());
+
+        for round in 0..MAX_ITERATIONS {
+            if dirty.is_empty() {
+                break;
+            }
+            let looking = round >= MAX_ITERATIONS - LOOKBACK;
+            if looking {
+                unstable.extend(dirty.iter().copied());
+            }
+
+            // evaluate everything against the values from the last round, so that components
+            // triggered together (like registers on the same clock) all see the same inputs
+            let mut changed = BTreeSet::new();
+            for id in std::mem::take(&mut dirty) {
+                let component = components.get_mut(&id).unwrap();
+                let pin_values = self.evaluate(id, component, &values);
+                for (i, value) in pin_values.iter().enumerate() {
+                    let is_output = component.instance.ty.pins[i].ty == PinType::Output;
+                    if is_output && component.values.get(i) != Some(value) {
+                        changed.insert(self.pins[&id][i]);
+                    }
+                }
+                if component.values.iter().ne(pin_values.iter()) {
+                    component.values = pin_values.into();
+                }
+            }
+
+            for net in changed {
+                let value = self.value(net, components);
+                if values[net] != value {
+                    values[net] = value;
+                    dirty.extend(self.readers[net].iter().copied());
+                    if looking {
+                        unstable_nets.insert(net);
+                    }
+                }
+            }
+        }
+
+        self.update_wires(&values, wires);
+        if dirty.is_empty() {
+            Ok(())
+        } else {
+            Err(self.oscillation(unstable, unstable_nets))
+        }
+    }
+
+    /// Simulates from `from` up to but not including `until` with each component taking its
+    /// propagation delay to respond, so that glitches and races show up. Every component is
+    /// evaluated at `from` to take edits into account, and changes that haven't arrived by `until`
+    /// are kept for the next run. `changed` is told about every net whose value changes, and when.
+    ///
+    /// Returns the time of the last change, along with what was still changing if it gave up
+    /// because the circuit wouldn't settle at some point in time.
+    pub fn run(
+        &mut self,
+        components: &mut im::OrdMap<usize, ComponentState>,
+        wires: &mut im::OrdMap<usize, WireState>,
+        from: u64,
+        until: u64,
+        mut changed: impl FnMut(u64, usize, Value),
+    ) -> (u64, Result<(), Oscillation>) {
+        let mut values: Vec<_> = (0..self.nets)
+            .map(|net| self.value(net, components))
+            .collect();
+        let mut dirty: BTreeSet<usize> = components.keys().copied().collect();
+        let (mut now, mut rounds) = (from, 0);
+        let (mut unstable, mut unstable_nets) = (BTreeSet::new(), BTreeSet::new());
+        let mut settled = true;
+
+        loop {
+            let looking = rounds >= MAX_ITERATIONS - LOOKBACK;
+            if looking {
+                unstable.extend(dirty.iter().copied());
+            }
+            for id in std::mem::take(&mut dirty) {
+                let component = components.get_mut(&id).unwrap();
+                let delay = component.instance.delay();
+                let pin_values = self.evaluate(id, component, &values);
+                // outputs that haven't been driven yet start off floating
+                let pins = &component.instance.ty.pins;
+                let mut seen: Vec<Value> = if component.values.len() == pins.len() {
+                    component.values.iter().copied().collect()
+                } else {
+                    pins.iter().map(|pin| Value::floating(pin.width)).collect()
+                };
+                for (i, &value) in pin_values.iter().enumerate() {
+                    if pins[i].ty == PinType::Input {
+                        seen[i] = value;
+                        continue;
+                    }
+                    if self.projected.get(&(id, i)).unwrap_or(&seen[i]) != &value {
+                        self.projected.insert((id, i), value);
+                        let arrivals = self.pending.entry(now + delay).or_default();
+                        arrivals.push((id, i, value));
+                    }
+                }
+                if component.values.iter().ne(seen.iter()) {
+                    component.values = seen.into();
+                }
+            }
+
+            let next = match self.pending.keys().next() {
+                Some(&time) if time < until => time,
+                _ => break,
+            };
+            if next == now {
+                rounds += 1;
+            } else {
+                rounds = 0;
+                unstable.clear();
+                unstable_nets.clear();
+            }
+            if rounds >= MAX_ITERATIONS {
+                settled = false;
+                break;
+            }
+            now = next;
+
+            let mut touched = BTreeSet::new();
+            for (id, i, value) in self.pending.remove(&now).unwrap() {
+                let component = components.get_mut(&id).unwrap();
+                if component.values.get(i) != Some(&value) {
+                    component.values.set(i, value);
+                    touched.insert(self.pins[&id][i]);
+                }
+            }
+            for net in touched {
+                let value = self.value(net, components);
+                if values[net] != value {
+                    values[net] = value;
+                    changed(now, net, value);
+                    dirty.extend(self.readers[net].iter().copied());
+                    if looking {
+                        unstable_nets.insert(net);
+                    }
+                }
+            }
+        }
+
+        self.update_wires(&values, wires);
+        if settled {
+            (now, Ok(()))
+        } else {
+            (now, Err(self.oscillation(unstable, unstable_nets)))
+        }
+    }
+}
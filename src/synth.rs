@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+
+use druid::{
+    widget::{Button, CrossAxisAlignment, Either, Flex, Label, SizedBox, TextBox},
+    Lens, Widget, WidgetExt,
+};
+
+use crate::{
+    analysis::MAX_TABLE_INPUTS,
+    canvas::CanvasState,
+    expand::{Builder, GateNetlist, Style},
+    logic::{self, Expr},
+};
+
+/// A combinational function to build, with the minterms and don't cares of each output over
+/// `inputs`, the first input in the most significant bit
+pub struct Spec {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<Output>,
+}
+
+pub struct Output {
+    pub name: String,
+    pub minterms: Vec<u64>,
+    pub dont_cares: Vec<u64>,
+}
+
+/// Reads either lines of expressions, each named like `Y = (a & !b) | c` unless there is only
+/// one, or a truth table. The first line of a table names its columns, with a `|` between the
+/// inputs and outputs, or else the last column is the only output. Each line after gives a row
+/// of `0`s and `1`s. An `x` or `-` matches both values in an input and means the output
+/// doesn't matter in an output. Rows that are left out have every output low.
+pub fn parse(text: &str) -> Result<Spec, String> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        return Err("Type an expression or a truth table".to_owned());
+    }
+    let is_row = |line: &&str| line.chars().all(|c| "01xX-|,+: \t".contains(c));
+    if lines.len() > 1 && lines[1..].iter().all(is_row) {
+        parse_table(&lines)
+    } else {
+        parse_expressions(&lines)
+    }
+}
+
+fn parse_expressions(lines: &[&str]) -> Result<Spec, String> {
+    let mut exprs: Vec<(String, Expr)> = Vec::new();
+    for line in lines {
+        let (name, expr) = match line.split_once('=') {
+            Some((name, expr)) => (name.trim().to_owned(), expr),
+            None if lines.len() == 1 => ("Y".to_owned(), *line),
+            None => return Err(format!("Name the output of \"{}\", like Y = ...", line)),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("\"{}\" can't name an output", name));
+        }
+        if exprs.iter().any(|(other, _)| *other == name) {
+            return Err(format!("{} is given more than once", name));
+        }
+        let expr = logic::parse(expr).map_err(|e| format!("{}: {}", name, e))?;
+        exprs.push((name, expr));
+    }
+
+    let mut inputs: Vec<String> = Vec::new();
+    for (_, expr) in exprs.iter() {
+        for var in expr.vars() {
+            if !inputs.contains(&var) {
+                inputs.push(var);
+            }
+        }
+    }
+    if inputs.len() > MAX_TABLE_INPUTS as usize {
+        return Err(format!("Too many inputs, the most is {}", MAX_TABLE_INPUTS));
+    }
+    let outputs = exprs
+        .into_iter()
+        .map(|(name, expr)| Output {
+            name,
+            minterms: expr.minterms(&inputs),
+            dont_cares: Vec::new(),
+        })
+        .collect();
+    Ok(Spec { inputs, outputs })
+}
+
+fn parse_table(lines: &[&str]) -> Result<Spec, String> {
+    // bars around the edges, as in Markdown, don't split anything
+    let strip = |line: &str| {
+        line.trim_matches(|c: char| c == '|' || c.is_whitespace())
+            .to_owned()
+    };
+    let header = strip(lines[0]);
+    let names = |part: &str| -> Vec<String> {
+        part.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+    let (inputs, outputs) = match header.split_once('|') {
+        Some((inputs, outputs)) if !outputs.contains('|') => (names(inputs), names(outputs)),
+        // bars between every column, as in Markdown
+        _ => {
+            let mut inputs = names(&header.replace('|', " "));
+            let output = inputs.pop().unwrap_or_default();
+            (inputs, vec![output])
+        },
+    };
+    if inputs.is_empty() || outputs.is_empty() || outputs.iter().any(String::is_empty) {
+        return Err("Name at least one input and one output in the first line".to_owned());
+    }
+    if inputs.len() > MAX_TABLE_INPUTS as usize {
+        return Err(format!("Too many inputs, the most is {}", MAX_TABLE_INPUTS));
+    }
+    let columns = inputs.len() + outputs.len();
+
+    // what each output is for each combination of inputs, where it has been given
+    let mut given: Vec<BTreeMap<u64, char>> = vec![BTreeMap::new(); outputs.len()];
+    for line in lines[1..].iter() {
+        let cells: Vec<char> = line
+            .chars()
+            .filter(|c| !"|,+: \t".contains(*c))
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        // rules under a header, made only of dashes
+        if cells.iter().all(|&c| c == '-') && line.contains("--") {
+            continue;
+        }
+        if cells.len() != columns {
+            return Err(format!("\"{}\" should have {} cells", line, columns));
+        }
+        let (row_inputs, row_outputs) = cells.split_at(inputs.len());
+        let mut combinations = vec![0u64];
+        for &cell in row_inputs {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|c| match cell {
+                    '0' => vec![c << 1],
+                    '1' => vec![c << 1 | 1],
+                    _ => vec![c << 1, c << 1 | 1],
+                })
+                .collect();
+        }
+        for (values, &cell) in given.iter_mut().zip(row_outputs) {
+            let cell = if cell == '-' { 'x' } else { cell };
+            for &combination in combinations.iter() {
+                match values.insert(combination, cell) {
+                    Some(old) if old != cell => {
+                        return Err(format!("\"{}\" contradicts an earlier row", line))
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    let outputs = outputs
+        .into_iter()
+        .zip(given)
+        .map(|(name, values)| {
+            let with = |value| {
+                values
+                    .iter()
+                    .filter(|&(_, &v)| v == value)
+                    .map(|(&c, _)| c)
+                    .collect()
+            };
+            Output {
+                name,
+                minterms: with('1'),
+                dont_cares: with('x'),
+            }
+        })
+        .collect();
+    Ok(Spec { inputs, outputs })
+}
+
+/// Minimises each output to a sum of products and builds them all from gates of the given
+/// style, sharing any gates the outputs have in common. The circuit's pins are the inputs and
+/// then the outputs, one bit each.
+pub fn synthesise(spec: &Spec, style: Style) -> (GateNetlist, Vec<(String, Expr)>) {
+    let mut b = Builder::new(style);
+    let inputs: Vec<usize> = spec.inputs.iter().map(|_| b.net()).collect();
+    let mut built = BTreeMap::new();
+    let mut pins: Vec<Vec<usize>> = inputs.iter().map(|&net| vec![net]).collect();
+    let mut exprs = Vec::new();
+    for output in spec.outputs.iter() {
+        let cover = logic::minimise(
+            spec.inputs.len() as u8,
+            &output.minterms,
+            &output.dont_cares,
+        );
+        let expr = logic::sum_of_products(&cover, &spec.inputs);
+        pins.push(vec![build(&mut b, &expr, spec, &inputs, &mut built)]);
+        exprs.push((output.name.clone(), expr));
+    }
+    (b.finish(pins), exprs)
+}
+
+/// The net carrying the value of `expr`, building anything that hasn't been built already
+fn build(
+    b: &mut Builder,
+    expr: &Expr,
+    spec: &Spec,
+    inputs: &[usize],
+    built: &mut BTreeMap<String, usize>,
+) -> usize {
+    let key = expr.to_string();
+    if let Some(&net) = built.get(&key) {
+        return net;
+    }
+    let net = match expr {
+        Expr::Const(false) => b.zero(),
+        Expr::Const(true) => b.one(),
+        Expr::Var(name) => inputs[spec.inputs.iter().position(|v| v == name).unwrap()],
+        Expr::Not(inner) => {
+            let inner = build(b, inner, spec, inputs, built);
+            b.not(inner)
+        },
+        Expr::And(terms) | Expr::Or(terms) | Expr::Xor(terms) => {
+            let nets: Vec<usize> = terms
+                .iter()
+                .map(|t| build(b, t, spec, inputs, built))
+                .collect();
+            match expr {
+                Expr::And(_) => b.and_all(&nets),
+                Expr::Or(_) => b.or_all(&nets),
+                _ => {
+                    let (first, rest) = nets.split_first().unwrap();
+                    rest.iter().fold(*first, |a, &net| b.xor(a, net))
+                },
+            }
+        },
+    };
+    built.insert(key, net);
+    net
+}
+
+struct SynthesisText;
+
+impl Lens<CanvasState, String> for SynthesisText {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &CanvasState, f: F) -> V {
+        match data.synthesis() {
+            Some(text) => f(text),
+            None => f(&String::new()),
+        }
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut CanvasState, f: F) -> V {
+        match data.synthesis_mut() {
+            Some(text) => f(text),
+            None => f(&mut String::new()),
+        }
+    }
+}
+
+/// A box to type the function into, with a button for each style of circuit to build it from.
+/// It is empty while synthesis isn't open.
+pub fn panel() -> impl Widget<CanvasState> {
+    let build = |label: &str, style: Style| {
+        Button::new(label).on_click(move |_, data: &mut CanvasState, _| data.synthesise(style))
+    };
+    let bar = Flex::row()
+        .with_child(Label::new("Synthesise"))
+        .with_flex_spacer(1.0)
+        .with_child(
+            Button::new("Close").on_click(|_, data: &mut CanvasState, _| data.close_synthesis()),
+        );
+    let styles = Flex::row()
+        .with_child(build("Gates", Style::Mixed))
+        .with_child(build("NAND only", Style::NandOnly))
+        .with_child(build("NOR only", Style::NorOnly));
+    let panel = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(bar)
+        .with_flex_child(
+            TextBox::multiline()
+                .with_placeholder("Y = (a & !b) | c\nor a truth table")
+                .lens(SynthesisText)
+                .expand(),
+            1.0,
+        )
+        .with_child(styles)
+        .padding(4.0)
+        .fix_width(320.0);
+    Either::new(
+        |data: &CanvasState, _| data.synthesis().is_some(),
+        panel,
+        SizedBox::empty(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kind::State;
+    use crate::value::Value;
+
+    /// The outputs of a synthesised circuit for some inputs
+    fn run(netlist: &GateNetlist, inputs: &[bool]) -> Vec<bool> {
+        let mut nets = vec![Value::floating(1); netlist.nets];
+        for (pin, &input) in netlist.pins.iter().zip(inputs) {
+            nets[pin[0]] = Value::from_bool(input);
+        }
+        // enough passes over the gates for every value to get through
+        for _ in 0..netlist.gates.len() {
+            for gate in netlist.gates.iter() {
+                let values: Vec<_> = gate.inputs.iter().map(|&net| nets[net]).collect();
+                nets[gate.output] = gate.kind.evaluate(&mut State::default(), &values)[0];
+            }
+        }
+        netlist.pins[inputs.len()..]
+            .iter()
+            .map(|pin| nets[pin[0]].is_high())
+            .collect()
+    }
+
+    #[test]
+    fn parses_expressions() {
+        let spec = parse("S = a ^ b ^ c\nC = a b + c (a + b)").unwrap();
+        assert_eq!(spec.inputs, ["a", "b", "c"]);
+        assert_eq!(spec.outputs[0].name, "S");
+        assert_eq!(spec.outputs[0].minterms, [1, 2, 4, 7]);
+        assert_eq!(spec.outputs[1].minterms, [3, 5, 6, 7]);
+        assert!(parse("a & b").is_ok());
+        assert!(parse("a & b\nb").is_err());
+        assert!(parse("Y = a\nY = b").is_err());
+    }
+
+    #[test]
+    fn parses_tables() {
+        let spec = parse("a b | y z\n0 0 | 0 1\n0 1 | 1 -\n1 x | 1 0").unwrap();
+        assert_eq!(spec.inputs, ["a", "b"]);
+        assert_eq!(spec.outputs[0].minterms, [1, 2, 3]);
+        assert_eq!(spec.outputs[1].minterms, [0]);
+        assert_eq!(spec.outputs[1].dont_cares, [1]);
+
+        let spec = parse("| a | b | y |\n|---|---|---|\n| 1 | 1 | 1 |").unwrap();
+        assert_eq!(spec.inputs, ["a", "b"]);
+        assert_eq!(spec.outputs[0].name, "y");
+        assert_eq!(spec.outputs[0].minterms, [3]);
+
+        assert!(parse("a | y\n0 | 1\nx | 0").is_err());
+        assert!(parse("a | y\n0 1 1").is_err());
+    }
+
+    #[test]
+    fn synthesises_a_full_adder_in_every_style() {
+        let spec = parse("S = a ^ b ^ c\nC = a b + c (a + b)").unwrap();
+        for &style in [Style::Mixed, Style::NandOnly, Style::NorOnly].iter() {
+            let (netlist, exprs) = synthesise(&spec, style);
+            assert_eq!(exprs.len(), 2);
+            for inputs in 0..8 {
+                let bits: Vec<bool> = (0..3).map(|i| inputs >> (2 - i) & 1 == 1).collect();
+                let ones = bits.iter().filter(|&&b| b).count();
+                assert_eq!(run(&netlist, &bits), [ones % 2 == 1, ones >= 2]);
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use druid::{im, FileSpec, Point, Selector};
+
+use crate::{
+    canvas::Coords,
+    component::{ComponentState, Orientation, Symbol},
+    kind::ComponentKind,
+    wire::{WireSegment, WireState},
+};
+
+pub const EXPORT_TIKZ: Selector<druid::FileInfo> = Selector::new("logicism/export-tikz");
+
+pub const TIKZ: FileSpec = FileSpec::new("CircuiTikZ picture", &["tex"]);
+
+/// A point on the grid in TikZ's coordinates, where y goes up
+fn point(c: Coords) -> String {
+    format!("({},{})", c.x, -c.y)
+}
+
+/// A point on the canvas in TikZ's coordinates, measured in cells of the grid
+fn canvas_point(p: Point) -> String {
+    format!("({},{})", (p.x - 8.0) / 16.0, -(p.y - 8.0) / 16.0)
+}
+
+/// The CircuiTikZ shape for a gate and the anchors for its pins in order, for the kinds that
+/// have one
+fn port(kind: ComponentKind) -> Option<(&'static str, &'static [&'static str])> {
+    const TWO: &[&str] = &["in 1", "in 2", "out"];
+    match kind {
+        ComponentKind::Not => Some(("not port", &["in", "out"])),
+        ComponentKind::And => Some(("and port", TWO)),
+        ComponentKind::Or => Some(("or port", TWO)),
+        ComponentKind::Nand => Some(("nand port", TWO)),
+        ComponentKind::Nor => Some(("nor port", TWO)),
+        ComponentKind::Xor => Some(("xor port", TWO)),
+        _ => None,
+    }
+}
+
+/// How far a gate facing `orientation` is turned from CircuiTikZ's, which faces east
+fn rotation(orientation: Orientation) -> i32 {
+    match orientation {
+        Orientation::East => 0,
+        Orientation::North => 90,
+        Orientation::West => 180,
+        Orientation::South => 270,
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            },
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '\\' => out.push_str("\\textbackslash{}"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The segments of a wire joined up into as few paths as they go end to end
+fn paths(segments: &im::Vector<WireSegment>) -> Vec<Vec<Coords>> {
+    let mut paths: Vec<Vec<Coords>> = Vec::new();
+    for segment in segments.iter() {
+        let (a, b) = segment.ends();
+        match paths.last_mut() {
+            Some(path) if path.last() == Some(&a) => path.push(b),
+            _ => paths.push(vec![a, b]),
+        }
+    }
+    paths
+}
+
+/// A `tikzpicture` of the circuit for documents that load the circuitikz package, with a cell of
+/// the grid to half a centimetre. Gates are drawn with CircuiTikZ's shapes, joined to where their
+/// pins are on the grid by short leads since the shapes aren't quite the size of ours, and
+/// everything else as a labelled box.
+pub fn tikz(
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> String {
+    let mut out = String::new();
+    out.push_str("% needs \\usepackage{circuitikz}\n");
+    out.push_str("\\begin{tikzpicture}[x=0.5cm, y=0.5cm]\n");
+
+    for (&id, component) in components.iter() {
+        let instance = &component.instance;
+        let name = instance.name(id);
+        let rect = instance.bounding_rect();
+        match (port(instance.ty.kind), &instance.ty.symbol) {
+            (Some((shape, anchors)), _) => {
+                writeln!(
+                    out,
+                    "  \\draw {} node[{}, rotate={}] ({}) {{}};",
+                    canvas_point(rect.center()),
+                    shape,
+                    rotation(instance.orientation),
+                    name
+                )
+                .unwrap();
+                // leads run along the gate's way and then across to the pin
+                let turn = match instance.orientation {
+                    Orientation::North | Orientation::South => "-|",
+                    Orientation::East | Orientation::West => "|-",
+                };
+                for (pin, anchor) in anchors.iter().enumerate() {
+                    writeln!(
+                        out,
+                        "  \\draw ({}.{}) {} {};",
+                        name,
+                        anchor,
+                        turn,
+                        point(instance.pin_coords(pin))
+                    )
+                    .unwrap();
+                }
+            },
+            (None, symbol) => {
+                let label = match symbol {
+                    Symbol::Display => name.clone(),
+                    _ => instance.ty.kind.name().to_owned(),
+                };
+                writeln!(
+                    out,
+                    "  \\draw {} rectangle {} node[midway] {{\\small {}}};",
+                    canvas_point(rect.origin()),
+                    canvas_point(Point::new(rect.x1, rect.y1)),
+                    escape(&label)
+                )
+                .unwrap();
+            },
+        }
+    }
+
+    // a dot wherever three or more wire ends meet, or a wire ends partway along another
+    let mut meetings: BTreeMap<Coords, usize> = BTreeMap::new();
+    let segments: Vec<(Coords, Coords)> = wires
+        .values()
+        .flat_map(|wire| wire.segments.iter().map(WireSegment::ends))
+        .collect();
+    for &(a, b) in segments.iter() {
+        for &end in [a, b].iter() {
+            *meetings.entry(end).or_default() += 1;
+        }
+    }
+    for (&point, count) in meetings.iter_mut() {
+        for &(a, b) in segments.iter() {
+            if a != point && b != point && WireSegment::runs_through(a, b, point) {
+                *count += 2;
+            }
+        }
+    }
+
+    for wire in wires.values() {
+        for path in paths(&wire.segments) {
+            let points: Vec<_> = path.into_iter().map(point).collect();
+            writeln!(out, "  \\draw {};", points.join(" -- ")).unwrap();
+        }
+    }
+    for (&at, &count) in meetings.iter() {
+        if count >= 3 {
+            writeln!(out, "  \\node[circ] at {} {{}};", point(at)).unwrap();
+        }
+    }
+    out.push_str("\\end{tikzpicture}\n");
+    out
+}
+
+pub fn write_tikz(
+    path: &Path,
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Result<(), String> {
+    std::fs::write(path, tikz(components, wires)).map_err(|e| e.to_string())
+}
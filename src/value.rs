@@ -0,0 +1,149 @@
+/// How to show a value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Radix {
+    Binary,
+    Decimal,
+    Hex,
+}
+
+impl Radix {
+    /// The radix after this one, to cycle through them all
+    pub fn next(self) -> Self {
+        match self {
+            Radix::Binary => Radix::Decimal,
+            Radix::Decimal => Radix::Hex,
+            Radix::Hex => Radix::Binary,
+        }
+    }
+}
+
+/// A logic value carried by a pin or a net, up to 64 bits wide. Each bit is either low, high,
+/// floating because nothing drives it, or an error because it is driven both ways at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Value {
+    width: u8,
+    /// The high bits, which never include floating or error bits
+    bits: u64,
+    floating: u64,
+    error: u64,
+}
+
+impl Value {
+    pub fn new(width: u8, bits: u64) -> Self {
+        Value {
+            width,
+            bits: bits & Value::mask(width),
+            floating: 0,
+            error: 0,
+        }
+    }
+
+    /// A value with nothing driving any of its bits
+    pub fn floating(width: u8) -> Self {
+        Value {
+            floating: Value::mask(width),
+            ..Value::zero(width)
+        }
+    }
+
+    pub fn error(width: u8) -> Self {
+        Value {
+            error: Value::mask(width),
+            ..Value::zero(width)
+        }
+    }
+
+    pub fn zero(width: u8) -> Self {
+        Value::new(width, 0)
+    }
+
+    pub fn from_bool(b: bool) -> Self {
+        Value::new(1, b as u64)
+    }
+
+    /// All the bits that fit in a value of the given width
+    pub fn mask(width: u8) -> u64 {
+        if width >= 64 {
+            u64::MAX
+        } else {
+            (1 << width) - 1
+        }
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    pub fn bit(&self, i: u8) -> bool {
+        i < self.width && self.bits >> i & 1 == 1
+    }
+
+    /// Whether any bit is high. Floating and error bits read as low.
+    pub fn is_high(&self) -> bool {
+        self.bits != 0
+    }
+
+    pub fn is_floating(&self) -> bool {
+        self.floating == Value::mask(self.width)
+    }
+
+    pub fn has_error(&self) -> bool {
+        self.error != 0
+    }
+
+    /// Whether every bit is low or high, with none floating or in error
+    pub fn is_defined(&self) -> bool {
+        self.error | self.floating == 0
+    }
+
+    /// Shows the value in `radix`, with `Z` for floating digits and `X` for errors or digits that
+    /// are only partly floating
+    pub fn format(&self, radix: Radix) -> String {
+        let digit = |mask: u64, shift: u8| {
+            if self.error & mask != 0 {
+                'X'
+            } else if self.floating & mask == mask {
+                'Z'
+            } else if self.floating & mask != 0 {
+                'X'
+            } else {
+                std::char::from_digit(((self.bits & mask) >> shift) as u32, 16).unwrap()
+            }
+        };
+        match radix {
+            Radix::Binary => (0..self.width).rev().map(|i| digit(1 << i, i)).collect(),
+            Radix::Decimal if self.is_floating() => "Z".to_owned(),
+            Radix::Decimal if self.error | self.floating != 0 => "X".to_owned(),
+            Radix::Decimal => self.bits.to_string(),
+            Radix::Hex => (0..(self.width + 3) / 4)
+                .rev()
+                .map(|n| {
+                    let shift = 4 * n;
+                    digit(Value::mask(self.width) & 0xf << shift, shift)
+                })
+                .collect(),
+        }
+    }
+
+    /// The value on a net driven by both `self` and `other`. Floating bits give way to driven
+    /// ones, and bits driven both ways become errors, as does everything if the widths differ.
+    pub fn resolve(self, other: Value) -> Value {
+        if self.width != other.width {
+            return Value::error(self.width.max(other.width));
+        }
+        let driven = |v: Value| !v.floating & Value::mask(v.width);
+        let both = driven(self) & driven(other);
+        let error = self.error | other.error | both & (self.bits ^ other.bits);
+        let floating = self.floating & other.floating;
+        Value {
+            width: self.width,
+            bits: (self.bits | other.bits) & !error & !floating,
+            floating,
+            error,
+        }
+    }
+}
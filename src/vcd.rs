@@ -0,0 +1,146 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use druid::{FileSpec, Selector};
+
+use crate::{
+    value::{Radix, Value},
+    waveform::Trace,
+};
+
+pub const EXPORT_VCD: Selector<druid::FileInfo> = Selector::new("logicism/export-vcd");
+
+pub const VCD: FileSpec = FileSpec::new("Value Change Dump", &["vcd"]);
+
+/// The scope everything is declared in
+const TOP: &str = "logicism";
+
+/// The short code a variable goes by in the body of a dump, made of printable characters
+fn identifier(mut n: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0 {
+            return code;
+        }
+        n -= 1;
+    }
+}
+
+fn change(value: Value, code: &str) -> String {
+    let bits = value.format(Radix::Binary).to_lowercase();
+    if value.width() == 1 {
+        format!("{}{}", bits, code)
+    } else {
+        format!("b{} {}", bits, code)
+    }
+}
+
+/// The scopes and name a trace's name is split into, leaving out any that are blank
+fn scopes(name: &str) -> Vec<String> {
+    name.split('.')
+        .map(|part| part.split_whitespace().collect::<Vec<_>>().join("_"))
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Dumps the history of every trace up to `end`. Names are split into scopes at dots, so pins
+/// named after their components end up in a scope for each component. Traces whose names are blank
+/// are named as they were when they were added.
+pub fn vcd(traces: &[Trace], end: u64) -> String {
+    let mut out = String::new();
+    writeln!(out, "$version Logicism $end").unwrap();
+    writeln!(out, "$timescale 1ns $end").unwrap();
+
+    let mut vars: Vec<(Vec<String>, usize)> = traces
+        .iter()
+        .enumerate()
+        .map(|(i, trace)| {
+            let mut path = scopes(&trace.name);
+            if path.is_empty() {
+                path = scopes(&trace.default_name);
+            }
+            if path.is_empty() {
+                path.push(format!("trace{}", i));
+            }
+            (path, i)
+        })
+        .collect();
+    vars.sort();
+
+    let mut scope: Vec<String> = vec![TOP.to_owned()];
+    writeln!(out, "$scope module {} $end", TOP).unwrap();
+    for (path, i) in vars.iter() {
+        let (name, parents) = path.split_last().unwrap();
+        let common = scope[1..]
+            .iter()
+            .zip(parents)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while scope.len() > common + 1 {
+            scope.pop();
+            writeln!(out, "$upscope $end").unwrap();
+        }
+        for parent in &parents[common..] {
+            writeln!(out, "$scope module {} $end", parent).unwrap();
+            scope.push(parent.clone());
+        }
+        let width = traces[*i].changes.front().map_or(1, |c| c.1.width());
+        let range = if width == 1 {
+            String::new()
+        } else {
+            format!(" [{}:0]", width - 1)
+        };
+        writeln!(
+            out,
+            "$var wire {} {} {}{} $end",
+            width,
+            identifier(*i),
+            name,
+            range
+        )
+        .unwrap();
+    }
+    for _ in 0..scope.len() {
+        writeln!(out, "$upscope $end").unwrap();
+    }
+    writeln!(out, "$enddefinitions $end").unwrap();
+
+    // every change, in order of time
+    let mut changes: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    for (i, trace) in traces.iter().enumerate() {
+        let code = identifier(i);
+        let width = trace.changes.front().map_or(1, |c| c.1.width());
+        if trace.changes.front().map_or(true, |c| c.0 > 0) {
+            changes
+                .entry(0)
+                .or_default()
+                .push(change(Value::error(width), &code));
+        }
+        for &(time, value) in trace.changes.iter() {
+            changes.entry(time).or_default().push(change(value, &code));
+        }
+    }
+    let last = changes.keys().next_back().copied();
+    for (time, lines) in changes {
+        writeln!(out, "#{}", time).unwrap();
+        if time == 0 {
+            writeln!(out, "$dumpvars").unwrap();
+        }
+        for line in lines {
+            writeln!(out, "{}", line).unwrap();
+        }
+        if time == 0 {
+            writeln!(out, "$end").unwrap();
+        }
+    }
+    // so that viewers show how long the last values lasted
+    if last.map_or(false, |last| last < end) {
+        writeln!(out, "#{}", end).unwrap();
+    }
+    out
+}
+
+pub fn write_vcd(path: &Path, traces: &[Trace], end: u64) -> Result<(), String> {
+    std::fs::write(path, vcd(traces, end)).map_err(|e| e.to_string())
+}
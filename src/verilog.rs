@@ -0,0 +1,381 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use druid::{im, FileSpec, Selector};
+
+use crate::{
+    component::ComponentState,
+    hdl::{self, Cell, Design},
+    kind::{ComponentKind, PinType, ShiftMode},
+    wire::WireState,
+};
+
+pub const EXPORT_VERILOG: Selector<druid::FileInfo> = Selector::new("logicism/export-verilog");
+
+pub const VERILOG: FileSpec = FileSpec::new("Verilog", &["v"]);
+
+fn net(net: usize) -> String {
+    format!("n{}", net)
+}
+
+/// The range of a vector `width` bits wide, or nothing for a single bit
+fn range(width: u8) -> String {
+    if width == 1 {
+        String::new()
+    } else {
+        format!("[{}:0] ", width - 1)
+    }
+}
+
+fn literal(width: u8, value: u64) -> String {
+    format!("{}'h{:x}", width, value)
+}
+
+/// The primitive a gate is written as, if it is one
+fn primitive(kind: ComponentKind) -> Option<&'static str> {
+    match kind {
+        ComponentKind::Not => Some("not"),
+        ComponentKind::And => Some("and"),
+        ComponentKind::Or => Some("or"),
+        ComponentKind::Nand => Some("nand"),
+        ComponentKind::Nor => Some("nor"),
+        ComponentKind::Xor => Some("xor"),
+        _ => None,
+    }
+}
+
+/// The statements inside the module for a component, in terms of its pin names
+fn module_body(cell: &Cell) -> String {
+    match cell.kind {
+        ComponentKind::Mux { select, width } => {
+            let mut body = format!("    reg {}y;\n    always @* begin\n", range(width));
+            body.push_str("        case (S)\n");
+            for i in 0..1 << select {
+                body.push_str(&format!("            {}: y = D{};\n", i, i));
+            }
+            body.push_str("        endcase\n    end\n    assign Y = y;\n");
+            body
+        },
+        ComponentKind::Demux { select, width } => (0..1 << select)
+            .map(|i| {
+                format!(
+                    "    assign Y{} = S == {} ? D : {};\n",
+                    i,
+                    i,
+                    literal(width, 0)
+                )
+            })
+            .collect(),
+        ComponentKind::Decoder { select } => (0..1 << select)
+            .map(|i| format!("    assign Y{} = A == {};\n", i, i))
+            .collect(),
+        ComponentKind::PriorityEncoder { select } => {
+            let n = 1 << select;
+            let mut y = literal(select, 0);
+            for i in 0..n {
+                y = format!("D{} ? {} : {}", i, literal(select, i), y);
+            }
+            let any: Vec<_> = (0..n).map(|i| format!("D{}", i)).collect();
+            format!(
+                "    assign Y = {};\n    assign V = {};\n",
+                y,
+                any.join(" | ")
+            )
+        },
+        ComponentKind::FullAdder | ComponentKind::Adder { .. } => {
+            "    assign {Cout, S} = A + B + Cin;\n".to_owned()
+        },
+        ComponentKind::Subtractor { width } => format!(
+            "    wire [{w}:0] subtrahend = {{1'b0, B}} + Bin;\n    \
+             assign D = A - subtrahend[{top}:0];\n    \
+             assign Bout = {{1'b0, A}} < subtrahend;\n",
+            w = width,
+            top = width - 1
+        ),
+        ComponentKind::Comparator { .. } => {
+            "    assign LT = A < B;\n    assign EQ = A == B;\n    assign GT = A > B;\n".to_owned()
+        },
+        ComponentKind::Multiplier { .. } => "    assign P = A * B;\n".to_owned(),
+        ComponentKind::Shifter { width } => format!(
+            "    reg [{top}:0] y;\n    \
+             always @* begin\n        \
+                 case (OP)\n            \
+                     2'd0: y = S < {w} ? A << S : 0;\n            \
+                     2'd1: y = S < {w} ? A >> S : 0;\n            \
+                     2'd2: y = $signed(A) >>> (S < {w} ? S : {top});\n            \
+                     default: y = A << (S % {w}) | A >> (({w} - S % {w}) % {w});\n        \
+                 endcase\n    \
+             end\n    \
+             assign Y = y;\n",
+            w = width,
+            top = width - 1
+        ),
+        ComponentKind::Alu { width } => format!(
+            "    wire [{w}:0] sum = {{1'b0, A}} + B;\n    \
+             reg [{top}:0] y;\n    \
+             always @* begin\n        \
+                 case (OP)\n            \
+                     3'd0: y = sum[{top}:0];\n            \
+                     3'd1: y = A - B;\n            \
+                     3'd2: y = A & B;\n            \
+                     3'd3: y = A | B;\n            \
+                     3'd4: y = A ^ B;\n            \
+                     3'd5: y = ~A;\n            \
+                     3'd6: y = A < B;\n            \
+                     default: y = B;\n        \
+                 endcase\n    \
+             end\n    \
+             assign Y = y;\n    \
+             assign C = OP == 3'd0 ? sum[{w}] : OP == 3'd1 ? A < B : 1'b0;\n    \
+             assign Z = y == 0;\n",
+            w = width,
+            top = width - 1
+        ),
+        ComponentKind::Register { width } => format!(
+            "    reg {r}q = 0;\n    \
+             always @(posedge CLK or posedge RST)\n        \
+                 if (RST) q <= 0;\n        \
+                 else if (EN) q <= D;\n    \
+             assign Q = q;\n",
+            r = range(width)
+        ),
+        ComponentKind::Counter { width } => format!(
+            "    reg {r}q = 0;\n    \
+             always @(posedge CLK or posedge RST)\n        \
+                 if (RST) q <= 0;\n        \
+                 else if (LD) q <= D;\n        \
+                 else if (EN) q <= UP ? q + 1 : q - 1;\n    \
+             assign Q = q;\n    \
+             assign TC = EN && q == (UP ? {ones} : 0);\n",
+            r = range(width),
+            ones = literal(width, u64::MAX >> (64 - width))
+        ),
+        ComponentKind::ShiftRegister { width, mode } => {
+            let up = |bit: &str| {
+                if width == 1 {
+                    bit.to_owned()
+                } else {
+                    format!("{{q[{}:0], {}}}", width - 2, bit)
+                }
+            };
+            let down = if width == 1 {
+                "SR".to_owned()
+            } else {
+                format!("{{SR, q[{}:1]}}", width - 1)
+            };
+            let step = match mode {
+                ShiftMode::Sipo => format!("else if (EN) q <= {};", up("SI")),
+                ShiftMode::Piso => format!("else if (LD) q <= D;\n        else q <= {};", up("SI")),
+                ShiftMode::Universal => format!(
+                    "else case (MODE)\n            \
+                         2'd1: q <= {};\n            \
+                         2'd2: q <= {};\n            \
+                         2'd3: q <= D;\n            \
+                         default: q <= q;\n        \
+                     endcase",
+                    up("SL"),
+                    down
+                ),
+            };
+            let output = match mode {
+                ShiftMode::Piso if width == 1 => "assign SO = q;".to_owned(),
+                ShiftMode::Piso => format!("assign SO = q[{}];", width - 1),
+                _ => "assign Q = q;".to_owned(),
+            };
+            format!(
+                "    reg {r}q = 0;\n    \
+                 always @(posedge CLK or posedge RST)\n        \
+                     if (RST) q <= 0;\n        \
+                     {step}\n    \
+                 {output}\n",
+                r = range(width),
+                step = step,
+                output = output
+            )
+        },
+        ComponentKind::Rom { address, data } | ComponentKind::Ram { address, data, .. } => {
+            let words = 1u64 << address;
+            let mut body = format!(
+                "    reg {}mem [0:{}];\n    integer i;\n    initial begin\n        \
+                 for (i = 0; i < {}; i = i + 1) mem[i] = 0;\n",
+                range(data),
+                words - 1,
+                words
+            );
+            for (i, &word) in cell.contents.iter().enumerate() {
+                if word != 0 && (i as u64) < words {
+                    body.push_str(&format!("        mem[{}] = {};\n", i, literal(data, word)));
+                }
+            }
+            body.push_str("    end\n");
+            match cell.kind {
+                ComponentKind::Ram { sync: true, .. } => {
+                    body.push_str("    always @(posedge CLK)\n        if (WE) mem[A] <= D;\n")
+                },
+                ComponentKind::Ram { sync: false, .. } => body.push_str(
+                    "    // written whenever WE is high, which synthesises to latches\n    \
+                     always @*\n        if (WE) mem[A] = D;\n",
+                ),
+                _ => {},
+            }
+            body.push_str("    assign Q = mem[A];\n");
+            body
+        },
+        ComponentKind::TriState { width } => {
+            format!("    assign Y = EN ? A : {{{}{{1'bz}}}};\n", width)
+        },
+        ComponentKind::TransmissionGate { width } => format!(
+            "    assign Y = N && !P ? A : !N && P ? {{{w}{{1'bz}}}} : {{{w}{{1'bx}}}};\n",
+            w = width
+        ),
+        _ => String::new(),
+    }
+}
+
+/// The definition of the module a component is an instance of, with ports named after its pins
+fn module(cell: &Cell) -> String {
+    let mut out = format!("module {} (\n", hdl::module_name(cell));
+    let ports: Vec<_> = cell
+        .kind
+        .pins()
+        .iter()
+        .map(|pin| {
+            let direction = match pin.ty {
+                PinType::Input => "input",
+                PinType::Output => "output",
+            };
+            format!("    {} wire {}{}", direction, range(pin.width), pin.name)
+        })
+        .collect();
+    out.push_str(&ports.join(",\n"));
+    out.push_str("\n);\n");
+    out.push_str(&module_body(cell));
+    out.push_str("endmodule\n");
+    out
+}
+
+/// What a component turns into inside the top module, which is a continuous assignment for
+/// constants and for splitting and joining buses, a primitive for a gate and an instance of a
+/// module for anything else
+fn instance(cell: &Cell) -> String {
+    let pins = &cell.pins;
+    if let Some(primitive) = primitive(cell.kind) {
+        // primitives take their output first
+        let (output, inputs) = pins.split_last().unwrap();
+        let mut terminals = vec![net(output.net)];
+        terminals.extend(inputs.iter().map(|pin| net(pin.net)));
+        return format!(
+            "    {} {} ({});\n",
+            primitive,
+            cell.name,
+            terminals.join(", ")
+        );
+    }
+    match cell.kind {
+        ComponentKind::Constant { width, value } => format!(
+            "    assign {} = {}; // {}\n",
+            net(pins[0].net),
+            literal(width, value),
+            cell.name
+        ),
+        ComponentKind::Split { .. } => {
+            let mut out = String::new();
+            for (i, pin) in pins[1..].iter().enumerate() {
+                writeln!(
+                    out,
+                    "    assign {} = {}[{}]; // {}",
+                    net(pin.net),
+                    net(pins[0].net),
+                    i,
+                    cell.name
+                )
+                .unwrap();
+            }
+            out
+        },
+        ComponentKind::Join { .. } => {
+            let (output, bits) = pins.split_last().unwrap();
+            let bits: Vec<_> = bits.iter().rev().map(|pin| net(pin.net)).collect();
+            format!(
+                "    assign {} = {{{}}}; // {}\n",
+                net(output.net),
+                bits.join(", "),
+                cell.name
+            )
+        },
+        _ => {
+            let connections: Vec<_> = pins
+                .iter()
+                .map(|pin| format!(".{}({})", pin.name, net(pin.net)))
+                .collect();
+            format!(
+                "    {} {} ({});\n",
+                hdl::module_name(cell),
+                cell.name,
+                connections.join(", ")
+            )
+        },
+    }
+}
+
+/// A structural Verilog module for the circuit, called `name`, followed by the modules for the
+/// components in it that aren't gates
+pub fn verilog(design: &Design, name: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "// Written by Logicism").unwrap();
+    writeln!(out, "`default_nettype none\n").unwrap();
+
+    let ports: Vec<_> = design
+        .ports
+        .iter()
+        .map(|port| {
+            let direction = match port.ty {
+                PinType::Input => "input",
+                PinType::Output => "output",
+            };
+            format!("    {} wire {}{}", direction, range(port.width), port.name)
+        })
+        .collect();
+    if ports.is_empty() {
+        writeln!(out, "module {};", name).unwrap();
+    } else {
+        writeln!(out, "module {} (\n{}\n);", name, ports.join(",\n")).unwrap();
+    }
+    for (&n, &width) in design.nets.iter() {
+        writeln!(out, "    wire {}{};", range(width), net(n)).unwrap();
+    }
+    out.push('\n');
+    for port in design.ports.iter() {
+        match port.ty {
+            PinType::Input => writeln!(out, "    assign {} = {};", net(port.net), port.name),
+            PinType::Output => writeln!(out, "    assign {} = {};", port.name, net(port.net)),
+        }
+        .unwrap();
+    }
+    out.push('\n');
+    let mut modules = BTreeMap::new();
+    for cell in design.cells.iter() {
+        out.push_str(&instance(cell));
+        if !hdl::is_inline(cell.kind) {
+            modules.insert(hdl::module_name(cell), module(cell));
+        }
+    }
+    writeln!(out, "endmodule").unwrap();
+
+    for module in modules.values() {
+        out.push('\n');
+        out.push_str(module);
+    }
+    out.push_str("\n`default_nettype wire\n");
+    out
+}
+
+/// Writes the circuit as a Verilog module named after the file
+pub fn write_verilog(
+    path: &Path,
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Result<(), String> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let design = hdl::design(components, wires);
+    std::fs::write(path, verilog(&design, &hdl::identifier(stem))).map_err(|e| e.to_string())
+}
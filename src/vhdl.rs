@@ -0,0 +1,719 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use druid::{im, FileSpec, Selector};
+
+use crate::{
+    component::ComponentState,
+    hdl::{self, Cell, Design},
+    kind::{shift_bits, ComponentKind, PinType, ShiftMode},
+    wire::WireState,
+};
+
+pub const EXPORT_VHDL: Selector<druid::FileInfo> = Selector::new("logicism/export-vhdl");
+
+pub const VHDL: FileSpec = FileSpec::new("VHDL", &["vhd", "vhdl"]);
+
+const LIBRARIES: &str = "library ieee;\nuse ieee.std_logic_1164.all;\nuse ieee.numeric_std.all;\n";
+
+/// Words VHDL keeps for itself, which can't name an entity
+const RESERVED: &[&str] = &[
+    "abs",
+    "access",
+    "after",
+    "alias",
+    "all",
+    "and",
+    "architecture",
+    "array",
+    "assert",
+    "attribute",
+    "begin",
+    "block",
+    "body",
+    "buffer",
+    "bus",
+    "case",
+    "component",
+    "configuration",
+    "constant",
+    "disconnect",
+    "downto",
+    "else",
+    "elsif",
+    "end",
+    "entity",
+    "exit",
+    "file",
+    "for",
+    "function",
+    "generate",
+    "generic",
+    "group",
+    "guarded",
+    "if",
+    "impure",
+    "in",
+    "inertial",
+    "inout",
+    "is",
+    "label",
+    "library",
+    "linkage",
+    "literal",
+    "loop",
+    "map",
+    "mod",
+    "nand",
+    "new",
+    "next",
+    "nor",
+    "not",
+    "null",
+    "of",
+    "on",
+    "open",
+    "or",
+    "others",
+    "out",
+    "package",
+    "port",
+    "postponed",
+    "procedure",
+    "process",
+    "pure",
+    "range",
+    "record",
+    "register",
+    "reject",
+    "rem",
+    "report",
+    "return",
+    "rol",
+    "ror",
+    "select",
+    "severity",
+    "signal",
+    "shared",
+    "sla",
+    "sll",
+    "sra",
+    "srl",
+    "subtype",
+    "then",
+    "to",
+    "transport",
+    "type",
+    "unaffected",
+    "units",
+    "until",
+    "use",
+    "variable",
+    "wait",
+    "when",
+    "while",
+    "with",
+    "xnor",
+    "xor",
+];
+
+fn net(net: usize) -> String {
+    format!("n{}", net)
+}
+
+/// The type of a signal `width` bits wide
+fn ty(width: u8) -> String {
+    if width == 1 {
+        "std_logic".to_owned()
+    } else {
+        format!("std_logic_vector({} downto 0)", width - 1)
+    }
+}
+
+fn literal(width: u8, value: u64) -> String {
+    if width == 1 {
+        format!("'{}'", value & 1)
+    } else {
+        format!("\"{:0w$b}\"", value, w = width as usize)
+    }
+}
+
+/// Every bit of a signal `width` bits wide set to `bit`
+fn fill(width: u8, bit: char) -> String {
+    if width == 1 {
+        format!("'{}'", bit)
+    } else {
+        format!("(others => '{}')", bit)
+    }
+}
+
+/// A pin read as an unsigned number
+fn unsigned(pin: &str, width: u8) -> String {
+    if width == 1 {
+        format!("unsigned'(0 => {})", pin)
+    } else {
+        format!("unsigned({})", pin)
+    }
+}
+
+/// The low `width` bits of an unsigned signal, as the type of a pin that wide
+fn low_bits(signal: &str, width: u8) -> String {
+    if width == 1 {
+        format!("{}(0)", signal)
+    } else {
+        format!("std_logic_vector({}({} downto 0))", signal, width - 1)
+    }
+}
+
+/// The operator a gate is written with, if it is one
+fn operator(kind: ComponentKind) -> Option<&'static str> {
+    match kind {
+        ComponentKind::Not => Some("not"),
+        ComponentKind::And => Some("and"),
+        ComponentKind::Or => Some("or"),
+        ComponentKind::Nand => Some("nand"),
+        ComponentKind::Nor => Some("nor"),
+        ComponentKind::Xor => Some("xor"),
+        _ => None,
+    }
+}
+
+/// The declarations and the statements of the architecture of a component's entity, in terms
+/// of its pin names
+fn architecture_body(cell: &Cell) -> (String, String) {
+    let mut declarations = String::new();
+    let mut statements = String::new();
+    match cell.kind {
+        ComponentKind::Mux { select, .. } => {
+            let n = 1u64 << select;
+            let choices: Vec<_> = (0..n - 1)
+                .map(|i| format!("D{} when S = {} else", i, literal(select, i)))
+                .chain(std::iter::once(format!("D{};", n - 1)))
+                .collect();
+            writeln!(statements, "    Y <= {}", choices.join("\n         ")).unwrap();
+        },
+        ComponentKind::Demux { select, width } => {
+            for i in 0..1 << select {
+                writeln!(
+                    statements,
+                    "    Y{} <= D when S = {} else {};",
+                    i,
+                    literal(select, i),
+                    fill(width, '0')
+                )
+                .unwrap();
+            }
+        },
+        ComponentKind::Decoder { select } => {
+            for i in 0..1 << select {
+                writeln!(
+                    statements,
+                    "    Y{} <= '1' when A = {} else '0';",
+                    i,
+                    literal(select, i)
+                )
+                .unwrap();
+            }
+        },
+        ComponentKind::PriorityEncoder { select } => {
+            let n = 1u64 << select;
+            // the highest input that is set wins
+            let choices: Vec<_> = (1..n)
+                .rev()
+                .map(|i| format!("{} when D{} = '1' else", literal(select, i), i))
+                .chain(std::iter::once(format!("{};", literal(select, 0))))
+                .collect();
+            writeln!(statements, "    Y <= {}", choices.join("\n         ")).unwrap();
+            let any: Vec<_> = (0..n).map(|i| format!("D{}", i)).collect();
+            writeln!(statements, "    V <= {};", any.join(" or ")).unwrap();
+        },
+        ComponentKind::FullAdder | ComponentKind::Adder { .. } => {
+            let width = match cell.kind {
+                ComponentKind::Adder { width } => width,
+                _ => 1,
+            };
+            writeln!(
+                declarations,
+                "    signal sum : unsigned({} downto 0);",
+                width
+            )
+            .unwrap();
+            writeln!(
+                statements,
+                "    sum <= resize({a}, {n}) + resize({b}, {n}) + {c};\n    \
+                 S <= {s};\n    \
+                 Cout <= sum({w});",
+                a = unsigned("A", width),
+                b = unsigned("B", width),
+                c = unsigned("Cin", 1),
+                n = width + 1,
+                s = low_bits("sum", width),
+                w = width
+            )
+            .unwrap();
+        },
+        ComponentKind::Subtractor { width } => {
+            // the top bit is set when the difference is negative, which is when there's a borrow
+            writeln!(
+                declarations,
+                "    signal difference : unsigned({} downto 0);",
+                width
+            )
+            .unwrap();
+            writeln!(
+                statements,
+                "    difference <= resize({a}, {n}) - resize({b}, {n}) - {c};\n    \
+                 D <= {d};\n    \
+                 Bout <= difference({w});",
+                a = unsigned("A", width),
+                b = unsigned("B", width),
+                c = unsigned("Bin", 1),
+                n = width + 1,
+                d = low_bits("difference", width),
+                w = width
+            )
+            .unwrap();
+        },
+        ComponentKind::Comparator { width } => {
+            let (a, b) = (unsigned("A", width), unsigned("B", width));
+            for (pin, op) in [("LT", "<"), ("EQ", "="), ("GT", ">")].iter() {
+                writeln!(
+                    statements,
+                    "    {} <= '1' when {} {} {} else '0';",
+                    pin, a, op, b
+                )
+                .unwrap();
+            }
+        },
+        ComponentKind::Multiplier { width } => {
+            writeln!(
+                statements,
+                "    P <= std_logic_vector({} * {});",
+                unsigned("A", width),
+                unsigned("B", width)
+            )
+            .unwrap();
+        },
+        ComponentKind::Shifter { width } => {
+            writeln!(
+                declarations,
+                "    signal amount : natural;\n    \
+                 signal result : unsigned({} downto 0);",
+                width - 1
+            )
+            .unwrap();
+            writeln!(
+                statements,
+                "    amount <= to_integer({s});\n    \
+                 with OP select result <=\n        \
+                     shift_left({a}, amount) when \"00\",\n        \
+                     shift_right({a}, amount) when \"01\",\n        \
+                     unsigned(shift_right(signed({a}), amount)) when \"10\",\n        \
+                     rotate_left({a}, amount) when others;\n    \
+                 Y <= {y};",
+                s = unsigned("S", shift_bits(width)),
+                a = unsigned("A", width),
+                y = low_bits("result", width)
+            )
+            .unwrap();
+        },
+        ComponentKind::Alu { width } => {
+            let (a, b) = (unsigned("A", width), unsigned("B", width));
+            writeln!(
+                declarations,
+                "    signal sum : unsigned({w} downto 0);\n    \
+                 signal less : unsigned({top} downto 0);\n    \
+                 signal result : unsigned({top} downto 0);",
+                w = width,
+                top = width - 1
+            )
+            .unwrap();
+            writeln!(
+                statements,
+                "    sum <= resize({a}, {n}) + resize({b}, {n});\n    \
+                 less <= to_unsigned(1, {w}) when {a} < {b} else to_unsigned(0, {w});\n    \
+                 with OP select result <=\n        \
+                     sum({top} downto 0) when \"000\",\n        \
+                     {a} - {b} when \"001\",\n        \
+                     {a} and {b} when \"010\",\n        \
+                     {a} or {b} when \"011\",\n        \
+                     {a} xor {b} when \"100\",\n        \
+                     not {a} when \"101\",\n        \
+                     less when \"110\",\n        \
+                     {b} when others;\n    \
+                 Y <= {y};\n    \
+                 C <= sum({w}) when OP = \"000\" else\n         \
+                      less(0) when OP = \"001\" else\n         \
+                      '0';\n    \
+                 Z <= '1' when result = 0 else '0';",
+                a = a,
+                b = b,
+                n = width + 1,
+                w = width,
+                top = width - 1,
+                y = low_bits("result", width)
+            )
+            .unwrap();
+        },
+        ComponentKind::Register { width } => {
+            writeln!(
+                declarations,
+                "    signal state : {} := {};",
+                ty(width),
+                fill(width, '0')
+            )
+            .unwrap();
+            writeln!(
+                statements,
+                "    process (CLK, RST)\n    \
+                 begin\n        \
+                     if RST = '1' then\n            \
+                         state <= {zero};\n        \
+                     elsif rising_edge(CLK) then\n            \
+                         if EN = '1' then\n                \
+                             state <= D;\n            \
+                         end if;\n        \
+                     end if;\n    \
+                 end process;\n    \
+                 Q <= state;",
+                zero = fill(width, '0')
+            )
+            .unwrap();
+        },
+        ComponentKind::Counter { width } => {
+            writeln!(
+                declarations,
+                "    constant ones : unsigned({top} downto 0) := (others => '1');\n    \
+                 signal state : unsigned({top} downto 0) := (others => '0');",
+                top = width - 1
+            )
+            .unwrap();
+            writeln!(
+                statements,
+                "    process (CLK, RST)\n    \
+                 begin\n        \
+                     if RST = '1' then\n            \
+                         state <= (others => '0');\n        \
+                     elsif rising_edge(CLK) then\n            \
+                         if LD = '1' then\n                \
+                             state <= {d};\n            \
+                         elsif EN = '1' then\n                \
+                             if UP = '1' then\n                    \
+                                 state <= state + 1;\n                \
+                             else\n                    \
+                                 state <= state - 1;\n                \
+                             end if;\n            \
+                         end if;\n        \
+                     end if;\n    \
+                 end process;\n    \
+                 Q <= {q};\n    \
+                 TC <= '1' when EN = '1' and ((UP = '1' and state = ones) or \
+                 (UP = '0' and state = 0)) else '0';",
+                d = unsigned("D", width),
+                q = low_bits("state", width)
+            )
+            .unwrap();
+        },
+        ComponentKind::ShiftRegister { width, mode } => {
+            let up = |bit: &str| {
+                if width == 1 {
+                    bit.to_owned()
+                } else {
+                    format!("state({} downto 0) & {}", width - 2, bit)
+                }
+            };
+            let down = if width == 1 {
+                "SR".to_owned()
+            } else {
+                format!("SR & state({} downto 1)", width - 1)
+            };
+            let step = match mode {
+                ShiftMode::Sipo => format!(
+                    "if EN = '1' then\n                \
+                         state <= {};\n            \
+                     end if;",
+                    up("SI")
+                ),
+                ShiftMode::Piso => format!(
+                    "if LD = '1' then\n                \
+                         state <= D;\n            \
+                     else\n                \
+                         state <= {};\n            \
+                     end if;",
+                    up("SI")
+                ),
+                ShiftMode::Universal => format!(
+                    "case MODE is\n                \
+                         when \"01\" => state <= {};\n                \
+                         when \"10\" => state <= {};\n                \
+                         when \"11\" => state <= D;\n                \
+                         when others => null;\n            \
+                     end case;",
+                    up("SL"),
+                    down
+                ),
+            };
+            let output = match mode {
+                ShiftMode::Piso if width == 1 => "SO <= state;".to_owned(),
+                ShiftMode::Piso => format!("SO <= state({});", width - 1),
+                _ => "Q <= state;".to_owned(),
+            };
+            writeln!(
+                declarations,
+                "    signal state : {} := {};",
+                ty(width),
+                fill(width, '0')
+            )
+            .unwrap();
+            writeln!(
+                statements,
+                "    process (CLK, RST)\n    \
+                 begin\n        \
+                     if RST = '1' then\n            \
+                         state <= {zero};\n        \
+                     elsif rising_edge(CLK) then\n            \
+                         {step}\n        \
+                     end if;\n    \
+                 end process;\n    \
+                 {output}",
+                zero = fill(width, '0'),
+                step = step,
+                output = output
+            )
+            .unwrap();
+        },
+        ComponentKind::Rom { address, data } | ComponentKind::Ram { address, data, .. } => {
+            let words = 1u64 << address;
+            let mut contents: Vec<_> = cell
+                .contents
+                .iter()
+                .enumerate()
+                .filter(|&(i, &word)| word != 0 && (i as u64) < words)
+                .map(|(i, &word)| format!("{} => {}", i, literal(data, word)))
+                .collect();
+            contents.push(format!("others => {}", fill(data, '0')));
+            let class = match cell.kind {
+                ComponentKind::Rom { .. } => "constant",
+                _ => "signal",
+            };
+            writeln!(
+                declarations,
+                "    type memory is array (0 to {}) of {};\n    \
+                 {} mem : memory := (\n        {}\n    );",
+                words - 1,
+                ty(data),
+                class,
+                contents.join(",\n        ")
+            )
+            .unwrap();
+            let a = unsigned("A", address);
+            match cell.kind {
+                ComponentKind::Ram { sync: true, .. } => writeln!(
+                    statements,
+                    "    process (CLK)\n    \
+                     begin\n        \
+                         if rising_edge(CLK) then\n            \
+                             if WE = '1' then\n                \
+                                 mem(to_integer({})) <= D;\n            \
+                             end if;\n        \
+                         end if;\n    \
+                     end process;",
+                    a
+                )
+                .unwrap(),
+                ComponentKind::Ram { sync: false, .. } => writeln!(
+                    statements,
+                    "    -- written whenever WE is high, which synthesises to latches\n    \
+                     process (WE, A, D)\n    \
+                     begin\n        \
+                         if WE = '1' then\n            \
+                             mem(to_integer({})) <= D;\n        \
+                         end if;\n    \
+                     end process;",
+                    a
+                )
+                .unwrap(),
+                _ => {},
+            }
+            writeln!(statements, "    Q <= mem(to_integer({}));", a).unwrap();
+        },
+        ComponentKind::TriState { width } => {
+            writeln!(
+                statements,
+                "    Y <= A when EN = '1' else {};",
+                fill(width, 'Z')
+            )
+            .unwrap();
+        },
+        ComponentKind::TransmissionGate { width } => {
+            writeln!(
+                statements,
+                "    Y <= A when N = '1' and P = '0' else\n         \
+                      {} when N = '0' and P = '1' else\n         \
+                      {};",
+                fill(width, 'Z'),
+                fill(width, 'X')
+            )
+            .unwrap();
+        },
+        _ => {},
+    }
+    (declarations, statements)
+}
+
+/// The entity a component is an instance of, with ports named after its pins, and its
+/// architecture
+fn entity(cell: &Cell) -> String {
+    let name = hdl::module_name(cell);
+    let ports: Vec<_> = cell
+        .kind
+        .pins()
+        .iter()
+        .map(|pin| {
+            let direction = match pin.ty {
+                PinType::Input => "in",
+                PinType::Output => "out",
+            };
+            format!("        {} : {} {}", pin.name, direction, ty(pin.width))
+        })
+        .collect();
+    let (declarations, statements) = architecture_body(cell);
+    format!(
+        "{libraries}\nentity {name} is\n    port (\n{ports}\n    );\nend entity;\n\n\
+         architecture behaviour of {name} is\n{declarations}begin\n{statements}\
+         end architecture;\n",
+        libraries = LIBRARIES,
+        name = name,
+        ports = ports.join(";\n"),
+        declarations = declarations,
+        statements = statements
+    )
+}
+
+/// The statements a component turns into inside the top architecture, which are signal
+/// assignments for gates, constants and for splitting and joining buses and an instance of an
+/// entity for anything else
+fn instance(cell: &Cell, nets: &BTreeMap<usize, u8>) -> String {
+    let pins = &cell.pins;
+    if let Some(operator) = operator(cell.kind) {
+        let (output, inputs) = pins.split_last().unwrap();
+        let expr = match inputs {
+            [a] => format!("{} {}", operator, net(a.net)),
+            _ => {
+                let inputs: Vec<_> = inputs.iter().map(|pin| net(pin.net)).collect();
+                inputs.join(&format!(" {} ", operator))
+            },
+        };
+        return format!("    {} <= {}; -- {}\n", net(output.net), expr, cell.name);
+    }
+    match cell.kind {
+        ComponentKind::Constant { width, value } => format!(
+            "    {} <= {}; -- {}\n",
+            net(pins[0].net),
+            literal(width, value),
+            cell.name
+        ),
+        ComponentKind::Split { .. } => {
+            let bus = pins[0].net;
+            let mut out = String::new();
+            for (i, pin) in pins[1..].iter().enumerate() {
+                let bit = if nets[&bus] == 1 {
+                    net(bus)
+                } else {
+                    format!("{}({})", net(bus), i)
+                };
+                writeln!(out, "    {} <= {}; -- {}", net(pin.net), bit, cell.name).unwrap();
+            }
+            out
+        },
+        ComponentKind::Join { .. } => {
+            let (output, bits) = pins.split_last().unwrap();
+            let bits: Vec<_> = bits.iter().rev().map(|pin| net(pin.net)).collect();
+            format!(
+                "    {} <= {}; -- {}\n",
+                net(output.net),
+                bits.join(" & "),
+                cell.name
+            )
+        },
+        _ => {
+            let connections: Vec<_> = pins
+                .iter()
+                .map(|pin| format!("{} => {}", pin.name, net(pin.net)))
+                .collect();
+            format!(
+                "    {}: entity work.{} port map ({});\n",
+                cell.name,
+                hdl::module_name(cell),
+                connections.join(", ")
+            )
+        },
+    }
+}
+
+/// The entities for the components in the circuit that aren't gates, followed by an entity for
+/// the circuit, called `name`, with a structural architecture
+pub fn vhdl(design: &Design, name: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "-- Written by Logicism").unwrap();
+
+    // entities have to come before anything that instantiates them
+    let mut entities = BTreeMap::new();
+    let mut statements = String::new();
+    for cell in design.cells.iter() {
+        statements.push_str(&instance(cell, &design.nets));
+        if !hdl::is_inline(cell.kind) {
+            entities.insert(hdl::module_name(cell), entity(cell));
+        }
+    }
+    for entity in entities.values() {
+        out.push('\n');
+        out.push_str(entity);
+    }
+
+    writeln!(out, "\n{}\nentity {} is", LIBRARIES, name).unwrap();
+    if !design.ports.is_empty() {
+        let ports: Vec<_> = design
+            .ports
+            .iter()
+            .map(|port| {
+                let direction = match port.ty {
+                    PinType::Input => "in",
+                    PinType::Output => "out",
+                };
+                format!("        {} : {} {}", port.name, direction, ty(port.width))
+            })
+            .collect();
+        writeln!(out, "    port (\n{}\n    );", ports.join(";\n")).unwrap();
+    }
+    writeln!(out, "end entity;\n\narchitecture structure of {} is", name).unwrap();
+    for (&n, &width) in design.nets.iter() {
+        writeln!(out, "    signal {} : {};", net(n), ty(width)).unwrap();
+    }
+    writeln!(out, "begin").unwrap();
+    for port in design.ports.iter() {
+        match port.ty {
+            PinType::Input => writeln!(out, "    {} <= {};", net(port.net), port.name),
+            PinType::Output => writeln!(out, "    {} <= {};", port.name, net(port.net)),
+        }
+        .unwrap();
+    }
+    out.push('\n');
+    out.push_str(&statements);
+    writeln!(out, "end architecture;").unwrap();
+    out
+}
+
+/// Writes the circuit as a VHDL entity named after the file
+pub fn write_vhdl(
+    path: &Path,
+    components: &im::OrdMap<usize, ComponentState>,
+    wires: &im::OrdMap<usize, WireState>,
+) -> Result<(), String> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let mut name = hdl::identifier(stem);
+    if RESERVED.contains(&name.to_lowercase().as_str()) {
+        name.push_str("_circuit");
+    }
+    let design = hdl::design(components, wires);
+    std::fs::write(path, vhdl(&design, &name)).map_err(|e| e.to_string())
+}
@@ -0,0 +1,422 @@
+use druid::{
+    commands, im,
+    kurbo::{BezPath, Line},
+    piet::{Text, TextLayout, TextLayoutBuilder},
+    widget::{Button, CrossAxisAlignment, Either, Flex, Label, SizedBox, TextBox},
+    BoxConstraints, Color, Data, Event, FileDialogOptions, Lens, MouseButton, Rect, RenderContext,
+    Size, Widget, WidgetExt,
+};
+
+use crate::{
+    canvas::CanvasState,
+    sim::Anchor,
+    value::{Radix, Value},
+    vcd, wire,
+};
+
+const NAME_WIDTH: f64 = 120.0;
+const AXIS_HEIGHT: f64 = 20.0;
+const ROW_HEIGHT: f64 = 24.0;
+/// Pixels per unit of simulation time
+const DEFAULT_ZOOM: f64 = 1.0;
+const MIN_ZOOM: f64 = 1.0 / 64.0;
+const MAX_ZOOM: f64 = 64.0;
+const ZOOM_STEP: f64 = 1.25;
+/// The narrowest gap between ticks on the time axis, in pixels
+const TICK_SPACING: f64 = 60.0;
+
+/// The history of the value on one net, recorded while the circuit is simulated
+#[derive(Clone, Data)]
+pub struct Trace {
+    pub anchor: Anchor,
+    pub name: String,
+    /// The name it was given when it was added, before any renaming
+    pub default_name: String,
+    /// The times the value changed and what it changed to, oldest first
+    pub changes: im::Vector<(u64, Value)>,
+}
+
+impl Trace {
+    pub fn new(anchor: Anchor, name: String) -> Self {
+        Trace {
+            anchor,
+            default_name: name.clone(),
+            name,
+            changes: im::Vector::new(),
+        }
+    }
+
+    /// Notes the value at `time`, which mustn't be before the last change. A value that changes
+    /// more than once at the same time only keeps the last change.
+    pub fn record(&mut self, time: u64, value: Value) {
+        if self.changes.back().map(|c| c.0) == Some(time) {
+            self.changes.pop_back();
+        }
+        if self.changes.back().map(|c| c.1) != Some(value) {
+            self.changes.push_back((time, value));
+        }
+    }
+
+    /// The value at `time`, or `None` if it is from before the trace was started
+    pub fn value_at(&self, time: u64) -> Option<Value> {
+        self.changes
+            .iter()
+            .take_while(|(t, _)| *t <= time)
+            .last()
+            .map(|(_, value)| *value)
+    }
+}
+
+/// The name of the highlighted trace, which it goes by in exported files
+struct TraceName;
+
+impl Lens<CanvasState, String> for TraceName {
+    fn with<V, F: FnOnce(&String) -> V>(&self, data: &CanvasState, f: F) -> V {
+        match data.highlighted_trace() {
+            Some(trace) => f(&trace.name),
+            None => f(&String::new()),
+        }
+    }
+
+    fn with_mut<V, F: FnOnce(&mut String) -> V>(&self, data: &mut CanvasState, f: F) -> V {
+        match data.highlighted_trace_mut() {
+            Some(trace) => f(&mut trace.name),
+            None => f(&mut String::new()),
+        }
+    }
+}
+
+/// The waveform, under a bar for labelling the highlighted trace and exporting them all. It is
+/// empty while nothing is traced.
+pub fn panel() -> impl Widget<CanvasState> {
+    let export = |ctx: &mut druid::EventCtx, _: &mut CanvasState, _: &druid::Env| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![vcd::VCD])
+            .default_type(vcd::VCD)
+            .accept_command(vcd::EXPORT_VCD);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+    };
+    let bar = Flex::row()
+        .with_child(Label::new("Label"))
+        .with_child(
+            TextBox::new()
+                .with_placeholder("click a name to label it")
+                .lens(TraceName)
+                .fix_width(200.0),
+        )
+        .with_child(Button::new("Export VCD").on_click(export));
+    let panel = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(bar.padding(2.0))
+        .with_child(Waveform::new().fix_height(160.0));
+    Either::new(
+        |data: &CanvasState, _| data.traces().is_empty(),
+        SizedBox::empty(),
+        panel,
+    )
+}
+
+/// The smallest round number of time units between ticks that keeps them `TICK_SPACING` apart
+fn tick_step(zoom: f64) -> f64 {
+    let min = TICK_SPACING / zoom;
+    let mut step = 1.0;
+    loop {
+        for &multiple in &[1.0, 2.0, 5.0] {
+            if step * multiple >= min {
+                return step * multiple;
+            }
+        }
+        step *= 10.0;
+    }
+}
+
+/// Plots the traces of a circuit against time. The wheel scrolls and zooms with ctrl held, and
+/// the view follows the latest values unless it is scrolled back. Left and right clicks place two
+/// cursors and the middle button clears them, while clicking a name highlights its wires on the
+/// canvas.
+pub struct Waveform {
+    zoom: f64,
+    /// The time at the left edge of the plot, or `None` to keep the latest time in view
+    start: Option<f64>,
+    cursors: [Option<u64>; 2],
+}
+
+impl Waveform {
+    pub fn new() -> Self {
+        Waveform {
+            zoom: DEFAULT_ZOOM,
+            start: None,
+            cursors: [None; 2],
+        }
+    }
+
+    /// The time at the left edge of a plot `width` pixels wide when the latest time is `now`
+    fn start(&self, now: f64, width: f64) -> f64 {
+        self.start
+            .unwrap_or_else(|| (now - width / self.zoom).max(0.0))
+    }
+
+    fn scroll_to(&mut self, start: f64, now: f64, width: f64) {
+        self.start = if start + width / self.zoom >= now {
+            None
+        } else {
+            Some(start.max(0.0))
+        };
+    }
+}
+
+impl Widget<CanvasState> for Waveform {
+    fn event(
+        &mut self,
+        ctx: &mut druid::EventCtx,
+        event: &Event,
+        data: &mut CanvasState,
+        _env: &druid::Env,
+    ) {
+        let now = data.time() as f64;
+        let width = (ctx.size().width - NAME_WIDTH).max(1.0);
+        let start = self.start(now, width);
+        match event {
+            Event::Wheel(m) if m.mods.ctrl() => {
+                let offset = (m.pos.x - NAME_WIDTH).max(0.0);
+                let time = start + offset / self.zoom;
+                let factor = if m.wheel_delta.y < 0.0 {
+                    ZOOM_STEP
+                } else {
+                    1.0 / ZOOM_STEP
+                };
+                self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+                self.scroll_to(time - offset / self.zoom, now, width);
+                ctx.set_handled();
+                ctx.request_paint();
+            },
+            Event::Wheel(m) => {
+                let delta = if m.wheel_delta.x != 0.0 {
+                    m.wheel_delta.x
+                } else {
+                    m.wheel_delta.y
+                };
+                self.scroll_to(start + delta / self.zoom, now, width);
+                ctx.set_handled();
+                ctx.request_paint();
+            },
+            Event::MouseDown(m) if m.pos.x < NAME_WIDTH => {
+                let row = ((m.pos.y - AXIS_HEIGHT) / ROW_HEIGHT).floor();
+                if row >= 0.0 && (row as usize) < data.traces().len() {
+                    let row = row as usize;
+                    let highlighted = data.highlighted();
+                    data.highlight(if highlighted == Some(row) {
+                        None
+                    } else {
+                        Some(row)
+                    });
+                }
+                ctx.set_handled();
+            },
+            Event::MouseDown(m) => {
+                let time = (start + (m.pos.x - NAME_WIDTH) / self.zoom)
+                    .round()
+                    .min(now) as u64;
+                match m.button {
+                    MouseButton::Left => self.cursors[0] = Some(time),
+                    MouseButton::Right => self.cursors[1] = Some(time),
+                    MouseButton::Middle => self.cursors = [None; 2],
+                    _ => {},
+                }
+                ctx.set_handled();
+                ctx.request_paint();
+            },
+            _ => {},
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut druid::LifeCycleCtx,
+        _event: &druid::LifeCycle,
+        _data: &CanvasState,
+        _env: &druid::Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &CanvasState,
+        data: &CanvasState,
+        _env: &druid::Env,
+    ) {
+        if !old_data.traces().same(data.traces())
+            || old_data.time() != data.time()
+            || old_data.highlighted() != data.highlighted()
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut druid::LayoutCtx,
+        bc: &BoxConstraints,
+        data: &CanvasState,
+        _env: &druid::Env,
+    ) -> Size {
+        let rows = data.traces().len().max(1) as f64;
+        bc.constrain(Size::new(
+            bc.max().width,
+            AXIS_HEIGHT + rows * ROW_HEIGHT + 4.0,
+        ))
+    }
+
+    fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &CanvasState, _env: &druid::Env) {
+        let size = ctx.size();
+        let now = data.time() as f64;
+        let start = self.start(now, (size.width - NAME_WIDTH).max(1.0));
+        let zoom = self.zoom;
+        let x_of = |time: f64| NAME_WIDTH + (time - start) * zoom;
+        let grid = Color::rgb8(0xe0, 0xe0, 0xe0);
+        let cursor_colors = [Color::BLUE, Color::rgb8(0xe0, 0x80, 0x00)];
+
+        ctx.fill(size.to_rect(), &Color::WHITE);
+        ctx.stroke(
+            Line::new((NAME_WIDTH, 0.0), (NAME_WIDTH, size.height)),
+            &Color::GRAY,
+            1.0,
+        );
+        ctx.stroke(
+            Line::new((0.0, AXIS_HEIGHT), (size.width, AXIS_HEIGHT)),
+            &Color::GRAY,
+            1.0,
+        );
+
+        // values are shown at the first cursor if there is one, and the latest time otherwise
+        let shown_at = self.cursors[0].unwrap_or(data.time());
+        for (i, trace) in data.traces().iter().enumerate() {
+            let top = AXIS_HEIGHT + i as f64 * ROW_HEIGHT;
+            if data.highlighted() == Some(i) {
+                let row = Rect::new(0.0, top, size.width, top + ROW_HEIGHT);
+                ctx.fill(row, &Color::rgb8(0xff, 0xf4, 0xc0));
+            }
+            let value = trace.value_at(shown_at);
+            let text = match value {
+                Some(value) if value.width() > 1 => {
+                    format!("{} {}", trace.name, value.format(Radix::Hex))
+                },
+                Some(value) => format!("{} {}", trace.name, value.format(Radix::Binary)),
+                None => trace.name.clone(),
+            };
+            let label = ctx
+                .text()
+                .new_text_layout(text)
+                .text_color(Color::BLACK)
+                .build()
+                .unwrap();
+            let y = top + (ROW_HEIGHT - label.size().height) / 2.0;
+            ctx.with_save(|ctx| {
+                ctx.clip(Rect::new(0.0, top, NAME_WIDTH - 2.0, top + ROW_HEIGHT));
+                ctx.draw_text(&label, (4.0, y));
+            });
+        }
+
+        ctx.with_save(|ctx| {
+            ctx.clip(Rect::new(NAME_WIDTH, 0.0, size.width, size.height));
+
+            // time axis
+            let step = tick_step(zoom);
+            let mut time = (start / step).ceil() * step;
+            while x_of(time) < size.width {
+                let x = x_of(time);
+                ctx.stroke(
+                    Line::new((x, AXIS_HEIGHT - 4.0), (x, size.height)),
+                    &grid,
+                    1.0,
+                );
+                let label = ctx
+                    .text()
+                    .new_text_layout(format!("{}", time))
+                    .text_color(Color::GRAY)
+                    .build()
+                    .unwrap();
+                ctx.draw_text(&label, (x + 2.0, 2.0));
+                time += step;
+            }
+
+            for (i, trace) in data.traces().iter().enumerate() {
+                let top = AXIS_HEIGHT + i as f64 * ROW_HEIGHT;
+                let (high, low) = (top + 4.0, top + ROW_HEIGHT - 4.0);
+                let middle = (high + low) / 2.0;
+                let mut last_y = None;
+                for (j, &(time, value)) in trace.changes.iter().enumerate() {
+                    let end = trace.changes.get(j + 1).map_or(now, |c| c.0 as f64);
+                    let (x0, x1) = (x_of(time as f64), x_of(end));
+                    let color = wire::color(Some(value));
+                    if value.width() == 1 {
+                        let y = if value.has_error() || value.is_floating() {
+                            middle
+                        } else if value.is_high() {
+                            high
+                        } else {
+                            low
+                        };
+                        if let Some(last_y) = last_y {
+                            ctx.stroke(Line::new((x0, last_y), (x0, y)), &Color::BLACK, 1.0);
+                        }
+                        if value.has_error() {
+                            let rect = Rect::new(x0, high, x1, low);
+                            ctx.fill(rect, &Color::rgba8(0xff, 0x00, 0x00, 0x40));
+                        }
+                        ctx.stroke(Line::new((x0, y), (x1, y)), &color, 2.0);
+                        last_y = Some(y);
+                    } else {
+                        // buses are drawn as a run of hexagons, one for each value
+                        let slant = 3.0f64.min((x1 - x0) / 2.0);
+                        let mut shape = BezPath::new();
+                        shape.move_to((x0, middle));
+                        shape.line_to((x0 + slant, high));
+                        shape.line_to((x1 - slant, high));
+                        shape.line_to((x1, middle));
+                        shape.line_to((x1 - slant, low));
+                        shape.line_to((x0 + slant, low));
+                        shape.close_path();
+                        ctx.stroke(shape, &color, 1.0);
+
+                        let label = ctx
+                            .text()
+                            .new_text_layout(value.format(Radix::Hex))
+                            .text_color(color)
+                            .build()
+                            .unwrap();
+                        let (left, right) = (x0.max(NAME_WIDTH), x1.min(size.width));
+                        if label.size().width + 2.0 * slant < right - left {
+                            let x = (left + right - label.size().width) / 2.0;
+                            let y = middle - label.size().height / 2.0;
+                            ctx.draw_text(&label, (x, y));
+                        }
+                    }
+                }
+            }
+
+            for (cursor, color) in self.cursors.iter().zip(&cursor_colors) {
+                if let Some(time) = cursor {
+                    let x = x_of(*time as f64);
+                    ctx.stroke(Line::new((x, 0.0), (x, size.height)), color, 1.0);
+                }
+            }
+        });
+
+        // cursor readout
+        let readout = match self.cursors {
+            [Some(a), Some(b)] => format!("A {}  B {}  \u{394} {}", a, b, b as i64 - a as i64),
+            [Some(a), None] => format!("A {}", a),
+            [None, Some(b)] => format!("B {}", b),
+            [None, None] => format!("t {}", data.time()),
+        };
+        let label = ctx
+            .text()
+            .new_text_layout(readout)
+            .text_color(Color::BLACK)
+            .build()
+            .unwrap();
+        ctx.draw_text(&label, (4.0, 2.0));
+    }
+}
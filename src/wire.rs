@@ -1,6 +1,8 @@
-use druid::{im, Color, Data, PaintCtx, Point, Rect, RenderContext, Size, Widget};
+use std::collections::BTreeSet;
 
-use crate::canvas::Coords;
+use druid::{im, Color, Data, PaintCtx, Point, Rect, RenderContext, Widget};
+
+use crate::{canvas::Coords, value::Value};
 
 #[derive(Clone, Data)]
 pub struct WireSegment {
@@ -17,29 +19,65 @@ impl WireSegment {
         }
     }
 
+    pub fn ends(&self) -> (Coords, Coords) {
+        (self.start, self.end)
+    }
+
+    /// Whether `point` is on the straight line from `start` to `end`, ends included
+    pub fn runs_through(start: Coords, end: Coords, point: Coords) -> bool {
+        let between = |a: isize, b: isize, x: isize| a.min(b) <= x && x <= a.max(b);
+        between(start.x, end.x, point.x) && between(start.y, end.y, point.y)
+    }
+
+    /// The straight line from `start` to `end` cut at each of `points` partway along it, for
+    /// formats that only join things at the ends of wires
+    pub fn cut(start: Coords, end: Coords, points: &BTreeSet<Coords>) -> Vec<(Coords, Coords)> {
+        let mut cuts: Vec<Coords> = points
+            .iter()
+            .copied()
+            .filter(|&p| p != start && p != end && WireSegment::runs_through(start, end, p))
+            .collect();
+        cuts.sort_by_key(|p| (p.x - start.x).abs() + (p.y - start.y).abs());
+        cuts.push(end);
+        let mut from = start;
+        cuts.into_iter()
+            .map(|to| (std::mem::replace(&mut from, to), to))
+            .collect()
+    }
+
     pub fn bounding_rect(&self) -> Rect {
         let start = self.start.to_widget_space();
         let end = self.end.to_widget_space();
         Rect::from_points(start, end).inflate(2.0, 2.0)
     }
 
-    pub fn paint(&self, ctx: &mut PaintCtx) {
-        let start = self.start.to_widget_space();
-        let end = self.end.to_widget_space();
-        let rect = Rect::from_points(start, end)
-            .with_origin(Point::new(10.0, 10.0))
-            .inflate(1.0, 1.0);
-        ctx.fill(rect, &Color::GREEN);
-        ctx.fill(
-            Rect::from_origin_size(Point::ORIGIN, Size::new(2.0, 2.0)),
-            &Color::RED,
-        );
+    /// Paints this segment in the space of a widget whose top left corner is at `origin`
+    pub fn paint(&self, ctx: &mut PaintCtx, origin: Point, color: &Color) {
+        let start = self.start.to_canvas_space() - origin.to_vec2();
+        let end = self.end.to_canvas_space() - origin.to_vec2();
+        let rect = Rect::from_points(start, end).inflate(1.0, 1.0);
+        ctx.fill(rect, color);
+    }
+}
+
+/// How a wire carrying `value` is painted: bright for high, dark for low, black for a bus, red
+/// where drivers fight, blue while nothing drives it and grey while it isn't simulated
+pub fn color(value: Option<Value>) -> Color {
+    match value {
+        None => Color::GRAY,
+        Some(v) if v.has_error() => Color::RED,
+        Some(v) if v.is_floating() => Color::BLUE,
+        Some(v) if v.width() > 1 => Color::BLACK,
+        Some(v) if v.is_high() => Color::rgb8(0x00, 0xd0, 0x00),
+        Some(_) => Color::rgb8(0x00, 0x64, 0x00),
     }
 }
 
 #[derive(Clone, Data)]
 pub struct WireState {
     pub segments: im::Vector<WireSegment>,
+    /// The value on the net this wire is part of, as of the last simulation
+    pub value: Option<Value>,
 }
 
 impl WireState {
@@ -75,11 +113,14 @@ impl Widget<WireState> for Wire {
 
     fn update(
         &mut self,
-        _ctx: &mut druid::UpdateCtx,
-        _old_data: &WireState,
-        _data: &WireState,
+        ctx: &mut druid::UpdateCtx,
+        old_data: &WireState,
+        data: &WireState,
         _env: &druid::Env,
     ) {
+        if old_data.value != data.value {
+            ctx.request_paint();
+        }
     }
 
     fn layout(
@@ -93,9 +134,11 @@ impl Widget<WireState> for Wire {
     }
 
     fn paint(&mut self, ctx: &mut druid::PaintCtx, data: &WireState, _env: &druid::Env) {
+        let origin = data.bounding_rect().origin();
+        let color = color(data.value);
         ctx.with_save(|ctx| {
             for segment in data.segments.iter() {
-                segment.paint(ctx);
+                segment.paint(ctx, origin, &color);
             }
         })
     }
@@ -0,0 +1,328 @@
+use std::path::Path;
+
+use druid::{FileSpec, Selector};
+use serde_json::Value;
+
+use crate::import::{Assembly, Gates, Import};
+
+pub const IMPORT_YOSYS: Selector<druid::FileInfo> = Selector::new("logicism/import-yosys");
+
+pub const YOSYS: FileSpec = FileSpec::new("Yosys netlist", &["json"]);
+
+/// The name of one bit of a net. Yosys numbers them, apart from the constants "0", "1", "x" and
+/// "z".
+fn bit_name(bit: &Value) -> String {
+    match bit {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The net of one bit, with constants made from gates and undefined bits read as zero
+fn bit(gates: &mut Gates, bit: &Value) -> usize {
+    match bit {
+        Value::Number(n) => gates.net(&n.to_string()),
+        Value::String(s) if s == "1" => gates.builder.one(),
+        _ => gates.builder.zero(),
+    }
+}
+
+/// The net a one-bit pin of a cell connects to
+fn pin(gates: &mut Gates, connections: &Value, name: &str) -> Result<usize, String> {
+    match connections[name].get(0) {
+        Some(b) => Ok(bit(gates, b)),
+        None => Err(format!("A cell is missing its {} connection", name)),
+    }
+}
+
+/// A control as it is if `flag` is 'P' for active high, or inverted if it is 'N' for active low
+fn polarity(gates: &mut Gates, net: usize, flag: char) -> usize {
+    if flag == 'N' {
+        gates.builder.not(net)
+    } else {
+        net
+    }
+}
+
+/// Adds one of Yosys's internal gate cells, returning whether there is anything here to make it
+/// from
+fn cell(gates: &mut Gates, ty: &str, connections: &Value) -> Result<bool, String> {
+    let (y, output) = match ty {
+        "$_BUF_" => (pin(gates, connections, "A")?, "Y"),
+        "$_NOT_" => {
+            let a = pin(gates, connections, "A")?;
+            (gates.builder.not(a), "Y")
+        },
+        "$_AND_" | "$_OR_" | "$_XOR_" | "$_NAND_" | "$_NOR_" | "$_XNOR_" | "$_ANDNOT_"
+        | "$_ORNOT_" => {
+            let a = pin(gates, connections, "A")?;
+            let b = pin(gates, connections, "B")?;
+            let g = &mut gates.builder;
+            let y = match ty {
+                "$_AND_" => g.and(a, b),
+                "$_OR_" => g.or(a, b),
+                "$_XOR_" => g.xor(a, b),
+                "$_NAND_" => g.nand(a, b),
+                "$_NOR_" => g.nor(a, b),
+                "$_XNOR_" => {
+                    let x = g.xor(a, b);
+                    g.not(x)
+                },
+                "$_ANDNOT_" => {
+                    let b = g.not(b);
+                    g.and(a, b)
+                },
+                _ => {
+                    let b = g.not(b);
+                    g.or(a, b)
+                },
+            };
+            (y, "Y")
+        },
+        "$_MUX_" | "$_NMUX_" => {
+            let a = pin(gates, connections, "A")?;
+            let b = pin(gates, connections, "B")?;
+            let s = pin(gates, connections, "S")?;
+            let y = gates.builder.mux(&[s], &[a, b]);
+            match ty {
+                "$_MUX_" => (y, "Y"),
+                _ => (gates.builder.not(y), "Y"),
+            }
+        },
+        // and-or-invert and or-and-invert
+        "$_AOI3_" | "$_OAI3_" | "$_AOI4_" | "$_OAI4_" => {
+            let a = pin(gates, connections, "A")?;
+            let b = pin(gates, connections, "B")?;
+            let c = pin(gates, connections, "C")?;
+            let d = match ty {
+                "$_AOI4_" | "$_OAI4_" => Some(pin(gates, connections, "D")?),
+                _ => None,
+            };
+            let g = &mut gates.builder;
+            let and_first = ty.starts_with("$_AOI");
+            let first = if and_first { g.and(a, b) } else { g.or(a, b) };
+            let second = match d {
+                Some(d) if and_first => g.and(c, d),
+                Some(d) => g.or(c, d),
+                None => c,
+            };
+            let y = if and_first {
+                g.or(first, second)
+            } else {
+                g.and(first, second)
+            };
+            (g.not(y), "Y")
+        },
+        _ => match flip_flop(gates, ty, connections)? {
+            Some(q) => (q, "Q"),
+            None => return Ok(false),
+        },
+    };
+    match connections[output].get(0) {
+        Some(out) => gates.drive(&bit_name(out), y),
+        None => return Err(format!("A cell is missing its {} connection", output)),
+    }
+    Ok(true)
+}
+
+/// Adds one of Yosys's flip-flop cells as a register, returning its output, or `None` if it's a
+/// kind that a register can't stand in for. The flags after the name give the clock's polarity
+/// and then those of the reset, the value it resets to and the enable, where there are any.
+fn flip_flop(gates: &mut Gates, ty: &str, connections: &Value) -> Result<Option<usize>, String> {
+    let name = ty.trim_start_matches("$_").trim_end_matches('_');
+    let (kind, flags) = match name.split_once('_') {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+    let flags: Vec<char> = flags.chars().collect();
+    // a register only clears asynchronously, so an asynchronous set can't be done
+    let (reset, sync, enable) = match (kind, flags.len()) {
+        ("DFF", 1) => (None, false, None),
+        ("DFFE", 2) => (None, false, Some(flags[1])),
+        ("DFF", 3) if flags[2] == '0' => (Some(flags[1]), false, None),
+        ("DFFE", 4) if flags[2] == '0' => (Some(flags[1]), false, Some(flags[3])),
+        ("SDFF", 3) => (Some(flags[1]), true, None),
+        ("SDFFE", 4) | ("SDFFCE", 4) => (Some(flags[1]), true, Some(flags[3])),
+        _ => return Ok(None),
+    };
+
+    let clk = pin(gates, connections, "C")?;
+    let clk = polarity(gates, clk, flags[0]);
+    let mut d = pin(gates, connections, "D")?;
+    let mut en = match enable {
+        Some(flag) => {
+            let e = pin(gates, connections, "E")?;
+            polarity(gates, e, flag)
+        },
+        None => gates.builder.one(),
+    };
+    let mut rst = gates.builder.zero();
+    if let Some(flag) = reset {
+        let r = pin(gates, connections, "R")?;
+        let r = polarity(gates, r, flag);
+        if sync {
+            // a synchronous reset is a change to what gets loaded, and one that doesn't wait for
+            // the enable loads whether or not it's set
+            d = if flags[2] == '1' {
+                gates.builder.or(d, r)
+            } else {
+                let not_r = gates.builder.not(r);
+                gates.builder.and(d, not_r)
+            };
+            if kind == "SDFFE" {
+                en = gates.builder.or(en, r);
+            }
+        } else {
+            rst = r;
+        }
+    }
+    Ok(Some(gates.builder.register(d, clk, en, rst)))
+}
+
+/// Whether Yosys marked a module as the top of the design
+fn is_top(module: &Value) -> bool {
+    match &module["attributes"]["top"] {
+        Value::Null => false,
+        Value::String(s) => s.contains('1'),
+        Value::Number(n) => n.as_u64() != Some(0),
+        _ => true,
+    }
+}
+
+/// Reads the top module of a netlist written by Yosys's `write_json`, made of the gates and
+/// flip-flops that `synth` maps a design to, and lays it out with an input for each input bit
+/// and a probe for each output bit
+pub fn import(text: &str) -> Result<Import, String> {
+    let json: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let modules = json["modules"]
+        .as_object()
+        .ok_or_else(|| "This isn't a Yosys netlist".to_owned())?;
+    let (name, module) = modules
+        .iter()
+        .find(|(_, module)| is_top(module))
+        .or_else(|| modules.iter().next())
+        .ok_or_else(|| "The netlist has no modules".to_owned())?;
+
+    let mut assembly = Assembly::new();
+    let mut gates = Gates::new();
+    if modules.len() > 1 {
+        assembly.note(format!(
+            "Read module {}, the top of the {} in the netlist",
+            name,
+            modules.len()
+        ));
+    }
+    for (port_name, port) in module["ports"].as_object().into_iter().flatten() {
+        let input = port["direction"] == "input";
+        let bits: Vec<&Value> = port["bits"].as_array().into_iter().flatten().collect();
+        for &b in bits.iter() {
+            if !input && !b.is_number() {
+                let net = bit(&mut gates, b);
+                gates.drive(&bit_name(b), net);
+            }
+        }
+        let names: Vec<String> = bits.into_iter().map(bit_name).collect();
+        gates.port(port_name, input, &names);
+    }
+
+    let mut instances = false;
+    let mut coarse = false;
+    for cell_data in module["cells"]
+        .as_object()
+        .into_iter()
+        .flat_map(|c| c.values())
+    {
+        let ty = cell_data["type"].as_str().unwrap_or("");
+        if !cell(&mut gates, ty, &cell_data["connections"])? {
+            if !ty.starts_with('$') {
+                instances = true;
+            } else if !ty.starts_with("$_") {
+                coarse = true;
+            }
+            assembly.leave_out(ty);
+        }
+    }
+    if instances {
+        assembly.note(
+            "Instances of other modules are left out. Running flatten in Yosys before write_json \
+             brings their gates in.",
+        );
+    }
+    if coarse {
+        assembly.note(
+            "Word-level cells are left out. Running synth or techmap in Yosys before write_json \
+             breaks them down into gates.",
+        );
+    }
+    gates.finish(&mut assembly);
+    Ok(assembly.finish())
+}
+
+pub fn read_yosys(path: &Path) -> Result<Import, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    import(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::tests::truth_table;
+
+    /// A module with the ports and cells given, as `write_json` would give it
+    fn netlist(ports: &str, cells: &str) -> String {
+        format!(
+            r#"{{"modules": {{"top": {{"attributes": {{"top": "00000000000000000000000000000001"}},
+                "ports": {{{}}}, "cells": {{{}}}}}}}}}"#,
+            ports, cells
+        )
+    }
+
+    const HALF_ADDER_PORTS: &str = r#"
+        "a": {"direction": "input", "bits": [2]},
+        "b": {"direction": "input", "bits": [3]},
+        "y": {"direction": "output", "bits": [4, 5]}"#;
+
+    #[test]
+    fn imports_a_half_adder() {
+        let cells = r#"
+            "$1": {"type": "$_XOR_", "connections": {"A": [2], "B": [3], "Y": [4]}},
+            "$2": {"type": "$_AND_", "connections": {"A": [2], "B": [3], "Y": [5]}}"#;
+        let import = import(&netlist(HALF_ADDER_PORTS, cells)).unwrap();
+        let (names, rows) = truth_table(import);
+        assert_eq!(names, ["a", "b", "y[0]", "y[1]"]);
+        assert_eq!(
+            rows,
+            [[0, 0, 0, 0], [0, 1, 1, 0], [1, 0, 1, 0], [1, 1, 0, 1]]
+        );
+    }
+
+    #[test]
+    fn imports_compound_gates_and_constants() {
+        // y[0] is a mux picking b when a is high, and y[1] is tied high
+        let ports = r#"
+            "a": {"direction": "input", "bits": [2]},
+            "b": {"direction": "input", "bits": [3]},
+            "y": {"direction": "output", "bits": [4, "1"]}"#;
+        let cells = r#"
+            "$1": {"type": "$_MUX_", "connections": {"A": ["0"], "B": [3], "S": [2], "Y": [4]}}"#;
+        let (_, rows) = truth_table(import(&netlist(ports, cells)).unwrap());
+        assert_eq!(
+            rows,
+            [[0, 0, 0, 1], [0, 1, 0, 1], [1, 0, 0, 1], [1, 1, 1, 1]]
+        );
+    }
+
+    #[test]
+    fn leaves_out_word_level_cells() {
+        let cells = r#"
+            "$1": {"type": "$add", "connections": {"A": [2], "B": [3], "Y": [4, 5]}}"#;
+        let imported = import(&netlist(HALF_ADDER_PORTS, cells)).unwrap();
+        let names: Vec<String> = (0..imported.components.len())
+            .map(|i| i.to_string())
+            .collect();
+        let report = imported.report(&names);
+        assert!(report.contains("$add"), "{}", report);
+        assert!(report.contains("techmap"), "{}", report);
+        assert!(import("{}").is_err());
+    }
+}